@@ -0,0 +1,43 @@
+#!/bin/sh
+# ディスプレイの無い環境で、ウィンドウを表示するバックエンドのテストを実行します。
+#
+# X11: 仮想Xサーバー (Xvfb) 上で tests/x11.rs を実行します。
+# Wayland: ヘッドレスの sway (wlroots) 上で tests/wayland.rs を実行します。
+set -eu
+
+cd "$(dirname "$0")/.."
+cargo build --tests
+
+runtime=$(mktemp -d)
+cleanup() {
+    kill $xvfb $pid 2>/dev/null || true
+    rm -rf "$runtime"
+}
+xvfb=
+pid=
+trap cleanup EXIT
+
+# 使われていないディスプレイ番号を選ぶ
+Xvfb -displayfd 3 -screen 0 1024x768x24 3>"$runtime/display" &
+xvfb=$!
+for _ in $(seq 50); do
+    [ -s "$runtime/display" ] && break
+    sleep 0.1
+done
+DISPLAY=:$(cat "$runtime/display") cargo test --test x11 -- --ignored
+
+# wlr-layer-shell に対応したコンポジタが必要 (weston は非対応)
+XDG_RUNTIME_DIR=$runtime WLR_BACKENDS=headless WLR_HEADLESS_OUTPUTS=1 \
+    WLR_LIBINPUT_NO_DEVICES=1 WLR_RENDERER=pixman sway -c /dev/null &
+pid=$!
+socket=
+for _ in $(seq 50); do
+    socket=$(find "$runtime" -maxdepth 1 -name 'wayland-*' ! -name '*.lock' -printf '%f\n' | head -n 1)
+    [ -n "$socket" ] && break
+    sleep 0.1
+done
+if [ -z "$socket" ]; then
+    echo "sway did not start" >&2
+    exit 1
+fi
+XDG_RUNTIME_DIR=$runtime WAYLAND_DISPLAY=$socket cargo test --test wayland -- --ignored
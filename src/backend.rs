@@ -0,0 +1,103 @@
+//! 描画先 (ウィンドウシステムなど) の違いを吸収するための機能です。
+//!
+//! 各OS向けの実装は `win` や `x11` などのモジュールにあります。
+
+use crate::hit::{Mask, Rect};
+
+/// ポインター (マウスの左ボタン) の操作です。
+///
+/// 座標は画面上の位置 (左上が原点) です。
+/// ウィンドウの位置を扱えない描画先 ( `Backend::position` が None ) では、ウィンドウの左上を原点とします。
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PointerEvent {
+    /// ボタンを押しました。 (入力範囲を設定した場合は、その範囲の中でのみ発生します)
+    Press(i32, i32),
+    /// ボタンを押したまま移動しました。 (ウィンドウの外に出ても発生します)
+    Move(i32, i32),
+    /// ボタンを離しました。
+    Release(i32, i32),
+}
+
+/// キャラクターを表示するウィンドウを操作するためのトレイトです。
+///
+/// 画像は `0x00_RR_GG_BB` 形式の `u32` で扱い、
+/// 透過色 (キーカラー) と一致する画素は透明として表示されます。
+pub trait Backend {
+    /// メッセージの処理を行います。
+    ///
+    /// * 返り値がSomeだった場合の真理値はメッセージの取得有無を表します。
+    /// * 返り値がNoneだった場合はアプリケーションの終了を表します。
+    fn update(&mut self) -> Option<bool>;
+
+    /// 描画する画像のバッファを取得します。
+    fn image(&mut self) -> &mut [u32];
+
+    /// バッファの内容を画面に反映します。
+    fn draw(&mut self);
+
+    /// ウィンドウを表示します。
+    fn show(&mut self);
+
+    /// ウィンドウを隠します。
+    fn hide(&mut self);
+
+    /// 描画範囲の大きさを取得します。
+    fn size(&self) -> (u32, u32);
+
+    /// ウィンドウの画面上の位置 (左上の座標) を取得します。
+    ///
+    /// 位置を扱えない描画先では None を返します。
+    fn position(&self) -> Option<(i32, i32)> {
+        None
+    }
+
+    /// ウィンドウを配置できる範囲 (タスクバーなどを除いた画面の範囲) を取得します。
+    ///
+    /// 位置を扱えない描画先では None を返します。
+    fn work_area(&self) -> Option<Rect> {
+        None
+    }
+
+    /// 他のアプリケーションのウィンドウの画面上の範囲の一覧を取得します。 (キャラクターの足場に利用します)
+    ///
+    /// 他のウィンドウを扱えない描画先では常に空です。
+    fn other_windows(&self) -> Vec<Rect> {
+        Vec::new()
+    }
+
+    /// ウィンドウを画面上の指定した位置 (左上の座標) へ移動します。
+    ///
+    /// 位置を扱えない描画先では何もしません。
+    fn move_to(&mut self, _x: i32, _y: i32) {}
+
+    /// 前回の呼び出し以降に発生したポインターの操作を取り出します。
+    ///
+    /// ポインターを扱えない描画先では常に空です。
+    fn pointer_events(&mut self) -> Vec<PointerEvent> {
+        Vec::new()
+    }
+
+    /// ポインター (マウスカーソル) の画面上の位置を取得します。 (ウィンドウの外にある場合も含みます)
+    ///
+    /// 画面全体のポインターの位置を扱えない描画先では None を返します。
+    fn pointer_position(&self) -> Option<(i32, i32)> {
+        None
+    }
+
+    /// 吹き出しなどを表示するための、入力を受け付けない別のウィンドウを作成します。
+    ///
+    /// 作成したウィンドウは非表示です。別のウィンドウを作成できない描画先では None を返します。
+    fn overlay(&self, _width: u16, _height: u16) -> Option<Box<dyn Backend>> {
+        None
+    }
+
+    /// 入力を受け付ける範囲を、キャラクターの不透明な部分に限定します。 (範囲外のクリックは下のウィンドウに届きます)
+    ///
+    /// 入力範囲を設定できない描画先や、透過色の部分が元々クリックを透過する描画先では何もしません。
+    fn set_input_mask(&mut self, _mask: &Mask) {}
+
+    /// 描画する画像の元になった GIF のパレットを知らせます。
+    ///
+    /// パレットを利用しない描画先では何もしません。
+    fn set_palette(&mut self, _palette: &[u32]) {}
+}
@@ -0,0 +1,243 @@
+//! 待機中の行動を重み付きの抽選で選ぶための機能です。
+//!
+//! 行動ごとに選ばれやすさ (重み) 、一度選ばれてから再び選ばれるまでの時間 (クールダウン) 、
+//! 選ばれる時間帯を指定できます。乱数のシード値を指定すると、同じ結果を再現できます。
+
+use std::time::{Duration, Instant};
+
+use crate::{
+    random::Rng,
+    timer::{Clock, SystemClock, Time, TimeZone},
+};
+
+/// 1日の分数です。
+const MINUTES_PER_DAY: u32 = 24 * 60;
+
+/// 1日のうちの時間帯です。 (開始時刻を含み、終了時刻を含みません)
+///
+/// 開始時刻より終了時刻が前の場合は、日付をまたぐ時間帯として扱います。
+/// ```rust
+/// let night: bkrbk::behavior::TimeWindow = "22:00-06:00".parse().unwrap();
+/// assert!(night.contains(23 * 60));
+/// assert!(night.contains(5 * 60 + 59));
+/// assert!(!night.contains(6 * 60));
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TimeWindow {
+    /// 開始時刻 (0時0分からの分数)
+    pub start: u32,
+    /// 終了時刻 (0時0分からの分数)
+    pub end: u32,
+}
+
+impl TimeWindow {
+    /// 0時0分からの分数で表した時刻が、時間帯に含まれるかどうかを判定します。
+    pub fn contains(&self, minutes: u32) -> bool {
+        let minutes = minutes % MINUTES_PER_DAY;
+        match self.start <= self.end {
+            true => (self.start..self.end).contains(&minutes),
+            false => minutes >= self.start || minutes < self.end,
+        }
+    }
+}
+
+impl std::str::FromStr for TimeWindow {
+    type Err = String;
+
+    /// `HH:MM-HH:MM` 形式の文字列を受け付けます。 (終了時刻は `24:00` も指定できます)
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let minutes = |s: &str| {
+            let (hour, minute) = s.trim().split_once(':')?;
+            let (hour, minute): (u32, u32) = (hour.parse().ok()?, minute.parse().ok()?);
+            let minutes = hour * 60 + minute;
+            (minute < 60 && minutes <= MINUTES_PER_DAY).then_some(minutes)
+        };
+        s.split_once('-')
+            .and_then(|(start, end)| {
+                let start = minutes(start).filter(|start| *start < MINUTES_PER_DAY)?;
+                Some(Self {
+                    start,
+                    end: minutes(end)?,
+                })
+            })
+            .ok_or(format!("invalid time window: {}", s))
+    }
+}
+
+/// 抽選の候補となる行動です。
+#[derive(Debug, Clone, PartialEq)]
+pub struct Choice<E> {
+    /// 選ばれた際に返す値
+    pub event: E,
+    /// 選ばれやすさ (0以下の場合は選ばれません)
+    pub weight: f64,
+    /// 一度選ばれてから、再び選ばれるようになるまでの時間
+    pub cooldown: Duration,
+    /// 選ばれる時間帯 (None の場合は常に選ばれます)
+    pub window: Option<TimeWindow>,
+}
+
+impl<E> Choice<E> {
+    /// クールダウンや時間帯の指定が無い候補を作成します。
+    pub fn new(event: E, weight: f64) -> Self {
+        Self {
+            event,
+            weight,
+            cooldown: Duration::ZERO,
+            window: None,
+        }
+    }
+}
+
+/// 重み付きの抽選で行動を選ぶための構造体です。
+///
+/// ```rust
+/// use bkrbk::behavior::{Behaviors, Choice};
+/// use bkrbk::timer::{MockClock, TimeZone};
+/// use std::time::{Duration, UNIX_EPOCH};
+///
+/// let clock = MockClock::new();
+/// // 2024-06-07 12:00 (UTC)
+/// clock.set_system_time(UNIX_EPOCH + Duration::from_secs(1_717_761_600));
+/// let mut behaviors = Behaviors::with_clock(clock.clone(), 42);
+/// behaviors.set_timezone(TimeZone::UTC);
+/// behaviors.add(Choice::new("blink", 5.0));
+/// behaviors.add(Choice {
+///     cooldown: Duration::from_secs(60),
+///     ..Choice::new("jump", 1.0)
+/// });
+/// behaviors.add(Choice {
+///     window: Some("22:00-06:00".parse().unwrap()),
+///     ..Choice::new("sleep", 100.0)
+/// });
+///
+/// // 昼間は sleep が選ばれず、jump は一度選ばれると1分間選ばれない
+/// let picks: Vec<&str> = (0..200).filter_map(|_| behaviors.pick().copied()).collect();
+/// assert!(!picks.contains(&"sleep"));
+/// assert_eq!(picks.iter().filter(|p| **p == "jump").count(), 1);
+///
+/// // 同じシード値なら同じ順番で選ばれる
+/// let run = |seed| {
+///     let mut behaviors = Behaviors::with_clock(clock.clone(), seed);
+///     behaviors.add(Choice::new("blink", 5.0));
+///     behaviors.add(Choice::new("jump", 1.0));
+///     (0..20).map(|_| behaviors.pick().copied()).collect::<Vec<_>>()
+/// };
+/// assert_eq!(run(7), run(7));
+/// ```
+pub struct Behaviors<E, C: Clock = SystemClock> {
+    /// 候補と、最後に選ばれた時刻
+    choices: Vec<(Choice<E>, Option<Instant>)>,
+    /// 時間帯の判定に利用するタイムゾーン (None の場合はローカルタイムゾーン)
+    timezone: Option<TimeZone>,
+    clock: C,
+    rng: Rng,
+}
+
+impl<E> Default for Behaviors<E> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<E> Behaviors<E> {
+    /// Behaviors 構造体を初期化して返します。
+    pub fn new() -> Self {
+        Self {
+            choices: Vec::new(),
+            timezone: None,
+            clock: SystemClock,
+            rng: Rng::from_entropy(),
+        }
+    }
+}
+
+impl<E, C: Clock> Behaviors<E, C> {
+    /// 指定した時計と乱数のシード値を利用する Behaviors 構造体を初期化して返します。
+    pub fn with_clock(clock: C, seed: u64) -> Self {
+        Self {
+            choices: Vec::new(),
+            timezone: None,
+            clock,
+            rng: Rng::new(seed),
+        }
+    }
+
+    /// 時間帯の判定に利用するタイムゾーンを設定します。
+    pub fn set_timezone(&mut self, timezone: TimeZone) {
+        self.timezone = Some(timezone);
+    }
+
+    /// 候補を追加し、その番号を返します。
+    pub fn add(&mut self, choice: Choice<E>) -> usize {
+        self.choices.push((choice, None));
+        self.choices.len() - 1
+    }
+
+    /// 候補の一覧を取得します。
+    pub fn choices(&self) -> impl Iterator<Item = &Choice<E>> {
+        self.choices.iter().map(|(choice, _)| choice)
+    }
+
+    /// 候補の数を取得します。
+    pub fn len(&self) -> usize {
+        self.choices.len()
+    }
+
+    /// 候補が1つもないかどうかを判定します。
+    pub fn is_empty(&self) -> bool {
+        self.choices.is_empty()
+    }
+
+    /// 現在の時刻を、0時0分からの分数で取得します。
+    fn minutes_of_day(&self) -> u32 {
+        let time = Time::from_system_time(self.clock.system_time());
+        let time = match self.timezone {
+            Some(timezone) => time.with_timezone(timezone),
+            None => time,
+        };
+        (time.time_of_day().as_secs() / 60) as u32
+    }
+
+    /// 現在選ばれる可能性のある候補の番号を取得します。
+    pub fn available(&self) -> Vec<usize> {
+        let now = self.clock.now();
+        let minutes = self.minutes_of_day();
+        self.choices
+            .iter()
+            .enumerate()
+            .filter(|(_, (choice, last))| {
+                let cooled = last.is_none_or(|last| now >= last + choice.cooldown);
+                let in_window = choice.window.is_none_or(|w| w.contains(minutes));
+                choice.weight > 0.0 && cooled && in_window
+            })
+            .map(|(i, _)| i)
+            .collect()
+    }
+
+    /// 重み付きの抽選で候補を1つ選びます。
+    ///
+    /// 選ばれる候補が1つもない場合は None を返します。
+    pub fn pick(&mut self) -> Option<&E> {
+        let available = self.available();
+        let total: f64 = available.iter().map(|i| self.choices[*i].0.weight).sum();
+        if available.is_empty() || total <= 0.0 {
+            return None;
+        }
+        let mut point = self.rng.next_f64() * total;
+        // 浮動小数点の誤差で最後まで残った場合は最後の候補を選ぶ
+        let mut picked = available[available.len() - 1];
+        for i in available {
+            let weight = self.choices[i].0.weight;
+            if point < weight {
+                picked = i;
+                break;
+            }
+            point -= weight;
+        }
+        let now = self.clock.now();
+        let (choice, last) = &mut self.choices[picked];
+        *last = Some(now);
+        Some(&choice.event)
+    }
+}
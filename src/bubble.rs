@@ -0,0 +1,474 @@
+//! キャラクターの吹き出しにセリフを表示するための機能です。
+//!
+//! 文字は内蔵のビットマップフォント (`font` モジュール) か、TrueType/OpenType のフォントファイルで描画します。
+//! 日本語のように単語を空白で区切らない文章も、行頭・行末の禁則を守って折り返します。
+//! セリフは1文字ずつ表示し、全て表示してから一定時間で消えます。
+
+use std::{
+    path::Path,
+    time::{Duration, Instant},
+};
+
+use ab_glyph::{Font as _, FontVec, PxScale, ScaleFont as _};
+
+use crate::{
+    color::Color,
+    font,
+    timer::{Clock, SystemClock},
+};
+
+/// 文字と枠の色です。
+pub const TEXT_COLOR: u32 = 0x00_20_20_20;
+/// 吹き出しの背景色です。
+pub const BACKGROUND: u32 = 0x00_FF_FF_F0;
+/// 1秒あたりに表示する文字数のデフォルト値です。
+pub const DEFAULT_TYPING: f64 = 30.0;
+/// 1秒あたりに表示する文字数の最小値です。 (0 を除きます)
+pub const MIN_TYPING: f64 = 0.1;
+
+/// 枠と文字の間の余白 (画素)
+const PADDING: usize = 6;
+/// 角の丸みの半径 (画素)
+const RADIUS: usize = 4;
+/// 吹き出しの下に付ける三角形の高さ (画素)
+const TAIL: usize = 8;
+
+/// 行頭に置かない文字です。 (句読点、閉じ括弧、小書きの仮名など)
+const NO_START: &str = "、。，．・：；？！ー～…‥）」』】〉》〕］｝ぁぃぅぇぉっゃゅょゎァィゥェォッャュョヮヵヶ,.:;!?)]}";
+/// 行末に置かない文字です。 (開き括弧)
+const NO_END: &str = "（「『【〈《〔［｛([{";
+
+#[derive(Debug)]
+pub enum Error {
+    /// フォントファイルを読み込めませんでした。
+    Io(std::io::Error),
+    /// 対応していない形式のフォントファイルです。
+    InvalidFont,
+}
+
+impl std::fmt::Display for Error {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Io(e) => write!(f, "{}", e),
+            Self::InvalidFont => write!(f, "unsupported or invalid font file"),
+        }
+    }
+}
+
+impl std::error::Error for Error {}
+
+impl From<std::io::Error> for Error {
+    fn from(e: std::io::Error) -> Self {
+        Self::Io(e)
+    }
+}
+
+/// 文字を描画するフォントです。
+#[derive(Debug)]
+pub enum Font {
+    /// 内蔵のビットマップフォント (英大文字と数字、一部の記号のみ) を指定した倍率で拡大します。
+    ///
+    /// 日本語などの文字は描画できません。表示するには `Font::load` (コマンドラインでは `--font`) でフォントファイルを指定してください。
+    Bitmap { scale: usize },
+    /// フォントファイルの文字を指定した大きさ (画素) で描画します。
+    Outline { font: FontVec, size: f32 },
+}
+
+impl Font {
+    /// 内蔵のビットマップフォントを指定した倍率で利用します。
+    pub fn bitmap(scale: usize) -> Self {
+        Self::Bitmap {
+            scale: scale.max(1),
+        }
+    }
+
+    /// TrueType/OpenType のフォントファイルを読み込み、指定した大きさ (画素) で利用します。
+    pub fn load(path: impl AsRef<Path>, size: f32) -> Result<Self, Error> {
+        let data = std::fs::read(path)?;
+        let font = FontVec::try_from_vec(data).map_err(|_| Error::InvalidFont)?;
+        Ok(Self::Outline { font, size })
+    }
+
+    /// 文章のうち、フォントに含まれていないために描画できない文字の一覧を取得します。 (空白は除きます)
+    ///
+    /// ```rust
+    /// use bkrbk::bubble::Font;
+    ///
+    /// let font = Font::bitmap(1);
+    /// assert_eq!(font.missing("Hello, 世界世界"), ['世', '界']);
+    /// ```
+    pub fn missing(&self, text: &str) -> Vec<char> {
+        let mut result = Vec::new();
+        for c in text.chars().filter(|c| !c.is_whitespace()) {
+            let found = match self {
+                Self::Bitmap { .. } => font::glyph(c.to_uppercase().next().unwrap_or(c)).is_some(),
+                Self::Outline { font, .. } => font.glyph_id(c).0 != 0,
+            };
+            if !found && !result.contains(&c) {
+                result.push(c);
+            }
+        }
+        result
+    }
+
+    /// 文字を描画した際に進む幅 (画素) を取得します。
+    pub fn advance(&self, c: char) -> usize {
+        match self {
+            Self::Bitmap { scale } => (font::GLYPH_WIDTH + 1) * scale,
+            Self::Outline { font, size } => {
+                let font = font.as_scaled(PxScale::from(*size));
+                font.h_advance(font.glyph_id(c)).round() as usize
+            }
+        }
+    }
+
+    /// 1行の高さ (画素) を取得します。
+    pub fn line_height(&self) -> usize {
+        match self {
+            Self::Bitmap { scale } => (font::GLYPH_HEIGHT + 1) * scale,
+            Self::Outline { font, size } => {
+                let font = font.as_scaled(PxScale::from(*size));
+                (font.height() + font.line_gap()).ceil() as usize
+            }
+        }
+    }
+
+    /// 文字列の幅 (画素) を取得します。
+    fn width(&self, text: &str) -> usize {
+        text.chars().map(|c| self.advance(c)).sum()
+    }
+
+    /// `width` 列の画像の指定した位置 (行の左上) に1文字描画します。
+    ///
+    /// フォントファイルの文字は、描画先の色と混ぜて滑らかにします。
+    fn draw(&self, image: &mut [u32], width: usize, (x, y): (usize, usize), c: char, color: u32) {
+        match self {
+            Self::Bitmap { scale } => {
+                let text = c.to_uppercase().next().unwrap_or(c).to_string();
+                font::draw_text(image, width, x, y, &text, color, *scale);
+            }
+            Self::Outline { font, size } => {
+                let scaled = font.as_scaled(PxScale::from(*size));
+                let glyph = scaled.glyph_id(c).with_scale_and_position(
+                    *size,
+                    ab_glyph::point(x as f32, y as f32 + scaled.ascent()),
+                );
+                let Some(outline) = font.outline_glyph(glyph) else {
+                    return;
+                };
+                let bounds = outline.px_bounds();
+                let height = image.len() / width;
+                outline.draw(|gx, gy, coverage| {
+                    let px = bounds.min.x as i32 + gx as i32;
+                    let py = bounds.min.y as i32 + gy as i32;
+                    if px < 0 || py < 0 || px as usize >= width || py as usize >= height {
+                        return;
+                    }
+                    let pixel = &mut image[py as usize * width + px as usize];
+                    let alpha = (coverage.clamp(0.0, 1.0) * 255.0) as u8;
+                    *pixel = Color::new(*pixel).alpha_blend(alpha, color);
+                });
+            }
+        }
+    }
+}
+
+/// 全角の文字 (漢字、仮名、ハングル、全角記号など) かどうかを判定します。 (どの文字の間でも折り返せます)
+fn is_wide(c: char) -> bool {
+    matches!(c,
+        '\u{1100}'..='\u{115F}'
+        | '\u{2E80}'..='\u{A4CF}'
+        | '\u{AC00}'..='\u{D7A3}'
+        | '\u{F900}'..='\u{FAFF}'
+        | '\u{FE30}'..='\u{FE4F}'
+        | '\u{FF00}'..='\u{FF60}'
+        | '\u{FFE0}'..='\u{FFE6}'
+        | '\u{20000}'..='\u{3FFFD}')
+}
+
+/// 文章を、間で折り返すことのできる単位 (前に空白があるかどうかと文字列) に分けます。
+///
+/// 半角の文字は空白までを1つにまとめ、全角の文字は1文字ずつに分けます。
+/// 行頭に置かない文字は前に、行末に置かない文字の次の文字はその後ろに付けます。
+fn tokenize(paragraph: &str) -> Vec<(bool, String)> {
+    let mut tokens: Vec<(bool, String)> = Vec::new();
+    let mut space = false;
+    // 直前の文字が半角の文字で、次の半角の文字を同じ単位にまとめるかどうか
+    let mut word = false;
+    for c in paragraph.chars() {
+        if c.is_whitespace() {
+            space = true;
+            word = false;
+            continue;
+        }
+        let joined = !space
+            && tokens.last().is_some_and(|(_, last)| {
+                word || NO_START.contains(c) || last.ends_with(|c| NO_END.contains(c))
+            });
+        match tokens.last_mut().filter(|_| joined) {
+            Some((_, last)) => last.push(c),
+            None => tokens.push((space, c.to_string())),
+        }
+        space = false;
+        word = !is_wide(c);
+    }
+    tokens
+}
+
+/// 文章を指定した幅 (画素) に収まるように折り返します。
+///
+/// 半角の文字は単語単位で、全角の文字は1文字単位で折り返し、行頭・行末の禁則を守ります。
+/// 1単語で幅を超える場合は、単語の途中で折り返します。 `advance` は各文字の幅を返す関数です。
+/// ```rust
+/// use bkrbk::bubble::wrap;
+///
+/// // 全ての文字を 10px として 30px に収める
+/// let advance = |_| 10;
+/// assert_eq!(wrap("こんにちは、世界", 30, advance), ["こんに", "ちは、", "世界"]);
+/// assert_eq!(wrap("「はい」と言った", 30, advance), ["「は", "い」と", "言った"]);
+/// assert_eq!(wrap("hi there\nbye", 50, advance), ["hi", "there", "bye"]);
+/// assert_eq!(wrap("Rustで書く", 40, advance), ["Rust", "で書く"]);
+/// ```
+pub fn wrap(text: &str, max_width: usize, advance: impl Fn(char) -> usize) -> Vec<String> {
+    let space_width = advance(' ');
+    let mut lines = Vec::new();
+    for paragraph in text.lines() {
+        let mut line = String::new();
+        let mut used = 0;
+        for (space, token) in tokenize(paragraph) {
+            let token_width: usize = token.chars().map(&advance).sum();
+            let gap = match space && used > 0 {
+                true => space_width,
+                false => 0,
+            };
+            if used > 0 && used + gap + token_width > max_width {
+                lines.push(std::mem::take(&mut line));
+                used = 0;
+            } else if gap > 0 {
+                line.push(' ');
+                used += gap;
+            }
+            for c in token.chars() {
+                let width = advance(c);
+                if used > 0 && used + width > max_width {
+                    lines.push(std::mem::take(&mut line));
+                    used = 0;
+                }
+                line.push(c);
+                used += width;
+            }
+        }
+        lines.push(line);
+    }
+    lines
+}
+
+/// 描画した吹き出しの画像です。
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Image {
+    /// `width` 列の画像 (吹き出しの外は透過色)
+    pub pixels: Vec<u32>,
+    pub width: usize,
+    pub height: usize,
+}
+
+/// 1つのセリフを表示する吹き出しです。
+///
+/// ```rust
+/// use bkrbk::bubble::{Bubble, Font, TEXT_COLOR};
+/// use bkrbk::timer::MockClock;
+/// use std::time::Duration;
+///
+/// // 1秒に10文字ずつ表示し、全て表示してから1秒後に消える
+/// let clock = MockClock::new();
+/// let bubble = Bubble::with_clock("HELLO", 10.0, Duration::from_secs(1), clock.clone());
+/// assert_eq!(bubble.visible(), 1);
+/// clock.advance(Duration::from_millis(250));
+/// assert_eq!(bubble.visible(), 3);
+/// assert!(bubble.is_typing());
+/// clock.advance(Duration::from_millis(250));
+/// assert!(!bubble.is_typing());
+/// assert_eq!(bubble.duration(), Duration::from_millis(1400));
+/// clock.advance(Duration::from_millis(800));
+/// assert!(!bubble.is_finished());
+/// clock.advance(Duration::from_millis(100));
+/// assert!(bubble.is_finished());
+///
+/// // 遅すぎる速さは `MIN_TYPING` として扱う
+/// let bubble = Bubble::with_clock("HI", 1e-300, Duration::ZERO, clock.clone());
+/// assert_eq!(bubble.duration(), Duration::from_secs(10));
+///
+/// // 下に三角形の付いた枠の中に文字を描画する
+/// let bg = 0x00_00_00_FF;
+/// let image = bubble.render(&Font::bitmap(1), 100, bg);
+/// assert!(image.width < 100);
+/// assert!(image.pixels.contains(&TEXT_COLOR));
+/// assert_eq!(image.pixels[0], bg);
+/// ```
+#[derive(Debug, Clone)]
+pub struct Bubble<C: Clock = SystemClock> {
+    text: String,
+    /// 1文字を表示する間隔 (0 の場合は一度に全て表示します)
+    interval: Duration,
+    /// 全て表示してから消えるまでの時間
+    hold: Duration,
+    /// 表示を始めた時刻
+    started: Instant,
+    clock: C,
+}
+
+impl Bubble {
+    /// 1秒あたりに `typing` 文字ずつ表示し、全て表示してから `hold` だけ経つと消える吹き出しを作成します。
+    ///
+    /// `typing` が 0 の場合は一度に全て表示し、 `MIN_TYPING` より遅い場合は `MIN_TYPING` として扱います。
+    pub fn new(text: &str, typing: f64, hold: Duration) -> Self {
+        Self::with_clock(text, typing, hold, SystemClock)
+    }
+}
+
+impl<C: Clock> Bubble<C> {
+    /// 指定した時計を利用する吹き出しを作成します。
+    pub fn with_clock(text: &str, typing: f64, hold: Duration, clock: C) -> Self {
+        let interval = match typing > 0.0 && typing.is_finite() {
+            true => Duration::try_from_secs_f64(1.0 / typing.max(MIN_TYPING)).unwrap_or_default(),
+            false => Duration::ZERO,
+        };
+        Self {
+            text: text.to_string(),
+            interval,
+            hold,
+            started: clock.now(),
+            clock,
+        }
+    }
+
+    /// セリフを取得します。
+    pub fn text(&self) -> &str {
+        &self.text
+    }
+
+    /// 表示する文字 (空白を除く) の数を取得します。
+    fn count(&self) -> usize {
+        self.text.chars().filter(|c| !c.is_whitespace()).count()
+    }
+
+    /// `count` 文字目を表示し終えるまでの時間を取得します。
+    fn typed_at(&self, count: usize) -> Duration {
+        let count = u32::try_from(count).unwrap_or(u32::MAX);
+        self.interval.checked_mul(count).unwrap_or(Duration::MAX)
+    }
+
+    /// 全て表示し終えるまでの時間を取得します。
+    fn typing_time(&self) -> Duration {
+        self.typed_at(self.count().saturating_sub(1))
+    }
+
+    /// 表示を始めてから消えるまでの時間を取得します。
+    pub fn duration(&self) -> Duration {
+        self.typing_time() + self.hold
+    }
+
+    /// 表示している文字 (空白を除く) の数を取得します。
+    pub fn visible(&self) -> usize {
+        if self.interval.is_zero() {
+            return self.count();
+        }
+        let elapsed = self.clock.now().saturating_duration_since(self.started);
+        let typed = elapsed.as_nanos() / self.interval.as_nanos() + 1;
+        (typed as usize).min(self.count())
+    }
+
+    /// まだ表示していない文字があるかどうかを判定します。
+    pub fn is_typing(&self) -> bool {
+        self.visible() < self.count()
+    }
+
+    /// 消える時刻を過ぎたかどうかを判定します。
+    pub fn is_finished(&self) -> bool {
+        self.clock.now() >= self.started + self.duration()
+    }
+
+    /// 次に表示が変わる (文字が増える、または消える) 時刻を取得します。 (消えた後は None)
+    pub fn next_change(&self) -> Option<Instant> {
+        if self.is_typing() {
+            return Some(self.started + self.typed_at(self.visible()));
+        }
+        (!self.is_finished()).then(|| self.started + self.duration())
+    }
+
+    /// 吹き出しを描画します。
+    ///
+    /// 幅が `max_width` に収まるようにセリフを折り返し、表示している文字だけを描画します。
+    /// 折り返しはセリフ全体で決めるので、文字が増えても吹き出しの大きさは変わりません。
+    pub fn render(&self, font: &Font, max_width: usize, bg: u32) -> Image {
+        let lines = wrap(&self.text, max_width.saturating_sub(PADDING * 2 + 2), |c| {
+            font.advance(c)
+        });
+        let line_height = font.line_height();
+        let content = lines.iter().map(|line| font.width(line)).max().unwrap_or(0);
+        let width = (content + PADDING * 2 + 2).min(max_width.max(RADIUS * 2 + 2));
+        let box_height = lines.len() * line_height + PADDING * 2 + 2;
+        let height = box_height + TAIL;
+        let mut pixels = vec![bg; width * height];
+
+        // 角の丸い枠
+        for y in 0..box_height {
+            for x in 0..width {
+                // 角の中心からの距離で、枠の外・枠線・内側を決める
+                let dx = RADIUS
+                    .saturating_sub(x)
+                    .max((x + RADIUS + 1).saturating_sub(width));
+                let dy = RADIUS
+                    .saturating_sub(y)
+                    .max((y + RADIUS + 1).saturating_sub(box_height));
+                let distance = ((dx * dx + dy * dy) as f64).sqrt();
+                pixels[y * width + x] = match distance {
+                    d if d > RADIUS as f64 + 0.5 => bg,
+                    d if d > RADIUS as f64 - 0.5 => TEXT_COLOR,
+                    _ => BACKGROUND,
+                };
+            }
+        }
+        // 下の中央に付ける三角形 (枠の下辺の内側を塗り潰して繋げる)
+        let center = width / 2;
+        for t in 0..=TAIL {
+            let y = box_height - 1 + t;
+            let (left, right) = (center.saturating_sub(TAIL - t), center + TAIL - t);
+            for x in left..=right.min(width - 1) {
+                let edge = x == left || x == right || t == TAIL;
+                pixels[y * width + x] = match edge {
+                    true => TEXT_COLOR,
+                    false => BACKGROUND,
+                };
+            }
+        }
+
+        // 表示している文字だけを描画する
+        let mut remaining = self.visible();
+        'lines: for (i, line) in lines.iter().enumerate() {
+            let mut x = PADDING + 1;
+            let y = PADDING + 1 + i * line_height;
+            for c in line.chars() {
+                if !c.is_whitespace() {
+                    if remaining == 0 {
+                        break 'lines;
+                    }
+                    remaining -= 1;
+                    font.draw(
+                        &mut pixels[..box_height * width],
+                        width,
+                        (x, y),
+                        c,
+                        TEXT_COLOR,
+                    );
+                }
+                x += font.advance(c);
+            }
+        }
+        Image {
+            pixels,
+            width,
+            height,
+        }
+    }
+}
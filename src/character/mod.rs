@@ -0,0 +1,526 @@
+//! アニメーションや効果音、振る舞いをまとめたキャラクターのパッケージを扱うための機能です。
+//!
+//! パッケージはディレクトリまたは ZIP ファイルで、最上位 (ZIP の場合は1階層下でも可) に
+//! マニフェスト ( `character.txt` ) を置きます。
+//!
+//! ```text
+//! name = Kuro
+//!
+//! [animation idle]
+//! file = child.gif
+//!
+//! [animation jump]
+//! file = jump.gif
+//! key = #00FF00
+//! scale = 2
+//! sound 3 = thud
+//!
+//! [sound thud]
+//! file = thud.wav
+//!
+//! [state idle]
+//! animation = idle
+//! on click = jump
+//!
+//! [state jump]
+//! animation = jump
+//! mode = once
+//! on finished = idle
+//! ```
+//!
+//! * `[animation <名前>]` : `file` (必須) 、透過色として扱う色 `key` 、拡大率 `scale` (1-8) 、
+//!   フレームに結び付けた効果音 `sound <フレームの番号 | last | complete> = <効果音の名前>`
+//! * `[sound <名前>]` : `file` (必須)
+//! * `[state <名前>]` : `state` モジュールの状態と同じ項目 ( `animation` にはアニメーションの名前を指定します)
+
+mod zip;
+
+use std::{collections::HashMap, path::Path};
+
+use crate::{
+    img::Gif,
+    player::Cue,
+    render::Frame,
+    state::{self, States},
+};
+
+/// マニフェストのファイル名です。
+pub const MANIFEST: &str = "character.txt";
+
+/// 拡大率の最大値です。
+const MAX_SCALE: usize = 8;
+
+/// パッケージの読み込みで発生するエラーです。
+#[derive(Debug)]
+pub enum Error {
+    /// ファイルの読み込みに失敗しました。
+    Io(std::io::Error),
+    /// ZIP ファイルの展開に失敗しました。
+    Zip(String),
+    /// マニフェストの内容に問題があります。 (見つかった全ての問題)
+    Invalid(Vec<Problem>),
+}
+
+impl std::fmt::Display for Error {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Io(e) => write!(f, "{}", e),
+            Self::Zip(e) => write!(f, "{}", e),
+            Self::Invalid(problems) => {
+                let lines: Vec<String> = problems.iter().map(|p| p.to_string()).collect();
+                write!(f, "{}", lines.join("\n"))
+            }
+        }
+    }
+}
+
+impl std::error::Error for Error {}
+
+impl From<std::io::Error> for Error {
+    fn from(value: std::io::Error) -> Self {
+        Self::Io(value)
+    }
+}
+
+/// マニフェストの問題です。
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Problem {
+    /// 問題のあるファイル
+    pub file: String,
+    /// 問題のある行 (1から始まります)
+    pub line: usize,
+    /// 問題の内容
+    pub message: String,
+}
+
+impl std::fmt::Display for Problem {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}:{}: {}", self.file, self.line, self.message)
+    }
+}
+
+/// パッケージに含まれるアニメーションです。
+pub struct Animation {
+    /// 名前
+    pub name: String,
+    /// パッケージ内のファイルパス
+    pub file: String,
+    pub gif: Gif,
+    /// 透過色として扱う色 ( `0x00_RR_GG_BB` 形式)
+    pub key: Option<u32>,
+    /// 拡大率
+    pub scale: usize,
+    /// フレームに結び付けた効果音 (タイミング, 効果音の名前)
+    pub sounds: Vec<(Cue, String)>,
+}
+
+impl Animation {
+    /// 拡大後の大きさを取得します。 (フレームが1つも無い場合は None を返します)
+    pub fn size(&self) -> Option<(u16, u16)> {
+        let (width, height) = self.gif.size()?;
+        let scale = self.scale as u16;
+        Some((width * scale, height * scale))
+    }
+
+    /// 全てのフレームを合成します。
+    ///
+    /// 透過色と一致する画素は `bg` に置き換え、拡大率に合わせて拡大します。
+    pub fn frames(&self, bg: u32) -> Vec<Frame> {
+        let Some((width, _)) = self.gif.size() else {
+            return Vec::new();
+        };
+        let (width, scale) = (width as usize, self.scale);
+        crate::render::frames(&self.gif, bg)
+            .into_iter()
+            .map(|frame| {
+                let mut image = Vec::with_capacity(frame.image.len() * scale * scale);
+                for row in frame.image.chunks_exact(width) {
+                    let row: Vec<u32> = row
+                        .iter()
+                        .map(|pixel| match Some(*pixel) == self.key {
+                            true => bg,
+                            false => *pixel,
+                        })
+                        .flat_map(|pixel| std::iter::repeat_n(pixel, scale))
+                        .collect();
+                    for _ in 0..scale {
+                        image.extend_from_slice(&row);
+                    }
+                }
+                Frame { image, ..frame }
+            })
+            .collect()
+    }
+}
+
+/// パッケージに含まれる効果音です。
+pub struct Sound {
+    /// 名前
+    pub name: String,
+    /// パッケージ内のファイルパス
+    pub file: String,
+    /// ファイルの内容
+    pub data: Vec<u8>,
+}
+
+/// キャラクターのパッケージです。
+///
+/// ```rust
+/// use std::collections::HashMap;
+///
+/// let manifest = "name = Kuro\n[animation jump]\nfile = jump.gif\nscale = 2\n[state jump]\nanimation = jump\n";
+/// let files = HashMap::from([
+///     ("character.txt".to_string(), manifest.as_bytes().to_vec()),
+///     ("jump.gif".to_string(), std::fs::read("share/jump.gif").unwrap()),
+/// ]);
+/// let character = bkrbk::character::Character::from_files(files).unwrap();
+/// assert_eq!(character.name, "Kuro");
+/// assert_eq!(character.animation("jump").unwrap().size(), Some((320, 360)));
+///
+/// // 全ての問題を行番号付きで報告する
+/// let manifest = "[animation jump]\nfile = missing.gif\nscale = 0\n[state idle]\nanimation = walk\n";
+/// let files = HashMap::from([("character.txt".to_string(), manifest.as_bytes().to_vec())]);
+/// let error = bkrbk::character::Character::from_files(files).err().unwrap();
+/// assert_eq!(
+///     error.to_string(),
+///     "character.txt:3: invalid scale: 0\n\
+///      character.txt:2: file not found: missing.gif\n\
+///      character.txt:5: unknown animation: walk"
+/// );
+///
+/// // パッケージの外を指すパスは受け付けない
+/// let dir = std::env::temp_dir().join(format!("bkrbk-doctest-{}", std::process::id()));
+/// std::fs::create_dir_all(&dir).unwrap();
+/// let manifest = "[sound ../../tmp/escaped]\nfile = ../secret.wav\n[animation jump]\nfile = /etc/passwd\n";
+/// std::fs::write(dir.join("character.txt"), manifest).unwrap();
+/// let error = bkrbk::character::Character::load(&dir).err().unwrap().to_string();
+/// std::fs::remove_dir_all(&dir).unwrap();
+/// assert!(error.contains("character.txt:1: invalid sound name: ../../tmp/escaped"));
+/// assert!(error.contains("character.txt:2: invalid file: ../secret.wav"));
+/// assert!(error.contains("character.txt:4: invalid file: /etc/passwd"));
+/// ```
+pub struct Character {
+    /// 名前 (マニフェストで指定していない場合は空)
+    pub name: String,
+    pub animations: Vec<Animation>,
+    pub sounds: Vec<Sound>,
+    /// 振る舞い (各状態の `animation` はアニメーションの名前です)
+    pub states: States,
+}
+
+/// パッケージ内のファイルの読み込み元です。
+enum Source<'a> {
+    /// ディレクトリ
+    Directory(&'a Path),
+    /// 展開済みのファイル (パス, 内容) と、マニフェストのあるディレクトリ
+    Files(HashMap<String, Vec<u8>>, String),
+}
+
+impl Source<'_> {
+    /// パッケージ内のファイルを読み込みます。 (見つからない場合は None を返します)
+    fn read(&self, file: &str) -> Option<Vec<u8>> {
+        match self {
+            // パッケージの外を指すパスは読み込まない
+            _ if !is_package_path(file) => None,
+            Self::Directory(path) => std::fs::read(path.join(file)).ok(),
+            Self::Files(files, prefix) => files.get(&format!("{}{}", prefix, file)).cloned(),
+        }
+    }
+}
+
+/// 読み込み途中のアニメーションです。
+struct AnimationEntry {
+    name: String,
+    line: usize,
+    file: Option<(usize, String)>,
+    key: Option<u32>,
+    scale: usize,
+    /// (行番号, タイミング, 効果音の名前)
+    sounds: Vec<(usize, Cue, String)>,
+}
+
+/// 読み込み途中の効果音です。
+struct SoundEntry {
+    name: String,
+    line: usize,
+    file: Option<(usize, String)>,
+}
+
+/// 読み込み中のセクションです。
+enum Section {
+    Top,
+    Animation,
+    Sound,
+    State,
+}
+
+impl Character {
+    /// ディレクトリまたは ZIP ファイルからパッケージを読み込みます。
+    pub fn load(path: &Path) -> Result<Self, Error> {
+        if path.is_dir() {
+            let manifest = std::fs::read_to_string(path.join(MANIFEST))?;
+            return Self::parse(&manifest, Source::Directory(path));
+        }
+        let files = zip::extract(&std::fs::read(path)?).map_err(Error::Zip)?;
+        Self::from_files(files)
+    }
+
+    /// 展開済みのファイル (パッケージ内のパス, 内容) からパッケージを読み込みます。
+    pub fn from_files(files: HashMap<String, Vec<u8>>) -> Result<Self, Error> {
+        // ZIP ファイルの中でディレクトリにまとめている場合は、その中のマニフェストを探す
+        let manifest = files
+            .keys()
+            .filter(|name| {
+                let depth = name.matches('/').count();
+                *name == MANIFEST || (depth == 1 && name.ends_with(&format!("/{}", MANIFEST)))
+            })
+            .min_by_key(|name| name.len())
+            .cloned()
+            .ok_or_else(|| {
+                Error::Io(std::io::Error::new(
+                    std::io::ErrorKind::NotFound,
+                    format!("{} not found", MANIFEST),
+                ))
+            })?;
+        let text = String::from_utf8_lossy(&files[&manifest]).into_owned();
+        let prefix = manifest.trim_end_matches(MANIFEST).to_string();
+        Self::parse(&text, Source::Files(files, prefix))
+    }
+
+    /// マニフェストを読み込み、参照しているファイルを読み込みます。
+    fn parse(text: &str, source: Source) -> Result<Self, Error> {
+        let mut problems = Vec::new();
+        let mut problem = |line: usize, message: String| {
+            problems.push(Problem {
+                file: MANIFEST.to_string(),
+                line,
+                message,
+            })
+        };
+        let mut name = String::new();
+        let mut animations: Vec<AnimationEntry> = Vec::new();
+        let mut sounds: Vec<SoundEntry> = Vec::new();
+        // 状態の定義は `state` モジュールの形式に直して読み込む
+        let mut state_lines: Vec<(usize, String)> = Vec::new();
+        // 状態で指定したアニメーションの名前と、その行番号
+        let mut state_animations: Vec<(usize, String)> = Vec::new();
+        let mut section = Section::Top;
+
+        for (i, line) in text.lines().enumerate() {
+            let number = i + 1;
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            if let Some(header) = line.strip_prefix('[').and_then(|l| l.strip_suffix(']')) {
+                let (kind, section_name) = header.trim().split_once(' ').unwrap_or((header, ""));
+                let section_name = section_name.trim().to_string();
+                let known = ["animation", "sound", "state"].contains(&kind);
+                if known && section_name.is_empty() {
+                    problem(number, format!("missing name: {}", line));
+                }
+                section = match kind {
+                    "animation" => {
+                        if animations.iter().any(|a| a.name == section_name) {
+                            problem(number, format!("duplicate animation: {}", section_name));
+                        }
+                        animations.push(AnimationEntry {
+                            name: section_name,
+                            line: number,
+                            file: None,
+                            key: None,
+                            scale: 1,
+                            sounds: Vec::new(),
+                        });
+                        Section::Animation
+                    }
+                    "sound" => {
+                        if !section_name.is_empty()
+                            && (!is_package_path(&section_name) || section_name.contains('/'))
+                        {
+                            problem(number, format!("invalid sound name: {}", section_name));
+                        }
+                        if sounds.iter().any(|s| s.name == section_name) {
+                            problem(number, format!("duplicate sound: {}", section_name));
+                        }
+                        sounds.push(SoundEntry {
+                            name: section_name,
+                            line: number,
+                            file: None,
+                        });
+                        Section::Sound
+                    }
+                    "state" => {
+                        state_lines.push((number, format!("[{}]", section_name)));
+                        Section::State
+                    }
+                    _ => {
+                        problem(number, format!("unknown section: {}", kind));
+                        Section::Top
+                    }
+                };
+                continue;
+            }
+
+            let Some((key, value)) = line.split_once('=').map(|(k, v)| (k.trim(), v.trim())) else {
+                problem(number, format!("invalid entry: {}", line));
+                continue;
+            };
+            match (&section, animations.last_mut(), sounds.last_mut()) {
+                (Section::Top, _, _) => match key {
+                    "name" => name = value.to_string(),
+                    _ => problem(number, format!("unknown key: {}", key)),
+                },
+                (Section::Animation, Some(animation), _) => match key {
+                    "file" if !is_package_path(value) => {
+                        problem(number, format!("invalid file: {}", value))
+                    }
+                    "file" => animation.file = Some((number, value.to_string())),
+                    "key" => match parse_color(value) {
+                        Some(color) => animation.key = Some(color),
+                        None => problem(number, format!("invalid color: {}", value)),
+                    },
+                    "scale" => match value.parse() {
+                        Ok(scale) if (1..=MAX_SCALE).contains(&scale) => animation.scale = scale,
+                        _ => problem(number, format!("invalid scale: {}", value)),
+                    },
+                    _ => match key.strip_prefix("sound ").map(|cue| cue.trim().parse()) {
+                        Some(Ok(cue)) => animation.sounds.push((number, cue, value.to_string())),
+                        Some(Err(e)) => problem(number, e),
+                        None => problem(number, format!("unknown key: {}", key)),
+                    },
+                },
+                (Section::Sound, _, Some(sound)) => match key {
+                    "file" if !is_package_path(value) => {
+                        problem(number, format!("invalid file: {}", value))
+                    }
+                    "file" => sound.file = Some((number, value.to_string())),
+                    _ => problem(number, format!("unknown key: {}", key)),
+                },
+                (Section::State, _, _) => {
+                    if key == "animation" {
+                        state_animations.push((number, value.to_string()));
+                    }
+                    state_lines.push((number, line.to_string()));
+                }
+                _ => unreachable!(),
+            }
+        }
+
+        // 参照しているファイルの読み込み
+        let mut result_animations = Vec::new();
+        for animation in &animations {
+            let Some((line, file)) = &animation.file else {
+                problem(
+                    animation.line,
+                    format!("missing file in animation {}", animation.name),
+                );
+                continue;
+            };
+            let Some(data) = source.read(file) else {
+                problem(*line, format!("file not found: {}", file));
+                continue;
+            };
+            match Gif::from_bytes(file, &data) {
+                Ok(gif) if gif.info.is_empty() => problem(*line, format!("no frames: {}", file)),
+                Ok(gif) => result_animations.push(Animation {
+                    name: animation.name.clone(),
+                    file: file.clone(),
+                    gif,
+                    key: animation.key,
+                    scale: animation.scale,
+                    sounds: Vec::new(),
+                }),
+                Err(e) => problem(*line, format!("invalid gif: {}: {}", file, e)),
+            }
+        }
+        let mut result_sounds = Vec::new();
+        for sound in &sounds {
+            let Some((line, file)) = &sound.file else {
+                problem(sound.line, format!("missing file in sound {}", sound.name));
+                continue;
+            };
+            match source.read(file) {
+                Some(data) => result_sounds.push(Sound {
+                    name: sound.name.clone(),
+                    file: file.clone(),
+                    data,
+                }),
+                None => problem(*line, format!("file not found: {}", file)),
+            }
+        }
+        // 効果音の参照
+        for animation in &animations {
+            for (line, cue, sound) in &animation.sounds {
+                if !sounds.iter().any(|s| s.name == *sound) {
+                    problem(*line, format!("unknown sound: {}", sound));
+                } else if let Some(a) = result_animations
+                    .iter_mut()
+                    .find(|a| a.name == animation.name)
+                {
+                    a.sounds.push((*cue, sound.clone()));
+                }
+            }
+        }
+
+        // 振る舞い
+        for (line, name) in &state_animations {
+            if !animations.iter().any(|a| a.name == *name) {
+                problem(*line, format!("unknown animation: {}", name));
+            }
+        }
+        let states = match States::parse_lines(state_lines.iter().map(|(n, l)| (*n, l.as_str()))) {
+            Ok(states) => Some(states),
+            Err(state::Error::Parse(errors)) => {
+                for (line, message) in errors {
+                    problem(line, message);
+                }
+                None
+            }
+            Err(e) => {
+                problem(1, e.to_string());
+                None
+            }
+        };
+
+        match (problems.is_empty(), states) {
+            (true, Some(states)) => Ok(Self {
+                name,
+                animations: result_animations,
+                sounds: result_sounds,
+                states,
+            }),
+            _ => Err(Error::Invalid(problems)),
+        }
+    }
+
+    /// 名前からアニメーションを取得します。
+    pub fn animation(&self, name: &str) -> Option<&Animation> {
+        self.animations.iter().find(|a| a.name == name)
+    }
+
+    /// 名前から効果音を取得します。
+    pub fn sound(&self, name: &str) -> Option<&Sound> {
+        self.sounds.iter().find(|s| s.name == name)
+    }
+}
+
+/// パッケージ内を指す相対パスかどうかを調べます。
+///
+/// 絶対パスや `..` を含むパスはパッケージの外を指せるため受け付けません。
+fn is_package_path(file: &str) -> bool {
+    !file.is_empty()
+        && !file.contains('\\')
+        && Path::new(file)
+            .components()
+            .all(|c| matches!(c, std::path::Component::Normal(_)))
+}
+
+/// `#RRGGBB` 形式の色を読み込みます。
+fn parse_color(s: &str) -> Option<u32> {
+    let hex = s.strip_prefix('#')?;
+    match hex.len() {
+        6 => u32::from_str_radix(hex, 16).ok(),
+        _ => None,
+    }
+}
@@ -0,0 +1,109 @@
+//! ZIP 形式のファイルを読み込むための機能です。
+//!
+//! キャラクターのパッケージを読み込むための最低限の実装で、無圧縮と Deflate のみに対応しています。
+//! 暗号化や ZIP64 、複数のファイルへの分割には対応していません。
+
+use std::collections::HashMap;
+
+/// 終端レコード (End of central directory record) の署名
+const END_SIGNATURE: u32 = 0x06_05_4B_50;
+/// 中央ディレクトリのエントリの署名
+const CENTRAL_SIGNATURE: u32 = 0x02_01_4B_50;
+/// ローカルファイルヘッダーの署名
+const LOCAL_SIGNATURE: u32 = 0x04_03_4B_50;
+/// 終端レコードの最小の大きさ
+const END_SIZE: usize = 22;
+/// 中央ディレクトリのエントリの固定部分の大きさ
+const CENTRAL_SIZE: usize = 46;
+/// ローカルファイルヘッダーの固定部分の大きさ
+const LOCAL_SIZE: usize = 30;
+
+/// 無圧縮
+const STORED: u16 = 0;
+/// Deflate
+const DEFLATED: u16 = 8;
+
+/// リトルエンディアンの値を読み込みます。
+fn u16_at(bytes: &[u8], offset: usize) -> Option<u16> {
+    let b = bytes.get(offset..offset + 2)?;
+    Some(u16::from_le_bytes([b[0], b[1]]))
+}
+
+/// リトルエンディアンの値を読み込みます。
+fn u32_at(bytes: &[u8], offset: usize) -> Option<u32> {
+    let b = bytes.get(offset..offset + 4)?;
+    Some(u32::from_le_bytes([b[0], b[1], b[2], b[3]]))
+}
+
+/// ZIP ファイルの全てのファイルを展開し、アーカイブ内のパスとデータの対応を返します。
+///
+/// ディレクトリは含みません。
+pub fn extract(bytes: &[u8]) -> Result<HashMap<String, Vec<u8>>, String> {
+    let broken = || "broken zip archive".to_string();
+
+    // 終端レコードは末尾のコメント (最大 65535 バイト) の前にある
+    let end = (0..=bytes.len().saturating_sub(END_SIZE))
+        .rev()
+        .take(u16::MAX as usize + 1)
+        .find(|i| u32_at(bytes, *i) == Some(END_SIGNATURE))
+        .ok_or("not a zip archive")?;
+    let count = u16_at(bytes, end + 10).ok_or_else(broken)? as usize;
+    let mut offset = u32_at(bytes, end + 16).ok_or_else(broken)? as usize;
+    if count == u16::MAX as usize || offset == u32::MAX as usize {
+        return Err("zip64 archives are not supported".to_string());
+    }
+
+    let mut result = HashMap::new();
+    for _ in 0..count {
+        if u32_at(bytes, offset) != Some(CENTRAL_SIGNATURE) {
+            return Err(broken());
+        }
+        let flags = u16_at(bytes, offset + 8).ok_or_else(broken)?;
+        let method = u16_at(bytes, offset + 10).ok_or_else(broken)?;
+        let crc = u32_at(bytes, offset + 16).ok_or_else(broken)?;
+        let compressed = u32_at(bytes, offset + 20).ok_or_else(broken)? as usize;
+        let size = u32_at(bytes, offset + 24).ok_or_else(broken)? as usize;
+        let name_length = u16_at(bytes, offset + 28).ok_or_else(broken)? as usize;
+        let extra_length = u16_at(bytes, offset + 30).ok_or_else(broken)? as usize;
+        let comment_length = u16_at(bytes, offset + 32).ok_or_else(broken)? as usize;
+        let local = u32_at(bytes, offset + 42).ok_or_else(broken)? as usize;
+        let name = bytes
+            .get(offset + CENTRAL_SIZE..offset + CENTRAL_SIZE + name_length)
+            .ok_or_else(broken)?;
+        let name = String::from_utf8_lossy(name).replace('\\', "/");
+        offset += CENTRAL_SIZE + name_length + extra_length + comment_length;
+
+        if name.ends_with('/') {
+            continue;
+        }
+        if flags & 1 != 0 {
+            return Err(format!("{}: encrypted files are not supported", name));
+        }
+
+        // データの位置はローカルファイルヘッダーの可変長部分の後ろ
+        if u32_at(bytes, local) != Some(LOCAL_SIGNATURE) {
+            return Err(broken());
+        }
+        let start = local
+            + LOCAL_SIZE
+            + u16_at(bytes, local + 26).ok_or_else(broken)? as usize
+            + u16_at(bytes, local + 28).ok_or_else(broken)? as usize;
+        let data = bytes.get(start..start + compressed).ok_or_else(broken)?;
+        let data = match method {
+            STORED => data.to_vec(),
+            DEFLATED => miniz_oxide::inflate::decompress_to_vec_with_limit(data, size)
+                .map_err(|e| format!("{}: {}", name, e))?,
+            _ => {
+                return Err(format!(
+                    "{}: unsupported compression method {}",
+                    name, method
+                ))
+            }
+        };
+        if data.len() != size || crc32fast::hash(&data) != crc {
+            return Err(format!("{}: checksum mismatch", name));
+        }
+        result.insert(name, data);
+    }
+    Ok(result)
+}
@@ -0,0 +1,330 @@
+//! キャラクターにポインター (マウスカーソル) を追いかけさせたり、ポインターから逃げさせたり、見させたりするための機能です。
+//!
+//! ウィンドウを1つの矩形として扱い、その中央からポインターまでの距離と向きで動きを決めます。
+//! 物理演算と同じく経過時間を一定の間隔 (`physics::STEP`) に区切って計算します。
+
+use std::time::{Duration, Instant};
+
+use crate::{
+    hit::Rect,
+    physics::STEP,
+    timer::{Clock, SystemClock},
+};
+
+/// 動く速さ (ピクセル/秒) のデフォルト値です。
+pub const DEFAULT_SPEED: f64 = 120.0;
+
+/// 反応する距離 (ピクセル) のデフォルト値です。
+pub const DEFAULT_DISTANCE: f64 = 200.0;
+
+/// ポインターへの反応の仕方です。
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Reaction {
+    /// 一定の距離より離れたら近付きます。 (常にポインターの方を向きます)
+    Follow,
+    /// 一定の距離より近付いたら離れます。 (逃げている間はポインターと反対の方を向きます)
+    Flee,
+    /// 一定の距離より近付いたらポインターの方を向きます。 (動きません)
+    Look,
+}
+
+impl std::str::FromStr for Reaction {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "follow" => Ok(Self::Follow),
+            "flee" => Ok(Self::Flee),
+            "look" => Ok(Self::Look),
+            _ => Err(format!("unknown reaction: {}", s)),
+        }
+    }
+}
+
+/// キャラクターから見たポインター (または動く先) の向きです。
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Direction {
+    Left,
+    Right,
+    Up,
+    Down,
+}
+
+impl Direction {
+    /// 中央からの差 (`dx`, `dy`) が指す向きを求めます。 (上下は縦の差の方が大きい場合のみ)
+    pub fn from_offset(dx: f64, dy: f64) -> Self {
+        match (dy.abs() > dx.abs(), dx < 0.0, dy < 0.0) {
+            (true, _, true) => Self::Up,
+            (true, _, false) => Self::Down,
+            (false, true, _) => Self::Left,
+            (false, false, _) => Self::Right,
+        }
+    }
+
+    /// 左右を反対にした向きを取得します。 (上下はそのまま)
+    fn mirrored(self) -> Self {
+        match self {
+            Self::Left => Self::Right,
+            Self::Right => Self::Left,
+            other => other,
+        }
+    }
+}
+
+impl std::str::FromStr for Direction {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "left" => Ok(Self::Left),
+            "right" => Ok(Self::Right),
+            "up" => Ok(Self::Up),
+            "down" => Ok(Self::Down),
+            _ => Err(format!("unknown direction: {}", s)),
+        }
+    }
+}
+
+/// ポインターへの反応の設定です。
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Settings {
+    /// 反応の仕方
+    pub reaction: Reaction,
+    /// 反応する距離 (ピクセル)
+    pub distance: f64,
+    /// 動く速さ (ピクセル/秒)
+    pub speed: f64,
+    /// 横にだけ動くかどうか (床の上を歩いたり落下したりする場合に利用します)
+    pub ground: bool,
+}
+
+impl Settings {
+    /// 指定した反応の仕方で、その他をデフォルト値にした設定を返します。
+    pub fn new(reaction: Reaction) -> Self {
+        Self {
+            reaction,
+            distance: DEFAULT_DISTANCE,
+            speed: DEFAULT_SPEED,
+            ground: false,
+        }
+    }
+}
+
+/// ポインターに反応して動く矩形 (ウィンドウ) です。
+///
+/// ```rust
+/// use bkrbk::cursor::{Direction, Follower, Reaction, Settings};
+/// use bkrbk::hit::Rect;
+/// use bkrbk::timer::MockClock;
+/// use std::time::Duration;
+///
+/// let area = Rect { x: 0, y: 0, width: 1000, height: 1000 };
+/// let settings = Settings { distance: 100.0, speed: 200.0, ..Settings::new(Reaction::Follow) };
+/// let clock = MockClock::new();
+/// let mut follower = Follower::with_clock(settings, area, (100, 100), clock.clone());
+/// follower.set_position(0, 0);
+///
+/// // 中央 (50, 50) から 100px の距離まで近付く
+/// for _ in 0..200 {
+///     clock.advance(Duration::from_millis(16));
+///     follower.update((550, 50));
+/// }
+/// assert_eq!(follower.position(), (400, 0));
+/// assert_eq!(follower.facing(), Some(Direction::Right));
+///
+/// // 向きに合うアニメーションが無ければ、反対向きのものを左右反転して使う
+/// let faces = [(Direction::Right, "right.gif")];
+/// assert_eq!(follower.select(&faces), Some(("right.gif", false)));
+/// follower.update((0, 50));
+/// assert_eq!(follower.facing(), Some(Direction::Left));
+/// assert_eq!(follower.select(&faces), Some(("right.gif", true)));
+///
+/// // 逃げる場合は離れるまで反対の方を向いて動く
+/// follower.settings.reaction = Reaction::Flee;
+/// clock.advance(Duration::from_millis(100));
+/// assert!(follower.update((400, 50)));
+/// assert_eq!(follower.facing(), Some(Direction::Right));
+/// for _ in 0..100 {
+///     clock.advance(Duration::from_millis(16));
+///     follower.update((400, 50));
+/// }
+/// assert_eq!(follower.facing(), None);
+/// ```
+#[derive(Debug, Clone)]
+pub struct Follower<C: Clock = SystemClock> {
+    /// ポインターへの反応の設定
+    pub settings: Settings,
+    /// 動ける範囲 (作業領域)
+    area: Rect,
+    /// 矩形の大きさ
+    size: (u32, u32),
+    /// 位置 (左上の座標)
+    position: (f64, f64),
+    /// 向いている方向 (反応していない場合は None)
+    facing: Option<Direction>,
+    /// 向いている方向のうち左右の成分
+    side: Direction,
+    /// 最後に計算した時刻
+    last: Instant,
+    /// まだ計算していない経過時間
+    pending: Duration,
+    clock: C,
+}
+
+impl Follower {
+    /// 指定した範囲を動く Follower 構造体を初期化して返します。
+    pub fn new(settings: Settings, area: Rect, size: (u32, u32)) -> Self {
+        Self::with_clock(settings, area, size, SystemClock)
+    }
+}
+
+impl<C: Clock> Follower<C> {
+    /// 指定した時計を利用して、指定した範囲を動く Follower 構造体を初期化して返します。
+    ///
+    /// 最初は範囲の左上にいます。
+    pub fn with_clock(settings: Settings, area: Rect, size: (u32, u32), clock: C) -> Self {
+        Self {
+            settings,
+            area,
+            size,
+            position: (area.x as f64, area.y as f64),
+            facing: None,
+            side: Direction::Right,
+            last: clock.now(),
+            pending: Duration::ZERO,
+            clock,
+        }
+    }
+
+    /// 位置 (左上の座標) を取得します。
+    pub fn position(&self) -> (i32, i32) {
+        (
+            self.position.0.round() as i32,
+            self.position.1.round() as i32,
+        )
+    }
+
+    /// 向いている方向を取得します。 (反応していない場合は None)
+    pub fn facing(&self) -> Option<Direction> {
+        self.facing
+    }
+
+    /// 動ける範囲を変更します。
+    pub fn set_area(&mut self, area: Rect) {
+        self.area = area;
+    }
+
+    /// 位置 (左上の座標) を変更します。
+    pub fn set_position(&mut self, x: i32, y: i32) {
+        self.position = (x as f64, y as f64);
+    }
+
+    /// 前回からの経過時間を捨てて、反応を止めます。 (止めている間は毎回呼び出します)
+    pub fn pause(&mut self) {
+        self.last = self.clock.now();
+        self.pending = Duration::ZERO;
+        self.facing = None;
+    }
+
+    /// 指定したポインターの位置 (画面上の座標) に対して、前回から経過した時間だけ計算を進めます。
+    ///
+    /// 位置が変わった場合は true を返します。
+    pub fn update(&mut self, pointer: (i32, i32)) -> bool {
+        let now = self.clock.now();
+        self.pending += now.saturating_duration_since(self.last);
+        self.last = now;
+        let before = self.position();
+        let mut moved = false;
+        loop {
+            let (dx, dy, distance) = self.offset(pointer);
+            let (sign, active) = match self.settings.reaction {
+                Reaction::Follow => (1.0, distance > self.settings.distance),
+                Reaction::Flee => (-1.0, distance < self.settings.distance),
+                Reaction::Look => (0.0, distance < self.settings.distance),
+            };
+            // 逃げる場合はポインターと反対の方を向く
+            let turn = match self.settings.reaction {
+                Reaction::Flee => -1.0,
+                _ => 1.0,
+            };
+            let facing = Direction::from_offset(turn * dx, turn * dy);
+            self.side = Direction::from_offset(turn * dx, 0.0);
+            self.facing = match self.settings.reaction {
+                Reaction::Follow => Some(facing),
+                _ => active.then_some(facing),
+            };
+            if !active || sign == 0.0 || self.pending < STEP {
+                break;
+            }
+            self.pending -= STEP;
+            moved |= self.step(STEP, (dx, dy), distance, sign);
+        }
+        // 動かない間の経過時間は溜めない
+        if !moved {
+            self.pending = self.pending.min(STEP);
+        }
+        self.position() != before
+    }
+
+    /// 矩形の中央からポインターまでの差 (`dx`, `dy`) と距離を求めます。 (横にだけ動く場合は横の差のみ)
+    fn offset(&self, pointer: (i32, i32)) -> (f64, f64, f64) {
+        let dx = pointer.0 as f64 - (self.position.0 + self.size.0 as f64 / 2.0);
+        let dy = pointer.1 as f64 - (self.position.1 + self.size.1 as f64 / 2.0);
+        let distance = match self.settings.ground {
+            true => dx.abs(),
+            false => dx.hypot(dy),
+        };
+        (dx, dy, distance)
+    }
+
+    /// `sign` の向き (1.0 で近付き、 -1.0 で離れる) へ計算を `dt` だけ進めます。
+    ///
+    /// 範囲の端で動けなかった場合は false を返します。
+    fn step(&mut self, dt: Duration, (dx, dy): (f64, f64), distance: f64, sign: f64) -> bool {
+        let dy = match self.settings.ground {
+            true => 0.0,
+            false => dy,
+        };
+        if distance == 0.0 {
+            return false;
+        }
+        // 近付く場合は反応する距離を越えて進まない
+        let mut length = self.settings.speed * dt.as_secs_f64();
+        if sign > 0.0 {
+            length = length.min(distance - self.settings.distance);
+        }
+        let left = self.area.x as f64;
+        let top = self.area.y as f64;
+        let right = (left + self.area.width as f64 - self.size.0 as f64).max(left);
+        let bottom = (top + self.area.height as f64 - self.size.1 as f64).max(top);
+        let (x, y) = self.position;
+        let next = (
+            (x + sign * dx / distance * length).clamp(left, right),
+            (y + sign * dy / distance * length).clamp(top, bottom),
+        );
+        self.position = next;
+        next != (x, y)
+    }
+
+    /// 向いている方向に合うものを一覧から選びます。
+    ///
+    /// 左右の向きに合うものが無い場合は、反対向きのものを選んで true (左右反転) と共に返します。
+    /// 上下の向きに合うものが無い場合は、ポインターが左右どちらにあるかで選び直します。
+    pub fn select<T: Copy>(&self, faces: &[(Direction, T)]) -> Option<(T, bool)> {
+        let facing = self.facing?;
+        let find = |direction: Direction| {
+            faces
+                .iter()
+                .find(|(d, _)| *d == direction)
+                .map(|(_, face)| *face)
+        };
+        let horizontal = match facing {
+            Direction::Up | Direction::Down => self.side,
+            other => other,
+        };
+        find(facing)
+            .map(|face| (face, false))
+            .or_else(|| find(horizontal).map(|face| (face, false)))
+            .or_else(|| find(horizontal.mirrored()).map(|face| (face, true)))
+    }
+}
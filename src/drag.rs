@@ -0,0 +1,116 @@
+//! キャラクターをマウスで掴んで動かすための機能です。
+//!
+//! 描画先から受け取ったポインターの操作を、クリックとドラッグに振り分けます。
+//! 少しだけ動いた場合はクリックとして扱うので、手ぶれで意図せず動くことはありません。
+
+use crate::backend::PointerEvent;
+
+/// クリックではなくドラッグとみなす移動量 (ピクセル) のデフォルト値です。
+pub const DEFAULT_THRESHOLD: i32 = 4;
+
+/// ポインターの操作を振り分けた結果です。
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DragEvent {
+    /// 動かさずにボタンを離しました。
+    Click,
+    /// ドラッグを始めました。
+    Start,
+    /// ウィンドウを指定した位置 (左上の座標) へ移動します。
+    Move(i32, i32),
+    /// ドラッグを終えました。
+    Drop,
+}
+
+/// ボタンを押している間の状態です。
+#[derive(Debug, Clone, Copy)]
+struct Grab {
+    /// ボタンを押した位置
+    start: (i32, i32),
+    /// ウィンドウの左上からポインターまでの距離
+    offset: (i32, i32),
+    /// ドラッグを始めたかどうか
+    dragging: bool,
+}
+
+/// ポインターの操作をクリックとドラッグに振り分けるための構造体です。
+///
+/// ```rust
+/// use bkrbk::backend::PointerEvent;
+/// use bkrbk::drag::{Drag, DragEvent};
+///
+/// let mut drag = Drag::new(4);
+/// let window = (100, 100);
+///
+/// // 少しだけ動かした場合はクリック
+/// assert_eq!(drag.handle(PointerEvent::Press(110, 120), window), None);
+/// assert_eq!(drag.handle(PointerEvent::Move(112, 121), window), None);
+/// assert_eq!(drag.handle(PointerEvent::Release(112, 121), window), Some(DragEvent::Click));
+///
+/// // 掴んだ位置を保ったままウィンドウを動かす
+/// drag.handle(PointerEvent::Press(110, 120), window);
+/// assert_eq!(drag.handle(PointerEvent::Move(130, 120), window), Some(DragEvent::Start));
+/// assert!(drag.is_dragging());
+/// assert_eq!(drag.handle(PointerEvent::Move(150, 125), window), Some(DragEvent::Move(140, 105)));
+/// assert_eq!(drag.handle(PointerEvent::Release(150, 125), (140, 105)), Some(DragEvent::Drop));
+/// assert!(!drag.is_dragging());
+/// ```
+#[derive(Debug, Clone)]
+pub struct Drag {
+    /// クリックではなくドラッグとみなす移動量
+    pub threshold: i32,
+    grab: Option<Grab>,
+}
+
+impl Default for Drag {
+    fn default() -> Self {
+        Self::new(DEFAULT_THRESHOLD)
+    }
+}
+
+impl Drag {
+    /// 指定した移動量を超えた場合にドラッグとみなす Drag 構造体を初期化して返します。
+    pub fn new(threshold: i32) -> Self {
+        Self {
+            threshold,
+            grab: None,
+        }
+    }
+
+    /// ドラッグ中かどうかを判定します。
+    pub fn is_dragging(&self) -> bool {
+        self.grab.is_some_and(|grab| grab.dragging)
+    }
+
+    /// ポインターの操作を処理します。
+    ///
+    /// `window` は現在のウィンドウの位置 (左上の座標) です。
+    /// ドラッグを始めた際は `Start` を返し、次の移動から `Move` を返します。
+    pub fn handle(&mut self, event: PointerEvent, window: (i32, i32)) -> Option<DragEvent> {
+        match event {
+            PointerEvent::Press(x, y) => {
+                self.grab = Some(Grab {
+                    start: (x, y),
+                    offset: (x - window.0, y - window.1),
+                    dragging: false,
+                });
+                None
+            }
+            PointerEvent::Move(x, y) => {
+                let grab = self.grab.as_mut()?;
+                if grab.dragging {
+                    return Some(DragEvent::Move(x - grab.offset.0, y - grab.offset.1));
+                }
+                let (dx, dy) = (x - grab.start.0, y - grab.start.1);
+                if dx.abs().max(dy.abs()) > self.threshold {
+                    grab.dragging = true;
+                    return Some(DragEvent::Start);
+                }
+                None
+            }
+            PointerEvent::Release(_, _) => match self.grab.take()?.dragging {
+                true => Some(DragEvent::Drop),
+                false => Some(DragEvent::Click),
+            },
+        }
+    }
+}
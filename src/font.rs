@@ -0,0 +1,156 @@
+//! 画像に文字を描画するための機能です。
+//!
+//! 外部のフォントファイルに依存しないように、最低限の文字 (数字、英大文字と一部の記号) を 3x5 のビットマップで持っています。
+
+/// 文字の幅 (画素)
+pub const GLYPH_WIDTH: usize = 3;
+/// 文字の高さ (画素)
+pub const GLYPH_HEIGHT: usize = 5;
+
+/// 文字のビットマップを取得します。
+///
+/// 各行の下位3bitが左から順に画素の有無を表します。対応していない文字の場合は None を返します。
+pub fn glyph(c: char) -> Option<[u8; GLYPH_HEIGHT]> {
+    let rows = match c {
+        '0' => [0b111, 0b101, 0b101, 0b101, 0b111],
+        '1' => [0b010, 0b110, 0b010, 0b010, 0b111],
+        '2' => [0b111, 0b001, 0b111, 0b100, 0b111],
+        '3' => [0b111, 0b001, 0b111, 0b001, 0b111],
+        '4' => [0b101, 0b101, 0b111, 0b001, 0b001],
+        '5' => [0b111, 0b100, 0b111, 0b001, 0b111],
+        '6' => [0b111, 0b100, 0b111, 0b101, 0b111],
+        '7' => [0b111, 0b001, 0b001, 0b001, 0b001],
+        '8' => [0b111, 0b101, 0b111, 0b101, 0b111],
+        '9' => [0b111, 0b101, 0b111, 0b001, 0b111],
+        '#' => [0b101, 0b111, 0b101, 0b111, 0b101],
+        'm' => [0b000, 0b110, 0b111, 0b101, 0b101],
+        's' => [0b000, 0b011, 0b010, 0b001, 0b110],
+        ':' => [0b000, 0b010, 0b000, 0b010, 0b000],
+        '.' => [0b000, 0b000, 0b000, 0b000, 0b010],
+        '/' => [0b001, 0b001, 0b010, 0b100, 0b100],
+        '-' => [0b000, 0b000, 0b111, 0b000, 0b000],
+        '!' => [0b010, 0b010, 0b010, 0b000, 0b010],
+        '?' => [0b110, 0b001, 0b010, 0b000, 0b010],
+        ',' => [0b000, 0b000, 0b000, 0b010, 0b100],
+        '\'' => [0b010, 0b010, 0b000, 0b000, 0b000],
+        '(' => [0b001, 0b010, 0b010, 0b010, 0b001],
+        ')' => [0b100, 0b010, 0b010, 0b010, 0b100],
+        '+' => [0b000, 0b010, 0b111, 0b010, 0b000],
+        '=' => [0b000, 0b111, 0b000, 0b111, 0b000],
+        '%' => [0b101, 0b001, 0b010, 0b100, 0b101],
+        '_' => [0b000, 0b000, 0b000, 0b000, 0b111],
+        'A' => [0b010, 0b101, 0b111, 0b101, 0b101],
+        'B' => [0b110, 0b101, 0b110, 0b101, 0b110],
+        'C' => [0b011, 0b100, 0b100, 0b100, 0b011],
+        'D' => [0b110, 0b101, 0b101, 0b101, 0b110],
+        'E' => [0b111, 0b100, 0b110, 0b100, 0b111],
+        'F' => [0b111, 0b100, 0b110, 0b100, 0b100],
+        'G' => [0b011, 0b100, 0b101, 0b101, 0b011],
+        'H' => [0b101, 0b101, 0b111, 0b101, 0b101],
+        'I' => [0b111, 0b010, 0b010, 0b010, 0b111],
+        'J' => [0b001, 0b001, 0b001, 0b101, 0b010],
+        'K' => [0b101, 0b101, 0b110, 0b101, 0b101],
+        'L' => [0b100, 0b100, 0b100, 0b100, 0b111],
+        'M' => [0b101, 0b111, 0b111, 0b101, 0b101],
+        'N' => [0b110, 0b101, 0b101, 0b101, 0b101],
+        'O' => [0b010, 0b101, 0b101, 0b101, 0b010],
+        'P' => [0b110, 0b101, 0b110, 0b100, 0b100],
+        'Q' => [0b010, 0b101, 0b101, 0b110, 0b011],
+        'R' => [0b110, 0b101, 0b110, 0b101, 0b101],
+        'S' => [0b011, 0b100, 0b010, 0b001, 0b110],
+        'T' => [0b111, 0b010, 0b010, 0b010, 0b010],
+        'U' => [0b101, 0b101, 0b101, 0b101, 0b111],
+        'V' => [0b101, 0b101, 0b101, 0b101, 0b010],
+        'W' => [0b101, 0b101, 0b111, 0b111, 0b101],
+        'X' => [0b101, 0b101, 0b010, 0b101, 0b101],
+        'Y' => [0b101, 0b101, 0b010, 0b010, 0b010],
+        'Z' => [0b111, 0b001, 0b010, 0b100, 0b111],
+        ' ' => [0b000; GLYPH_HEIGHT],
+        _ => return None,
+    };
+    Some(rows)
+}
+
+/// 文字列を描画した際の大きさを取得します。
+pub fn text_size(text: &str, scale: usize) -> (usize, usize) {
+    let count = text.chars().count();
+    let width = match count {
+        0 => 0,
+        _ => (count * (GLYPH_WIDTH + 1) - 1) * scale,
+    };
+    (width, GLYPH_HEIGHT * scale)
+}
+
+/// 文字列を指定した幅に収まるように、単語単位で折り返します。
+///
+/// 1単語で幅を超える場合は、単語の途中で折り返します。
+/// ```rust
+/// let lines = bkrbk::font::wrap_text("STAND UP AND STRETCH", 40, 1);
+/// assert_eq!(lines, vec!["STAND UP", "AND", "STRETCH"]);
+/// ```
+pub fn wrap_text(text: &str, max_width: usize, scale: usize) -> Vec<String> {
+    let columns = ((max_width / scale.max(1) + 1) / (GLYPH_WIDTH + 1)).max(1);
+    let mut lines = Vec::new();
+    for paragraph in text.lines() {
+        let mut line = String::new();
+        for word in paragraph.split_whitespace() {
+            let mut word: Vec<char> = word.chars().collect();
+            let used = line.chars().count();
+            if used > 0 && used + 1 + word.len() <= columns {
+                line.push(' ');
+                line.extend(word);
+                continue;
+            }
+            if used > 0 {
+                lines.push(std::mem::take(&mut line));
+            }
+            while word.len() > columns {
+                lines.push(word.drain(..columns).collect());
+            }
+            line.extend(word);
+        }
+        lines.push(line);
+    }
+    lines
+}
+
+/// 画像に文字列を描画します。
+///
+/// `image` は `width` 列の画像として扱い、範囲外にはみ出した部分は描画しません。
+/// 対応していない文字は空白として扱います。
+/// ```rust
+/// let mut image = vec![0; 4 * 5];
+/// bkrbk::font::draw_text(&mut image, 4, 0, 0, "1", 0x00_FF_FF_FF, 1);
+/// assert_eq!(&image[0..4], &[0, 0x00_FF_FF_FF, 0, 0]);
+/// ```
+pub fn draw_text(
+    image: &mut [u32],
+    width: usize,
+    x: usize,
+    y: usize,
+    text: &str,
+    color: u32,
+    scale: usize,
+) {
+    let height = image.len() / width;
+    for (i, c) in text.chars().enumerate() {
+        let rows = glyph(c).unwrap_or([0; GLYPH_HEIGHT]);
+        let left = x + i * (GLYPH_WIDTH + 1) * scale;
+        for (dy, row) in rows.iter().enumerate() {
+            for dx in 0..GLYPH_WIDTH {
+                if row & (0b100 >> dx) == 0 {
+                    continue;
+                }
+                for sy in 0..scale {
+                    for sx in 0..scale {
+                        let px = left + dx * scale + sx;
+                        let py = y + dy * scale + sy;
+                        if px < width && py < height {
+                            image[py * width + px] = color;
+                        }
+                    }
+                }
+            }
+        }
+    }
+}
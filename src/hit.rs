@@ -0,0 +1,121 @@
+//! キャラクターが描画されている部分だけを操作の対象にするための機能です。
+//!
+//! 合成済みの画像のうち、透過色 (キーカラー) 以外の画素をキャラクターの一部として扱います。
+//! 入力の振り分けと、各描画先の入力範囲の設定の両方に利用します。
+
+/// 画像の不透明な部分を表す矩形です。
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Rect {
+    pub x: i32,
+    pub y: i32,
+    pub width: u32,
+    pub height: u32,
+}
+
+/// 画像の各画素が不透明かどうかを表す構造体です。
+///
+/// ```rust
+/// use bkrbk::hit::{Mask, Rect};
+///
+/// let bg = 0x00_00_00_FF;
+/// let o = 0x00_FF_00_00;
+/// #[rustfmt::skip]
+/// let image = [
+///     bg, o,  o,  bg,
+///     bg, o,  o,  bg,
+///     o,  o,  bg, o,
+/// ];
+/// let mask = Mask::from_image(&image, 4, bg);
+/// assert!(mask.contains(1, 0));
+/// assert!(!mask.contains(0, 0));
+/// assert!(!mask.contains(-1, 2));
+/// assert!(!mask.contains(4, 2));
+///
+/// // 同じ並びの行はまとめる
+/// assert_eq!(
+///     mask.rectangles(),
+///     [
+///         Rect { x: 1, y: 0, width: 2, height: 2 },
+///         Rect { x: 0, y: 2, width: 2, height: 1 },
+///         Rect { x: 3, y: 2, width: 1, height: 1 },
+///     ]
+/// );
+/// ```
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Mask {
+    width: usize,
+    height: usize,
+    opaque: Vec<bool>,
+}
+
+impl Mask {
+    /// `width` 列の画像から、 `bg` と一致しない画素を不透明として Mask 構造体を作成します。
+    pub fn from_image(image: &[u32], width: usize, bg: u32) -> Self {
+        Self {
+            width,
+            height: image.len() / width.max(1),
+            opaque: image.iter().map(|pixel| *pixel != bg).collect(),
+        }
+    }
+
+    /// 大きさ (幅, 高さ) を取得します。
+    pub fn size(&self) -> (usize, usize) {
+        (self.width, self.height)
+    }
+
+    /// 画像の左上を原点とした位置が、不透明な画素かどうかを判定します。 (画像の外は false)
+    pub fn contains(&self, x: i32, y: i32) -> bool {
+        let (Ok(x), Ok(y)) = (usize::try_from(x), usize::try_from(y)) else {
+            return false;
+        };
+        x < self.width && y < self.height && self.opaque[y * self.width + x]
+    }
+
+    /// 不透明な部分が1つも無いかどうかを判定します。
+    pub fn is_empty(&self) -> bool {
+        !self.opaque.contains(&true)
+    }
+
+    /// 行ごとに連続した不透明な画素の範囲 (開始位置, 終了位置) の一覧を取得します。
+    fn spans(&self, y: usize) -> Vec<(usize, usize)> {
+        let row = &self.opaque[y * self.width..(y + 1) * self.width];
+        let mut result = Vec::new();
+        let mut x = 0;
+        while x < self.width {
+            if !row[x] {
+                x += 1;
+                continue;
+            }
+            let start = x;
+            while x < self.width && row[x] {
+                x += 1;
+            }
+            result.push((start, x));
+        }
+        result
+    }
+
+    /// 不透明な部分を重ならない矩形の一覧に変換します。
+    ///
+    /// 矩形は上から順に並び、同じ高さの矩形は左から順に並びます。 (X11 の YX-banded の順序)
+    /// 不透明な画素の並びが同じ行は、1つの矩形にまとめます。
+    pub fn rectangles(&self) -> Vec<Rect> {
+        let mut result = Vec::new();
+        let mut y = 0;
+        while y < self.height {
+            let spans = self.spans(y);
+            let mut height = 1;
+            while y + height < self.height && self.spans(y + height) == spans {
+                height += 1;
+            }
+            result.extend(spans.into_iter().map(|(start, end)| Rect {
+                x: start as i32,
+                y: y as i32,
+                width: (end - start) as u32,
+                height: height as u32,
+            }));
+            y += height;
+        }
+        result
+    }
+}
@@ -1,24 +1,154 @@
+//! 画像を扱うための機能です。
+
+/// パレットに含まれない色の代わりに利用する色です。
+const DEFAULT_BG: u32 = 0x00_FF_FF_FF;
+
+/// 画像の読み込みで発生するエラーです。
+#[derive(Debug)]
+pub enum Error {
+    /// ファイルを開けませんでした。
+    Io(std::io::Error),
+    /// GIF として解析できませんでした。
+    Decode(gif::DecodingError),
+}
+
+impl std::fmt::Display for Error {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Io(e) => write!(f, "{}", e),
+            Self::Decode(e) => write!(f, "invalid GIF: {}", e),
+        }
+    }
+}
+
+impl std::error::Error for Error {}
+
+impl From<std::io::Error> for Error {
+    fn from(e: std::io::Error) -> Self {
+        Self::Io(e)
+    }
+}
+
+impl From<gif::DecodingError> for Error {
+    fn from(e: gif::DecodingError) -> Self {
+        Self::Decode(e)
+    }
+}
+
 pub struct Gif {
     pub filepath: String,
     pub info: Vec<gif::Frame<'static>>,
+    /// フレームにパレットが無い場合に利用するパレット (RGB の並び)
+    pub global_palette: Option<Vec<u8>>,
 }
 
 impl Gif {
-    pub fn new(filepath: &str) -> Self {
-        use std::fs::File;
-        let input = File::open(filepath).unwrap();
+    /// ファイルから GIF を読み込みます。
+    ///
+    /// ```rust
+    /// use bkrbk::img::{Error, Gif};
+    ///
+    /// let gif = Gif::open("share/jump.gif").unwrap();
+    /// assert!(gif.size().is_some());
+    /// assert!(matches!(Gif::open("share/missing.gif"), Err(Error::Io(_))));
+    /// assert!(matches!(Gif::open("Cargo.toml"), Err(Error::Decode(_))));
+    /// ```
+    pub fn open(filepath: &str) -> Result<Self, Error> {
+        let input = std::io::BufReader::new(std::fs::File::open(filepath)?);
+        Ok(Self::read(filepath, input)?)
+    }
+
+    /// メモリ上のデータから GIF を読み込みます。
+    ///
+    /// `name` は `filepath` の代わりに保持する名前です。
+    pub fn from_bytes(name: &str, bytes: &[u8]) -> Result<Self, gif::DecodingError> {
+        Self::read(name, bytes)
+    }
+
+    fn read(name: &str, input: impl std::io::Read) -> Result<Self, gif::DecodingError> {
         let mut options = gif::DecodeOptions::new();
         options.set_color_output(gif::ColorOutput::Indexed);
 
-        let info: Vec<_> = options
-            .read_info(input)
-            .unwrap()
-            .into_iter()
-            .map(|info| info.unwrap())
-            .collect();
-        Self {
-            filepath: filepath.to_string(),
+        let decoder = options.read_info(input)?;
+        let global_palette = decoder.global_palette().map(|p| p.to_vec());
+        let info = decoder.into_iter().collect::<Result<Vec<_>, _>>()?;
+        Ok(Self {
+            filepath: name.to_string(),
             info,
+            global_palette,
+        })
+    }
+
+    /// 全てのフレームを描画できる大きさを取得します。
+    ///
+    /// フレームが1つも無い場合は None を返します。
+    pub fn size(&self) -> Option<(u16, u16)> {
+        let width = self.info.iter().map(|info| info.left + info.width).max()?;
+        let height = self.info.iter().map(|info| info.top + info.height).max()?;
+        Some((width, height))
+    }
+
+    /// 各フレームの表示時間を取得します。
+    pub fn delays(&self) -> Vec<std::time::Duration> {
+        self.info
+            .iter()
+            .map(|info| std::time::Duration::from_millis(info.delay as u64 * 10))
+            .collect()
+    }
+
+    /// 指定したフレームのパレットを u32 型の色の一覧に変換します。
+    ///
+    /// フレームにパレットが無い場合はグローバルパレットを利用します。
+    ///
+    /// ```rust
+    /// use bkrbk::img::Gif;
+    ///
+    /// // グローバルパレットだけを持つ 1x1 の GIF
+    /// let mut bytes = Vec::new();
+    /// let mut encoder = gif::Encoder::new(&mut bytes, 1, 1, &[0xFF, 0x00, 0x00]).unwrap();
+    /// let mut frame = gif::Frame::default();
+    /// frame.width = 1;
+    /// frame.height = 1;
+    /// frame.buffer = std::borrow::Cow::Borrowed(&[0]);
+    /// encoder.write_frame(&frame).unwrap();
+    /// drop(encoder);
+    ///
+    /// let gif = Gif::from_bytes("red.gif", &bytes).unwrap();
+    /// assert_eq!(gif.palette(0)[0], 0x00_FF_00_00);
+    /// ```
+    pub fn palette(&self, index: usize) -> Vec<u32> {
+        let mut result = vec![DEFAULT_BG; u8::MAX as usize + 1];
+        let palette = self.info[index]
+            .palette
+            .as_ref()
+            .or(self.global_palette.as_ref());
+        if let Some(palette) = palette {
+            for (color, rgb) in result.iter_mut().zip(palette.chunks_exact(3)) {
+                *color = crate::color::Color::as_u32(rgb[0], rgb[1], rgb[2]);
+            }
+        }
+        result
+    }
+
+    /// 指定したフレームを画像に重ねて描画します。
+    ///
+    /// 最初のフレーム以外では、パレットの0番目の色は透明として扱い上書きしません。
+    /// `image` は `width` 列の画像として扱います。
+    pub fn draw(&self, index: usize, image: &mut [u32], width: usize) {
+        let info = &self.info[index];
+        let palette = self.palette(index);
+
+        for y in 0..info.height as usize {
+            for x in 0..info.width as usize {
+                let base_i = y * info.width as usize + x;
+                let core_i = (y + info.top as usize) * width + x + info.left as usize;
+                let pale_i = info.buffer[base_i];
+
+                // 0の場合は上書きしない
+                if index == 0 || 0 != pale_i {
+                    image[core_i] = palette[pale_i as usize];
+                }
+            }
         }
     }
 }
@@ -1,2 +1,27 @@
+pub mod backend;
+pub mod behavior;
+pub mod bubble;
+pub mod character;
 pub mod color;
+pub mod cursor;
+pub mod drag;
+pub mod font;
+pub mod hit;
+pub mod img;
+pub mod physics;
+pub mod player;
+pub mod random;
+pub mod reminder;
+pub mod render;
+pub mod script;
+pub mod state;
+pub mod term;
+pub mod timer;
+pub mod transition;
+pub mod walk;
+#[cfg(target_os = "linux")]
+pub mod wayland;
+#[cfg(target_os = "windows")]
 pub mod win;
+#[cfg(target_os = "linux")]
+pub mod x11;
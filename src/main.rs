@@ -1,137 +1,1709 @@
-use bkrbk::win;
+use std::{
+    collections::HashMap,
+    ops::Range,
+    path::PathBuf,
+    time::{Duration, Instant, SystemTime},
+};
 
-pub mod img;
-pub mod timer;
+use bkrbk::{
+    backend::{Backend, PointerEvent},
+    bubble, character, cursor, drag, hit, img, physics, player, reminder, render, script, state,
+    term::graphics::Protocol,
+    timer, transition, walk,
+};
 
 #[derive(Debug)]
 enum Error {
     ParseArgs,
     ParseInfo,
+    Render(bkrbk::render::Error),
+    Reminder(reminder::Error),
+    State(PathBuf, state::Error),
+    Character(PathBuf, character::Error),
+    Script(PathBuf, script::Error),
+    Font(PathBuf, bubble::Error),
+    Image(PathBuf, img::Error),
+    #[cfg(target_os = "windows")]
+    Windows(windows::core::Error),
+    #[cfg(target_os = "linux")]
+    X11(bkrbk::x11::Error),
+    #[cfg(target_os = "linux")]
+    Wayland(bkrbk::wayland::Error),
+    #[cfg(not(any(target_os = "windows", target_os = "linux")))]
+    UnsupportedPlatform,
 }
 
-fn parse_args() -> Result<(String, u32), Error> {
-    let args = std::env::args().collect::<Vec<String>>();
-    let path = args.get(1).cloned().ok_or(Error::ParseArgs)?;
+impl std::fmt::Display for Error {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::ParseArgs => write!(
+                f,
+                "usage: bkrbk [--term | --sixel | --kitty] [--pacer <sleep|spin|yield>]\n             \
+                 [--fps] [--stats <output.json|output.csv>]\n             \
+                 [--schedule <trigger>[=<filepath>]]... [--remind <duration>=<message>]...\n             \
+                 [--pomodoro <focus>/<short>/<long> | --stop-pomodoro]\n             \
+                 [--break-message <message>] [--focus-message <message>]\n             \
+                 [--notify-animation <filepath>] [--state <filepath>]\n             \
+                 [--speed <rate>] [--reverse] [--mode <loop|once|ping-pong>]\n             \
+                 [--range <start>-<end>] [--catch-up] [--on <cue>[@<filepath>]=<action>]...\n             \
+                 [--transition <effect>[:<duration>]] [--states <filepath>]\n             \
+                 [--script <filepath>] [--held <filepath>]\n             \
+                 [--physics] [--gravity <px/s²>] [--friction <rate>] [--restitution <rate>]\n             \
+                 [--walk <filepath>] [--walk-speed <px/s>] [--climb] [--sit <duration>]\n             \
+                 [--cursor <follow|flee|look>[:<distance>]] [--cursor-speed <px/s>]\n             \
+                 [--face <left|right|up|down>=<filepath>]...\n             \
+                 [--font <filepath>] [--font-size <px>] [--typing <chars/s>]\n             \
+                 <filepath | character>\n       \
+                 bkrbk render <filepath> <output> [--sheet] [--columns <n>]"
+            ),
+            Self::ParseInfo => write!(f, "failed to read frame information"),
+            Self::Render(e) => write!(f, "{}", e),
+            Self::Reminder(e) => write!(f, "{}", e),
+            Self::State(path, e) => write!(f, "{}", with_path(path, e)),
+            Self::Character(path, e) => write!(f, "{}", with_path(path, e)),
+            Self::Script(path, e) => write!(f, "{}", with_path(path, e)),
+            Self::Font(path, e) => write!(f, "{}", with_path(path, e)),
+            Self::Image(path, e) => write!(f, "{}", with_path(path, e)),
+            #[cfg(target_os = "windows")]
+            Self::Windows(e) => write!(f, "{}", e),
+            #[cfg(target_os = "linux")]
+            Self::X11(e) => write!(f, "{}", e),
+            #[cfg(target_os = "linux")]
+            Self::Wayland(e) => write!(f, "{}", e),
+            #[cfg(not(any(target_os = "windows", target_os = "linux")))]
+            Self::UnsupportedPlatform => write!(f, "this platform is not supported"),
+        }
+    }
+}
+
+/// エラーを表示して終了します。
+fn exit_with(e: Error) -> ! {
+    eprintln!("{}", e);
+    std::process::exit(1);
+}
+
+/// GIF ファイルを読み込みます。
+fn open_gif(filepath: &str) -> Result<img::Gif, Error> {
+    img::Gif::open(filepath).map_err(|e| Error::Image(filepath.into(), e))
+}
+
+/// エラーの各行の先頭にファイルパスを付けます。
+fn with_path(path: &std::path::Path, e: &dyn std::error::Error) -> String {
+    let lines: Vec<String> = e
+        .to_string()
+        .lines()
+        .map(|line| format!("{}: {}", path.display(), line))
+        .collect();
+    lines.join("\n")
+}
+
+/// 描画先の種類です。
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum BackendKind {
+    /// 実行環境に合わせたウィンドウ
+    Window,
+    /// 端末 (ブロック文字)
+    Terminal,
+    /// 端末 (Sixel, Kitty graphics protocol)
+    Graphics(bkrbk::term::graphics::Protocol),
+}
+
+/// 実行する処理です。
+enum Command {
+    /// キャラクターを表示します。
+    Run(BackendKind),
+    /// フレームを PNG ファイルとして書き出します。
+    Render {
+        /// 出力先 (コンタクトシートの場合はファイル、それ以外の場合はディレクトリ)
+        output: std::path::PathBuf,
+        /// 全てのフレームを1枚にまとめるかどうか
+        sheet: bool,
+        /// コンタクトシートの列数
+        columns: usize,
+    },
+}
+
+/// フレームに結び付けたイベントで行う処理です。
+#[derive(Debug, Clone)]
+enum Action {
+    /// アニメーションを切り替えます。 (None の場合は通常のアニメーション)
+    Play(Option<String>),
+    /// コマンドを実行します。
+    Run(String),
+    /// 効果音を再生します。
+    Sound(PathBuf),
+}
+
+impl std::str::FromStr for Action {
+    type Err = String;
+
+    /// `play:<filepath>` 、 `run:<command>` 、 `sound:<filepath>` のいずれかを受け付けます。
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.split_once(':') {
+            Some(("play", "")) => Ok(Self::Play(None)),
+            Some(("play", filepath)) => Ok(Self::Play(Some(filepath.to_string()))),
+            Some(("run", command)) if !command.is_empty() => Ok(Self::Run(command.to_string())),
+            Some(("sound", filepath)) if !filepath.is_empty() => Ok(Self::Sound(filepath.into())),
+            _ => Err(format!("invalid action: {}", s)),
+        }
+    }
+}
+
+/// コマンドライン引数です。
+struct Args {
+    filepath: String,
+    bg_color: u32,
+    command: Command,
+    /// フレームの待機方法
+    pacing: timer::Strategy,
+    /// fps の実測値を表示するかどうか
+    fps: bool,
+    /// 統計情報の出力先 (拡張子が `.csv` の場合は CSV 、それ以外は JSON)
+    stats: Option<std::path::PathBuf>,
+    /// キャラクターを表示する条件と再生するアニメーション (省略時は `filepath`)
+    schedules: Vec<(timer::Trigger, Option<String>)>,
+    /// 追加するリマインダー (通知までの時間, メッセージ)
+    reminders: Vec<(Duration, String)>,
+    /// 開始するポモドーロタイマーの時間 (作業, 短い休憩, 長い休憩)
+    pomodoro: Option<[Duration; 3]>,
+    /// 実行中のポモドーロタイマーを停止するかどうか
+    stop_pomodoro: bool,
+    /// 休憩を始める際のメッセージ
+    break_message: Option<String>,
+    /// 作業を始める際のメッセージ
+    focus_message: Option<String>,
+    /// 通知の際に再生するアニメーション
+    notify_animation: Option<String>,
+    /// リマインダーの状態の保存先
+    state: Option<PathBuf>,
+    /// 通常のアニメーションの再生速度 (倍率)
+    speed: f64,
+    /// 通常のアニメーションを逆再生するかどうか
+    reverse: bool,
+    /// 通常のアニメーションの再生方法
+    mode: player::Mode,
+    /// 通常のアニメーションで繰り返し再生するフレームの範囲
+    range: Option<Range<usize>>,
+    /// 通常のアニメーションで、遅れた場合に1フレームずつ追いつくかどうか
+    catch_up: bool,
+    /// フレームに結び付けたイベント (タイミング, アニメーション (省略時は `filepath`), 処理)
+    hooks: Vec<(player::Cue, Option<String>, Action)>,
+    /// アニメーションを切り替える際の効果と、切り替えにかかる時間
+    transition: Option<(transition::Effect, Duration)>,
+    /// キャラクターの振る舞いの定義 (指定した場合、 `filepath` の代わりに状態のアニメーションを再生する)
+    states: Option<state::States>,
+    /// キャラクターのパッケージ (指定した場合、 `filepath` は最初の状態のアニメーションの名前になる)
+    character: Option<character::Character>,
+    /// キャラクターの振る舞いを記述したスクリプト
+    script: Option<script::Script>,
+    /// マウスで掴んでいる間に再生するアニメーション
+    held: Option<String>,
+    /// 物理演算の設定 (指定した場合、キャラクターが落下したり投げられたりする)
+    physics: Option<physics::Settings>,
+    /// 歩いている間に再生するアニメーション (右向き、指定した場合は作業領域の下端に沿って歩く)
+    walk: Option<String>,
+    /// 歩き方の設定
+    walk_settings: walk::Settings,
+    /// ポインターへの反応の設定
+    cursor: Option<cursor::Settings>,
+    /// ポインターの向きに合わせて再生するアニメーション
+    faces: Vec<(cursor::Direction, String)>,
+    /// 吹き出しの文字を描画するフォント
+    font: bubble::Font,
+    /// 吹き出しに1秒あたりに表示する文字数 (0 の場合は一度に全て表示する)
+    typing: f64,
+}
+
+fn parse_args() -> Result<Args, Error> {
+    let mut args = std::env::args().skip(1).peekable();
+    let render = args.next_if(|arg| arg == "render").is_some();
+
+    let mut positional = Vec::new();
+    let mut backend = BackendKind::Window;
+    let mut sheet = false;
+    let mut columns = 8;
+    let mut pacing = timer::Strategy::default();
+    let mut fps = false;
+    let mut stats = None;
+    let mut schedules = Vec::new();
+    let mut reminders = Vec::new();
+    let mut pomodoro = None;
+    let mut stop_pomodoro = false;
+    let mut break_message = None;
+    let mut focus_message = None;
+    let mut notify_animation = None;
+    let mut state = default_state_path();
+    let mut speed = 1.0;
+    let mut reverse = false;
+    let mut catch_up = false;
+    let mut mode = player::Mode::default();
+    let mut range = None;
+    let mut hooks = Vec::new();
+    let mut transition = None;
+    let mut states = None;
+    let mut character = None;
+    let mut script = None;
+    let mut held = None;
+    let mut physics = None;
+    let mut walk = None;
+    let mut walk_settings = walk::Settings::default();
+    let mut cursor = None;
+    let mut cursor_speed = None;
+    let mut faces = Vec::new();
+    let mut font = None;
+    let mut font_size = DEFAULT_FONT_SIZE;
+    let mut typing = bubble::DEFAULT_TYPING;
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--term" if !render => backend = BackendKind::Terminal,
+            "--sixel" if !render => backend = BackendKind::Graphics(Protocol::Sixel),
+            "--kitty" if !render => backend = BackendKind::Graphics(Protocol::Kitty),
+            "--pacer" if !render => {
+                pacing = args
+                    .next()
+                    .and_then(|s| s.parse().ok())
+                    .ok_or(Error::ParseArgs)?;
+            }
+            "--fps" if !render => fps = true,
+            "--stats" if !render => stats = Some(args.next().ok_or(Error::ParseArgs)?.into()),
+            "--schedule" if !render => {
+                let value = args.next().ok_or(Error::ParseArgs)?;
+                let (trigger, filepath) = match value.split_once('=') {
+                    Some((trigger, filepath)) => (trigger, Some(filepath.to_string())),
+                    None => (value.as_str(), None),
+                };
+                let trigger = trigger.parse().map_err(|e| {
+                    eprintln!("{}", e);
+                    Error::ParseArgs
+                })?;
+                schedules.push((trigger, filepath));
+            }
+            "--remind" if !render => {
+                let value = args.next().ok_or(Error::ParseArgs)?;
+                let (after, message) = value.split_once('=').ok_or(Error::ParseArgs)?;
+                let after = timer::parse_duration(after).ok_or(Error::ParseArgs)?;
+                reminders.push((after, message.to_string()));
+            }
+            "--pomodoro" if !render => {
+                let value = args.next().ok_or(Error::ParseArgs)?;
+                let durations: Vec<_> = value.split('/').map(timer::parse_duration).collect();
+                pomodoro = match durations[..] {
+                    [Some(focus), Some(short), Some(long)] => Some([focus, short, long]),
+                    _ => return Err(Error::ParseArgs),
+                };
+            }
+            "--stop-pomodoro" if !render => stop_pomodoro = true,
+            "--break-message" if !render => break_message = args.next(),
+            "--focus-message" if !render => focus_message = args.next(),
+            "--notify-animation" if !render => notify_animation = args.next(),
+            "--state" if !render => state = args.next().map(PathBuf::from),
+            "--speed" if !render => {
+                speed = args
+                    .next()
+                    .and_then(|s| s.parse().ok())
+                    .filter(|speed| (player::MIN_SPEED..=player::MAX_SPEED).contains(speed))
+                    .ok_or(Error::ParseArgs)?;
+            }
+            "--reverse" if !render => reverse = true,
+            "--catch-up" if !render => catch_up = true,
+            "--mode" if !render => {
+                mode = args
+                    .next()
+                    .and_then(|s| s.parse().ok())
+                    .ok_or(Error::ParseArgs)?;
+            }
+            "--range" if !render => {
+                let value = args.next().ok_or(Error::ParseArgs)?;
+                let (start, end) = value.split_once('-').ok_or(Error::ParseArgs)?;
+                let start: usize = start.parse().map_err(|_| Error::ParseArgs)?;
+                let end: usize = end.parse().map_err(|_| Error::ParseArgs)?;
+                if start > end {
+                    return Err(Error::ParseArgs);
+                }
+                // 終わりのフレームも含める
+                range = Some(start..end + 1);
+            }
+            "--on" if !render => {
+                let value = args.next().ok_or(Error::ParseArgs)?;
+                let (target, action) = value.split_once('=').ok_or(Error::ParseArgs)?;
+                let (cue, filepath) = match target.split_once('@') {
+                    Some((cue, filepath)) => (cue, Some(filepath.to_string())),
+                    None => (target, None),
+                };
+                let parsed = cue.parse().and_then(|cue| Ok((cue, action.parse()?)));
+                let (cue, action) = parsed.map_err(|e| {
+                    eprintln!("{}", e);
+                    Error::ParseArgs
+                })?;
+                hooks.push((cue, filepath, action));
+            }
+            "--transition" if !render => {
+                let value = args.next().ok_or(Error::ParseArgs)?;
+                let (effect, duration) = match value.split_once(':') {
+                    Some((effect, duration)) => (
+                        effect,
+                        timer::parse_duration(duration).ok_or(Error::ParseArgs)?,
+                    ),
+                    None => (value.as_str(), transition::DEFAULT_DURATION),
+                };
+                let effect = effect.parse().map_err(|e| {
+                    eprintln!("{}", e);
+                    Error::ParseArgs
+                })?;
+                transition = Some((effect, duration));
+            }
+            "--states" if !render => {
+                let path = PathBuf::from(args.next().ok_or(Error::ParseArgs)?);
+                states = Some(state::States::load(&path).map_err(|e| Error::State(path, e))?);
+            }
+            "--script" if !render => {
+                let path = PathBuf::from(args.next().ok_or(Error::ParseArgs)?);
+                script = Some(script::Script::load(&path).map_err(|e| Error::Script(path, e))?);
+            }
+            "--held" if !render => held = args.next(),
+            "--physics" if !render => {
+                physics.get_or_insert_with(physics::Settings::default);
+            }
+            "--gravity" | "--friction" | "--restitution" if !render => {
+                let value: f64 = args
+                    .next()
+                    .and_then(|s| s.parse().ok())
+                    .filter(|value: &f64| value.is_finite() && *value >= 0.0)
+                    // 跳ね返った後の方が速くならないようにする
+                    .filter(|value| arg != "--restitution" || *value <= 1.0)
+                    .ok_or(Error::ParseArgs)?;
+                let settings = physics.get_or_insert_with(physics::Settings::default);
+                match arg.as_str() {
+                    "--gravity" => settings.gravity = value,
+                    "--friction" => settings.friction = value,
+                    _ => settings.restitution = value,
+                }
+            }
+            "--walk" if !render => walk = args.next(),
+            "--walk-speed" if !render => {
+                walk_settings.speed = args
+                    .next()
+                    .and_then(|s| s.parse().ok())
+                    .filter(|speed: &f64| speed.is_finite() && *speed > 0.0)
+                    .ok_or(Error::ParseArgs)?;
+            }
+            "--climb" if !render => walk_settings.climb = true,
+            "--sit" if !render => {
+                walk_settings.sit = args
+                    .next()
+                    .and_then(|s| timer::parse_duration(&s))
+                    .ok_or(Error::ParseArgs)?;
+            }
+            "--cursor" if !render => {
+                let value = args.next().ok_or(Error::ParseArgs)?;
+                let (reaction, distance) = match value.split_once(':') {
+                    Some((reaction, distance)) => (
+                        reaction,
+                        distance
+                            .parse()
+                            .ok()
+                            .filter(|distance: &f64| distance.is_finite() && *distance >= 0.0)
+                            .ok_or(Error::ParseArgs)?,
+                    ),
+                    None => (value.as_str(), cursor::DEFAULT_DISTANCE),
+                };
+                let reaction = reaction.parse().map_err(|e| {
+                    eprintln!("{}", e);
+                    Error::ParseArgs
+                })?;
+                cursor = Some(cursor::Settings {
+                    distance,
+                    ..cursor::Settings::new(reaction)
+                });
+            }
+            "--cursor-speed" if !render => {
+                cursor_speed = args
+                    .next()
+                    .and_then(|s| s.parse().ok())
+                    .filter(|speed: &f64| speed.is_finite() && *speed > 0.0);
+                cursor_speed.ok_or(Error::ParseArgs)?;
+            }
+            "--face" if !render => {
+                let value = args.next().ok_or(Error::ParseArgs)?;
+                let (direction, filepath) = value.split_once('=').ok_or(Error::ParseArgs)?;
+                let direction = direction.parse().map_err(|e| {
+                    eprintln!("{}", e);
+                    Error::ParseArgs
+                })?;
+                faces.push((direction, filepath.to_string()));
+            }
+            "--font" if !render => font = Some(PathBuf::from(args.next().ok_or(Error::ParseArgs)?)),
+            "--font-size" if !render => {
+                font_size = args
+                    .next()
+                    .and_then(|s| s.parse().ok())
+                    .filter(|size: &f32| size.is_finite() && *size > 0.0)
+                    .ok_or(Error::ParseArgs)?;
+            }
+            "--typing" if !render => {
+                typing = args
+                    .next()
+                    .and_then(|s| s.parse().ok())
+                    .filter(|typing: &f64| {
+                        *typing == 0.0 || (typing.is_finite() && *typing >= bubble::MIN_TYPING)
+                    })
+                    .ok_or(Error::ParseArgs)?;
+            }
+            "--sheet" if render => sheet = true,
+            "--columns" if render => {
+                columns = args
+                    .next()
+                    .and_then(|n| n.parse().ok())
+                    .ok_or(Error::ParseArgs)?;
+            }
+            _ if arg.starts_with("--") => return Err(Error::ParseArgs),
+            _ => positional.push(arg),
+        }
+    }
+
+    let command = match render {
+        true if positional.len() == 2 => Command::Render {
+            output: positional.pop().unwrap().into(),
+            sheet,
+            columns,
+        },
+        false if positional.len() == 1 => {
+            // ディレクトリや ZIP ファイルはキャラクターのパッケージとして読み込む
+            let path = PathBuf::from(&positional[0]);
+            if path.is_dir() || path.extension().is_some_and(|ext| ext == "zip") {
+                let loaded =
+                    character::Character::load(&path).map_err(|e| Error::Character(path, e))?;
+                positional[0] = loaded.states.states[0].animation.clone();
+                states = Some(loaded.states.clone());
+                character = Some(loaded);
+            }
+            Command::Run(backend)
+        }
+        // 振る舞いを定義した場合は、最初の状態のアニメーションを通常のアニメーションにする
+        false if positional.is_empty() && states.is_some() => {
+            let states = states.as_ref().unwrap();
+            positional.push(states.states[0].animation.clone());
+            Command::Run(backend)
+        }
+        _ => return Err(Error::ParseArgs),
+    };
+    // 床の上を歩いたり落下したりする場合は、ポインターに反応しても横にだけ動く
+    if let Some(settings) = cursor.as_mut() {
+        settings.speed = cursor_speed.unwrap_or(settings.speed);
+        settings.ground = physics.is_some() || walk.is_some();
+    }
+    let font = match font {
+        Some(path) => bubble::Font::load(&path, font_size).map_err(|e| Error::Font(path, e))?,
+        None => bubble::Font::bitmap(MESSAGE_SCALE),
+    };
     // let bg_color = args.get(2).cloned().ok_or(Error::ParseArgs)?;
-    Ok((path, u32::from_str_radix("0000FF", 16).unwrap()))
+    Ok(Args {
+        filepath: positional.pop().unwrap(),
+        bg_color: u32::from_str_radix("0000FF", 16).unwrap(),
+        command,
+        pacing,
+        fps,
+        stats,
+        schedules,
+        reminders,
+        pomodoro,
+        stop_pomodoro,
+        break_message,
+        focus_message,
+        notify_animation,
+        state,
+        speed,
+        reverse,
+        mode,
+        range,
+        catch_up,
+        hooks,
+        transition,
+        states,
+        character,
+        script,
+        held,
+        physics,
+        walk,
+        walk_settings,
+        cursor,
+        faces,
+        font,
+        typing,
+    })
 }
 
-fn get_image_size(image: &img::Gif) -> Result<(u16, u16), Error> {
-    let wframe = image
-        .info
-        .iter()
-        .max_by_key(|info| info.top + info.width)
-        .ok_or(Error::ParseInfo)?;
-    let width = wframe.top + wframe.width;
+/// リマインダーの状態を保存するファイルのデフォルトのパスを取得します。
+fn default_state_path() -> Option<PathBuf> {
+    #[cfg(target_os = "windows")]
+    let dir = std::env::var_os("LOCALAPPDATA").map(PathBuf::from);
+    #[cfg(not(target_os = "windows"))]
+    let dir = std::env::var_os("XDG_STATE_HOME")
+        .map(PathBuf::from)
+        .or_else(|| std::env::var_os("HOME").map(|home| PathBuf::from(home).join(".local/state")));
+    dir.map(|dir| dir.join("bkrbk").join("reminders.txt"))
+}
 
-    let wframe = image
-        .info
-        .iter()
-        .max_by_key(|info| info.top + info.height)
-        .ok_or(Error::ParseInfo)?;
-    let height = wframe.top + wframe.height;
+/// フレームを PNG ファイルとして書き出します。
+fn render(
+    image: &img::Gif,
+    bg_color: u32,
+    output: &std::path::Path,
+    sheet: bool,
+    columns: usize,
+) -> Result<(), Error> {
+    let (width, height) = image.size().ok_or(Error::ParseInfo)?;
+    let (width, height) = (width as usize, height as usize);
+    let frames = render::frames(image, bg_color);
+    if sheet {
+        render::ContactSheet::new(&frames, width, height, columns, bg_color)
+            .write_png(output)
+            .map_err(Error::Render)?;
+        return Ok(());
+    }
+
+    std::fs::create_dir_all(output).map_err(|e| Error::Render(render::Error::Io(e)))?;
+    for (i, frame) in frames.iter().enumerate() {
+        let path = output.join(format!("frame_{:03}.png", i));
+        render::write_png(&path, &frame.image, width, height, Some(bg_color))
+            .map_err(Error::Render)?;
+    }
+    Ok(())
+}
+
+/// 実行環境に合わせたウィンドウを作成します。
+#[cfg(target_os = "windows")]
+fn create_backend(width: u16, height: u16, bg_color: u32) -> Result<Box<dyn Backend>, Error> {
+    use bkrbk::win;
+
+    // NOTE: プロパティに自身のアドレスを登録するため、先にヒープ上へ確保してから初期化する
+    let mut core = Box::new(win::Core::new("bkrbk", width as _, height as _, bg_color));
+    core.init().map_err(Error::Windows)?;
+    core.reset();
+    Ok(core)
+}
+
+/// 実行環境に合わせたウィンドウを作成します。
+///
+/// Wayland のセッションでは レイヤーシェル を利用し、利用できない場合は X11 (XWayland) で表示します。
+#[cfg(target_os = "linux")]
+fn create_backend(width: u16, height: u16, bg_color: u32) -> Result<Box<dyn Backend>, Error> {
+    use bkrbk::wayland;
+
+    if std::env::var_os("WAYLAND_DISPLAY").is_some() {
+        let anchor = wayland::Anchor::Bottom | wayland::Anchor::Right;
+        match wayland::Window::new("bkrbk", width as _, height as _, bg_color, anchor, 0) {
+            Ok(window) => return Ok(Box::new(window)),
+            Err(e) if std::env::var_os("DISPLAY").is_some() => eprintln!("{}", e),
+            Err(e) => return Err(Error::Wayland(e)),
+        }
+    }
+
+    let window = bkrbk::x11::Window::new("bkrbk", width, height, bg_color).map_err(Error::X11)?;
+    Ok(Box::new(window))
+}
+
+/// 実行環境に合わせたウィンドウを作成します。
+#[cfg(not(any(target_os = "windows", target_os = "linux")))]
+fn create_backend(_width: u16, _height: u16, _bg_color: u32) -> Result<Box<dyn Backend>, Error> {
+    Err(Error::UnsupportedPlatform)
+}
+
+/// キャラクターを隠している間にメッセージを処理する間隔です。
+const IDLE_INTERVAL: Duration = Duration::from_millis(100);
+/// 通知のメッセージを表示する最短の時間です。
+const NOTIFICATION_DURATION: Duration = Duration::from_secs(10);
+/// 吹き出しの文字の拡大率です。 (内蔵のフォントを利用する場合)
+const MESSAGE_SCALE: usize = 2;
+/// 吹き出しの文字の大きさ (画素) のデフォルト値です。 (フォントファイルを利用する場合)
+const DEFAULT_FONT_SIZE: f32 = 16.0;
+/// 吹き出しを表示するウィンドウの大きさです。
+const BUBBLE_SIZE: (u16, u16) = (240, 160);
+/// 切り替えの効果を描画する間隔です。
+const TRANSITION_INTERVAL: Duration = Duration::from_millis(16);
+/// ウィンドウを動かしている間 (落下、投げ、歩き) に描画する間隔です。
+const MOVE_INTERVAL: Duration = Duration::from_millis(16);
+/// ポインターの位置を確認する間隔です。
+const POINTER_INTERVAL: Duration = Duration::from_millis(50);
+/// 足場にする他のウィンドウの一覧を更新する間隔です。
+const PLATFORM_INTERVAL: Duration = Duration::from_secs(1);
+
+/// 再生するアニメーションの一覧です。 (0番目は通常のアニメーション)
+struct Animations {
+    /// 各アニメーションの大きさ (フレームが1つも無い場合は None)
+    sizes: Vec<Option<(u16, u16)>>,
+    /// 合成済みのフレーム (シークや逆再生で任意のフレームを表示するため、先に合成しておく)
+    frames: Vec<Vec<render::Frame>>,
+    /// ファイルパスとアニメーションの番号の対応
+    indices: HashMap<String, usize>,
+    bg_color: u32,
+}
+
+impl Animations {
+    fn new(filepath: &str, gif: img::Gif, bg_color: u32) -> Self {
+        let mut result = Self {
+            sizes: Vec::new(),
+            frames: Vec::new(),
+            indices: HashMap::new(),
+            bg_color,
+        };
+        result.insert(filepath, gif.size(), render::frames(&gif, bg_color));
+        result
+    }
+
+    /// キャラクターのパッケージから全てのアニメーションを読み込みます。
+    ///
+    /// アニメーションの名前をファイルパスの代わりに利用します。最初の状態のアニメーションを通常のアニメーションにします。
+    fn from_character(character: &character::Character, bg_color: u32) -> Self {
+        let mut result = Self {
+            sizes: Vec::new(),
+            frames: Vec::new(),
+            indices: HashMap::new(),
+            bg_color,
+        };
+        let initial = &character.states.states[0].animation;
+        let animations = character.animations.iter();
+        for animation in animations
+            .clone()
+            .filter(|a| a.name == *initial)
+            .chain(animations.filter(|a| a.name != *initial))
+        {
+            result.insert(
+                &animation.name,
+                animation.size(),
+                animation.frames(bg_color),
+            );
+        }
+        result
+    }
+
+    /// 合成済みのアニメーションを追加し、その番号を返します。
+    fn insert(&mut self, key: &str, size: Option<(u16, u16)>, frames: Vec<render::Frame>) -> usize {
+        self.sizes.push(size);
+        self.frames.push(frames);
+        self.indices.insert(key.to_string(), self.frames.len() - 1);
+        self.frames.len() - 1
+    }
+
+    /// ファイルパスに対応するアニメーションの番号を取得します。 (初めての場合は読み込みます)
+    ///
+    /// ファイルパスが None の場合は通常のアニメーションを返します。
+    fn load(&mut self, filepath: Option<&str>) -> Result<usize, Error> {
+        let Some(filepath) = filepath else {
+            return Ok(0);
+        };
+        self.load_as(filepath, filepath)
+    }
 
-    Ok((width, height))
+    /// ファイルパスのアニメーションを、指定した名前で読み込みます。 (読み込み済みの場合はその番号を返します)
+    fn load_as(&mut self, key: &str, filepath: &str) -> Result<usize, Error> {
+        if let Some(index) = self.indices.get(key) {
+            return Ok(*index);
+        }
+        let gif = open_gif(filepath)?;
+        let frames = render::frames(&gif, self.bg_color);
+        Ok(self.insert(key, gif.size(), frames))
+    }
+
+    /// 読み込み済みのアニメーションの番号を取得します。 (None の場合は通常のアニメーション)
+    fn find(&self, filepath: Option<&str>) -> Option<usize> {
+        match filepath {
+            Some(filepath) => self.indices.get(filepath).copied(),
+            None => Some(0),
+        }
+    }
+
+    /// アニメーションの名前 (ファイルパス) を取得します。
+    fn name_of(&self, index: usize) -> &str {
+        self.indices
+            .iter()
+            .find(|(_, i)| **i == index)
+            .map(|(name, _)| name.as_str())
+            .unwrap_or_default()
+    }
+
+    /// アニメーションの大きさを取得します。
+    fn size_of(&self, index: usize) -> (u16, u16) {
+        self.sizes[index].unwrap_or_default()
+    }
+
+    fn frames(&self, index: usize) -> &[render::Frame] {
+        &self.frames[index]
+    }
+
+    /// アニメーションを再生するためのプレイヤーを作成します。
+    fn player(&self, index: usize) -> player::Player {
+        let delays: Vec<_> = self.frames[index].iter().map(|f| f.delay).collect();
+        player::Player::new(&delays)
+    }
+
+    /// 全てのアニメーションを描画できる大きさを取得します。
+    fn size(&self) -> Result<(u16, u16), Error> {
+        self.sizes.iter().try_fold((0, 0), |(w, h), size| {
+            let (width, height) = size.ok_or(Error::ParseInfo)?;
+            Ok((w.max(width), h.max(height)))
+        })
+    }
 }
 
-pub struct Counter {
-    max: usize,
-    value: usize,
+/// 予定や通知によってキャラクターを表示している状態です。
+struct Appearance {
+    /// 表示を終える時刻
+    until: Instant,
 }
 
-impl Counter {
-    pub fn new(max: usize) -> Self {
-        Self { max, value: 0 }
+/// 通常のアニメーションを、引数で指定した方法で再生するプレイヤーを作成します。
+fn main_player(animations: &Animations, args: &Args) -> player::Player {
+    let mut player = animations.player(0);
+    player.set_speed(args.speed);
+    player.set_reverse(args.reverse);
+    player.set_mode(args.mode);
+    if args.catch_up {
+        player.set_late_policy(timer::LatePolicy::CatchUp);
     }
+    if let Some(range) = args.range.clone() {
+        // 範囲は読み込んだ直後に確かめている
+        let _ = player.set_range(range);
+    }
+    player
+}
+
+/// 状態遷移で決めたキャラクターの振る舞いです。
+struct Behavior {
+    machine: state::StateMachine,
+    /// 各状態で再生するアニメーションの番号
+    animations: Vec<usize>,
+}
+
+/// 予定や通知で表示していない間に再生するアニメーションの番号と、そのプレイヤーを作成します。
+///
+/// 振る舞いを定義した場合は現在の状態に合わせ、それ以外の場合は通常のアニメーションを再生します。
+fn idle_player(
+    animations: &Animations,
+    args: &Args,
+    behavior: Option<&Behavior>,
+) -> (usize, player::Player) {
+    let Some(behavior) = behavior else {
+        return (0, main_player(animations, args));
+    };
+    let state = behavior.machine.state();
+    let index = behavior.animations[behavior.machine.current()];
+    let mut player = animations.player(index);
+    player.set_mode(state.mode);
+    player.set_speed(state.speed);
+    (index, player)
+}
+
+/// アニメーションを再生するプレイヤーを作成します。
+fn start_player(animations: &Animations, args: &Args, index: usize) -> player::Player {
+    match index {
+        0 => main_player(animations, args),
+        _ => animations.player(index),
+    }
+}
+
+/// 吹き出しに表示できない文字がある場合は知らせます。
+fn warn_missing_glyphs(font: &bubble::Font, text: &str) {
+    let missing: String = font.missing(text).into_iter().collect();
+    if missing.is_empty() {
+        return;
+    }
+    match font {
+        bubble::Font::Bitmap { .. } => eprintln!(
+            "the built-in font can not show \"{}\"; use --font to choose a font file",
+            missing
+        ),
+        bubble::Font::Outline { .. } => eprintln!("the font can not show \"{}\"", missing),
+    }
+}
+
+/// 切り替えの効果を始めます。 (引数で指定していない場合は None を返します)
+///
+/// `from` は切り替え前に表示していた画像です。
+fn begin_transition(args: &Args, from: Vec<u32>) -> Option<(transition::Transition, Vec<u32>)> {
+    args.transition
+        .map(|(effect, duration)| (transition::Transition::new(effect, duration), from))
+}
 
-    pub fn update(&mut self) -> usize {
-        if self.value < self.max {
-            let result = self.value;
-            self.value += 1;
-            result
-        } else {
-            let result = 0;
-            self.value = 1;
-            result
+/// コマンドをシェル経由で実行します。 (終了は待ちません)
+fn spawn(command: &str) -> std::io::Result<std::process::Child> {
+    #[cfg(target_os = "windows")]
+    let (shell, flag) = ("cmd", "/C");
+    #[cfg(not(target_os = "windows"))]
+    let (shell, flag) = ("sh", "-c");
+    std::process::Command::new(shell)
+        .args([flag, command])
+        .stdin(std::process::Stdio::null())
+        .stdout(std::process::Stdio::null())
+        .spawn()
+}
+
+/// 効果音を再生します。 (OS に付属するコマンドを利用し、終了は待ちません)
+fn play_sound(path: &std::path::Path) -> std::io::Result<std::process::Child> {
+    use std::process::{Command, Stdio};
+    #[cfg(target_os = "windows")]
+    let commands = [(
+        "powershell",
+        vec![
+            "-NoProfile".into(),
+            "-Command".into(),
+            format!(
+                "(New-Object Media.SoundPlayer '{}').PlaySync()",
+                path.display().to_string().replace('\'', "''")
+            ),
+        ],
+    )];
+    #[cfg(target_os = "macos")]
+    let commands = [("afplay", vec![path.display().to_string()])];
+    #[cfg(not(any(target_os = "windows", target_os = "macos")))]
+    let commands = [
+        ("paplay", vec![path.display().to_string()]),
+        ("aplay", vec!["-q".into(), path.display().to_string()]),
+    ];
+
+    // 最初に起動できたコマンドを利用する
+    let mut programs = Vec::new();
+    for (program, args) in commands {
+        let result = Command::new(program)
+            .args(args)
+            .stdin(Stdio::null())
+            .stdout(Stdio::null())
+            .stderr(Stdio::null())
+            .spawn();
+        match result {
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => programs.push(program),
+            result => return result,
         }
     }
+    Err(std::io::Error::new(
+        std::io::ErrorKind::NotFound,
+        format!("no sound player found ({})", programs.join(", ")),
+    ))
 }
 
-const DEFAULT_BG: u32 = 0x00_FF_FF_FF;
+/// `width` 列の画像の指定した位置に、吹き出しを重ねて描画します。 (透過色の部分と、はみ出した部分は描画しません)
+fn draw_bubble(
+    image: &mut [u32],
+    width: usize,
+    bubble: &bubble::Image,
+    (x, y): (i32, i32),
+    bg: u32,
+) {
+    let height = image.len() / width;
+    for (dy, row) in bubble.pixels.chunks_exact(bubble.width).enumerate() {
+        for (dx, pixel) in row.iter().enumerate() {
+            let (px, py) = (x + dx as i32, y + dy as i32);
+            if *pixel == bg || px < 0 || py < 0 || px as usize >= width || py as usize >= height {
+                continue;
+            }
+            image[py as usize * width + px as usize] = *pixel;
+        }
+    }
+}
 
 fn main() {
-    let (filepath, bg_color) = parse_args().unwrap();
-    let image = img::Gif::new(&filepath);
-    let (width, height) = get_image_size(&image).unwrap();
+    let mut args = parse_args().unwrap_or_else(|e| exit_with(e));
+    let backend = match args.command {
+        Command::Run(backend) => backend,
+        Command::Render {
+            output,
+            sheet,
+            columns,
+        } => {
+            let image = open_gif(&args.filepath).unwrap_or_else(|e| exit_with(e));
+            if let Err(e) = render(&image, args.bg_color, &output, sheet, columns) {
+                eprintln!("{}", e);
+                std::process::exit(1);
+            }
+            return;
+        }
+    };
+    let mut animations = match &args.character {
+        Some(character) => Animations::from_character(character, args.bg_color),
+        None => match open_gif(&args.filepath) {
+            Ok(gif) => Animations::new(&args.filepath, gif, args.bg_color),
+            Err(e) => exit_with(e),
+        },
+    };
+    if let Some(range) = args.range.clone() {
+        if let Err(e) = animations.player(0).set_range(range) {
+            eprintln!("{}", e);
+            std::process::exit(1);
+        }
+    }
+    let mut scheduler = timer::Scheduler::new();
+    for (trigger, filepath) in std::mem::take(&mut args.schedules) {
+        let index = animations.load(filepath.as_deref());
+        scheduler.add(trigger, index.unwrap_or_else(|e| exit_with(e)));
+    }
 
-    let mut core = win::Core::new("bkrbk", width as _, height as _, bg_color);
-    core.init().unwrap();
-    core.reset();
-    core.front.show(
-        core.handler,
-        core.class_name.reference,
-        &core.main_window_name.entity,
-    );
+    // リマインダー (前回までの状態に引数で指定した予定を追加する)
+    let mut reminders = match &args.state {
+        Some(path) => reminder::Reminders::load(path).unwrap_or_else(|e| {
+            eprintln!("{}: {}", path.display(), Error::Reminder(e));
+            std::process::exit(1);
+        }),
+        None => reminder::Reminders::default(),
+    };
+    let now = SystemTime::now();
+    for (after, message) in &args.reminders {
+        reminders.add(now + *after, message, args.notify_animation.as_deref());
+    }
+    if args.stop_pomodoro {
+        reminders.stop_pomodoro();
+    }
+    if let Some([focus, short_break, long_break]) = args.pomodoro {
+        let mut settings = reminder::PomodoroSettings {
+            focus,
+            short_break,
+            long_break,
+            ..Default::default()
+        };
+        for (notification, message) in [
+            (&mut settings.break_notification, &args.break_message),
+            (&mut settings.focus_notification, &args.focus_message),
+        ] {
+            notification.message = message.clone().unwrap_or(notification.message.clone());
+            notification.animation = args.notify_animation.clone();
+        }
+        reminders.start_pomodoro(now, settings);
+    }
+    if let Err(e) = reminders.save() {
+        eprintln!("{}", Error::Reminder(e));
+    }
+    // 通知で再生するアニメーションは、ウィンドウの大きさを決めるために先に読み込む
+    let notifications = reminders
+        .reminders()
+        .iter()
+        .map(|reminder| &reminder.notification)
+        .chain(reminders.pomodoro().into_iter().flat_map(|pomodoro| {
+            [
+                &pomodoro.settings.break_notification,
+                &pomodoro.settings.focus_notification,
+            ]
+        }));
+    for notification in notifications {
+        if let Err(e) = animations.load(notification.animation.as_deref()) {
+            exit_with(e);
+        }
+    }
+    // 状態遷移による振る舞い
+    let mut behavior = args.states.take().map(|states| {
+        let indices = states
+            .states
+            .iter()
+            .map(|state| animations.load(Some(&state.animation)))
+            .collect::<Result<_, _>>()
+            .unwrap_or_else(|e| exit_with(e));
+        Behavior {
+            machine: state::StateMachine::new(states),
+            animations: indices,
+        }
+    });
+    // フレームに結び付けたイベント (アニメーションごと)
+    let mut cues: Vec<player::Cues<Action>> = Vec::new();
+    let mut hooks = std::mem::take(&mut args.hooks);
+    // パッケージの効果音は一時ディレクトリに書き出して再生する
+    let sound_dir = std::env::temp_dir().join(format!("bkrbk-{}", std::process::id()));
+    if let Some(character) = args.character.take() {
+        for animation in &character.animations {
+            for (cue, name) in &animation.sounds {
+                // 名前はパスに使わず、効果音の番号のディレクトリに書き出す
+                let Some(index) = character.sounds.iter().position(|s| s.name == *name) else {
+                    continue;
+                };
+                let sound = &character.sounds[index];
+                let Some(file_name) = std::path::Path::new(&sound.file).file_name() else {
+                    continue;
+                };
+                let path = sound_dir.join(index.to_string()).join(file_name);
+                let written = std::fs::create_dir_all(path.parent().unwrap())
+                    .and_then(|_| std::fs::write(&path, &sound.data));
+                match written {
+                    Ok(_) => hooks.push((*cue, Some(animation.name.clone()), Action::Sound(path))),
+                    Err(e) => eprintln!("{}: {}", path.display(), e),
+                }
+            }
+        }
+    }
+    for (cue, filepath, action) in hooks {
+        if let Action::Play(target) = &action {
+            if let Err(e) = animations.load(target.as_deref()) {
+                exit_with(e);
+            }
+        }
+        let index = animations
+            .load(filepath.as_deref())
+            .unwrap_or_else(|e| exit_with(e));
+        if cues.len() <= index {
+            cues.resize_with(index + 1, player::Cues::new);
+        }
+        cues[index].add(cue, action);
+    }
+
+    // スクリプト (トップレベルで読み込むアニメーションは、ウィンドウの大きさを決めるために先に読み込む)
+    let mut script = args.script.take();
+    if let Some(script) = script.as_mut() {
+        if let Err(e) = script.start() {
+            eprintln!("{}: {}", script.name(), e);
+        }
+        // 読み込めなかったアニメーションは、再生しようとした時にも知らせる
+        for name in script.loads() {
+            if let Err(e) = animations.load_as(&name, &script.resolve(&name).to_string_lossy()) {
+                eprintln!("{}: {}", script.name(), e);
+            }
+        }
+    }
+
+    // マウスで掴んでいる間に再生するアニメーション
+    let held = args.held.as_deref().map(|filepath| {
+        animations
+            .load(Some(filepath))
+            .unwrap_or_else(|e| exit_with(e))
+    });
+    // ポインターの向きに合わせて再生するアニメーション
+    let faces: Vec<(cursor::Direction, usize)> = args
+        .faces
+        .iter()
+        .map(|(direction, filepath)| Ok((*direction, animations.load(Some(filepath))?)))
+        .collect::<Result<_, _>>()
+        .unwrap_or_else(|e| exit_with(e));
+    // 歩いている間に再生するアニメーション
+    let walking = args.walk.as_deref().map(|filepath| {
+        animations
+            .load(Some(filepath))
+            .unwrap_or_else(|e| exit_with(e))
+    });
+
+    // 予定や通知を指定した場合は、その時刻になるまで隠しておく
+    let hidden = !scheduler.is_empty() || !args.reminders.is_empty() || args.pomodoro.is_some();
+
+    // 全てのアニメーションを描画できる大きさにする
+    let (width, height) = animations.size().unwrap_or_else(|e| {
+        eprintln!("{}", e);
+        std::process::exit(1);
+    });
+
+    let mut backend = match backend {
+        BackendKind::Window => create_backend(width, height, args.bg_color),
+        BackendKind::Terminal => Ok(Box::new(bkrbk::term::Terminal::stdout(
+            width as _,
+            height as _,
+            args.bg_color,
+        )) as Box<dyn Backend>),
+        BackendKind::Graphics(protocol) => Ok(Box::new(bkrbk::term::graphics::Graphics::stdout(
+            protocol,
+            width as _,
+            height as _,
+            args.bg_color,
+        )) as Box<dyn Backend>),
+    }
+    .unwrap_or_else(|e| {
+        eprintln!("{}", e);
+        std::process::exit(1);
+    });
+    // 吹き出しを表示するウィンドウ (作成できない描画先ではキャラクターの画像に重ねる)
+    let mut overlay = backend.overlay(BUBBLE_SIZE.0, BUBBLE_SIZE.1);
+    let mut overlay_shown = false;
+    let work_area = backend.work_area();
+    // 描画先が扱えないために無視する機能を知らせる
+    let placeable = backend.position().is_some() && work_area.is_some();
+    let ignored = [
+        ("--physics", args.physics.is_some() && !placeable),
+        ("--walk", walking.is_some() && !placeable),
+        (
+            "--cursor",
+            args.cursor.is_some() && (!placeable || backend.pointer_position().is_none()),
+        ),
+    ];
+    for (flag, _) in ignored.iter().filter(|(_, ignored)| *ignored) {
+        eprintln!(
+            "{} is ignored: this backend can not place the window or track the pointer",
+            flag
+        );
+    }
+    let mut visible = !hidden;
+    if visible {
+        backend.show();
+    } else {
+        backend.hide();
+    }
 
-    let mut timer = timer::Timer::new(1);
-    let mut current = Counter::new(image.info.len());
+    // 再生中のアニメーションの番号
+    let (mut current, mut player) = idle_player(&animations, &args, behavior.as_ref());
+    // イベントで実行したコマンド (終了したものから取り除く)
+    let mut children: Vec<std::process::Child> = Vec::new();
+    // 予定や通知によって表示している状態
+    let mut appearance: Option<Appearance> = None;
+    // 表示している吹き出し (通知のメッセージとスクリプトのセリフ)
+    let mut speech: Option<bubble::Bubble> = None;
+    // スクリプトにまだ知らせていない、状態遷移で移った状態の名前
+    let mut entered: Option<String> = None;
+    // マウスによるクリックとドラッグ
+    let mut drag = drag::Drag::default();
+    // 落下と投げ (ウィンドウの位置と作業領域が分かる描画先のみ)
+    let mut body = args.physics.and_then(|settings| {
+        let (x, y) = backend.position()?;
+        let mut body = physics::Body::new(
+            settings,
+            backend.work_area()?,
+            (width as u32, height as u32),
+        );
+        body.set_position(x, y);
+        Some(body)
+    });
+    // 投げた速さを求めるためのドラッグ中の動き
+    let mut tracker = physics::Tracker::default();
+    // 作業領域の下端や他のウィンドウの上を歩く (ウィンドウの位置と作業領域が分かる描画先のみ)
+    let mut walker = walking.and_then(|_| {
+        let (x, y) = backend.position()?;
+        let area = backend.work_area()?;
+        let mut walker = walk::Walker::new(args.walk_settings, area, (width as u32, height as u32));
+        walker.set_position(x, y);
+        Some(walker)
+    });
+    // 足場にする他のウィンドウの一覧を最後に更新した時刻
+    let mut platforms_updated: Option<Instant> = None;
+    // ポインターを追いかけたり、逃げたり、見たりする (ポインターの位置が分かる描画先のみ)
+    let mut follower = args.cursor.and_then(|settings| {
+        backend.pointer_position()?;
+        let (x, y) = backend.position()?;
+        let area = backend.work_area()?;
+        let mut follower = cursor::Follower::new(settings, area, (width as u32, height as u32));
+        follower.set_position(x, y);
+        Some(follower)
+    });
+    // 向きや歩きに合わせて切り替えたアニメーション (反応を終えたら元に戻す)
+    let mut steered: Option<usize> = None;
+    // 最後に表示した画像の不透明な部分 (クリックの判定と入力範囲に利用する)
+    let mut mask = hit::Mask::default();
+    // フレームを合成する画像 (メッセージを重ねる前の状態を保持する)
+    let mut canvas = vec![args.bg_color; width as usize * height as usize];
+    let blank = canvas.clone();
+    // 切り替えの効果と、切り替え前に表示していた画像
+    let mut transition: Option<(transition::Transition, Vec<u32>)> = None;
+    // 切り替えの効果を終えてからウィンドウを隠すかどうか
+    let mut hiding = false;
+    let mut pacer = timer::Pacer::new(args.pacing);
+    let mut stats = timer::FrameStats::default();
+    if args.fps {
+        stats.add_sink(Box::new(timer::FpsPrinter::default()));
+    }
 
     // main loop
     'main: loop {
-        match core.update() {
+        match backend.update() {
             None => break 'main,
             Some(_) => {
-                let c = current.update();
-                let info = image.info.get(c).unwrap(); // TODO: unwrap() をなくす
-
-                // 描画
-                let palette = {
-                    let palette = info.palette.clone().unwrap();
-                    let mut iter = palette.into_iter();
-                    let mut result = vec![DEFAULT_BG; u8::MAX as usize];
-                    let mut count = 0;
-                    while let Some(color) = iter.next() {
-                        let r = color;
-                        let g = iter.next().unwrap();
-                        let b = iter.next().unwrap();
-                        let color = ((r as u32) << 16) + ((g as u32) << 8) + (b as u32);
-                        result[count] = color;
-                        count += 1;
-                    }
-                    result
+                let now = Instant::now();
+                if let Some(overlay) = overlay.as_mut() {
+                    overlay.update();
+                }
+
+                // 予定や通知の時刻になったら、キャラクターを表示してアニメーションを再生する
+                let mut events: Vec<(usize, Option<String>)> = scheduler
+                    .poll()
+                    .into_iter()
+                    .map(|index| (index, None))
+                    .collect();
+                let notifications = reminders.poll(SystemTime::now());
+                if !notifications.is_empty() {
+                    if let Err(e) = reminders.save() {
+                        eprintln!("{}", Error::Reminder(e));
+                    }
+                }
+                for notification in notifications {
+                    // 読み込めない場合は通常のアニメーションでメッセージを表示する
+                    let index = animations
+                        .load(notification.animation.as_deref())
+                        .unwrap_or_else(|e| {
+                            eprintln!("{}", e);
+                            0
+                        });
+                    events.push((index, Some(notification.message)));
+                }
+                if let Some(&(index, _)) = events.last() {
+                    let messages: Vec<String> = events
+                        .into_iter()
+                        .filter_map(|(_, message)| message)
+                        .collect();
+                    current = index;
+                    player = animations.player(index);
+                    let mut until = now + player.duration();
+                    if !messages.is_empty() {
+                        let text = messages.join("\n");
+                        warn_missing_glyphs(&args.font, &text);
+                        let message =
+                            bubble::Bubble::new(&text, args.typing, NOTIFICATION_DURATION);
+                        until = until.max(now + message.duration());
+                        speech = Some(message);
+                    }
+                    appearance = Some(Appearance { until });
+                    // 隠していた場合は何もない状態から切り替える
+                    let from = match visible {
+                        true => canvas.clone(),
+                        false => blank.clone(),
+                    };
+                    transition = begin_transition(&args, from);
+                    canvas.fill(args.bg_color);
+                    backend.show();
+                    visible = true;
+                    hiding = false;
+                }
+                if appearance.as_ref().is_some_and(|a| now >= a.until) {
+                    appearance = None;
+                    transition = begin_transition(&args, canvas.clone());
+                    canvas.fill(args.bg_color);
+                    (current, player) = idle_player(&animations, &args, behavior.as_ref());
+                    hiding = hidden;
+                }
+                if hiding && transition.as_ref().is_none_or(|(t, _)| t.is_finished()) {
+                    backend.hide();
+                    speech = None;
+                    if let Some(overlay) = overlay.as_mut().filter(|_| overlay_shown) {
+                        overlay.hide();
+                        overlay_shown = false;
+                    }
+                    visible = false;
+                    hiding = false;
+                    transition = None;
+                }
+                if transition.as_ref().is_some_and(|(t, _)| t.is_finished()) {
+                    transition = None;
+                }
+
+                if !visible {
+                    // 次の予定まで待機する (メッセージを処理するため一定時間ごとに戻る)
+                    let due = reminders.next_due().map(|due| {
+                        now + due
+                            .duration_since(SystemTime::now())
+                            .unwrap_or(Duration::ZERO)
+                    });
+                    let deadline = [scheduler.next_deadline(), due]
+                        .into_iter()
+                        .flatten()
+                        .fold(now + IDLE_INTERVAL, Instant::min);
+                    timer::sleep_until(deadline);
+                    continue 'main;
+                }
+
+                // マウスの操作と着地 (イベントとして状態遷移とスクリプトに知らせる)
+                let mut pointer_events = Vec::new();
+                for event in backend.pointer_events() {
+                    let window = backend.position().unwrap_or_default();
+                    // キャラクターが描画されていない部分のクリックは無視する
+                    if let PointerEvent::Press(x, y) = event {
+                        if !mask.contains(x - window.0, y - window.1) {
+                            continue;
+                        }
+                    }
+                    match drag.handle(event, window) {
+                        Some(drag::DragEvent::Click) => pointer_events.push("click"),
+                        Some(drag::DragEvent::Start) => {
+                            if let Some(body) = body.as_mut() {
+                                body.hold();
+                            }
+                            tracker.clear();
+                            pointer_events.push("drag");
+                        }
+                        Some(drag::DragEvent::Move(x, y)) => {
+                            tracker.push(x, y);
+                            backend.move_to(x, y);
+                        }
+                        Some(drag::DragEvent::Drop) => {
+                            // 離した時の速さで投げる
+                            if let Some(body) = body.as_mut() {
+                                body.set_position(window.0, window.1);
+                                let (vx, vy) = tracker.velocity();
+                                body.throw(vx, vy);
+                            }
+                            pointer_events.push("drop");
+                        }
+                        None => {}
+                    }
+                }
+                if let Some(body) = body.as_mut().filter(|_| !drag.is_dragging()) {
+                    // スクリプトなどで動かされた場合は、その位置から落下させる
+                    if let Some((x, y)) = backend.position().filter(|p| *p != body.position()) {
+                        body.set_position(x, y);
+                    }
+                    let resting = body.is_resting();
+                    if body.update() {
+                        let (x, y) = body.position();
+                        backend.move_to(x, y);
+                    }
+                    if !resting && body.is_resting() {
+                        pointer_events.push("land");
+                    }
+                }
+
+                // 予定や通知で表示している間と、掴んでいる間、落下している間は自分では動かない
+                let grounded = body.as_ref().is_none_or(|b| b.is_resting() || b.is_held());
+                let free = appearance.is_none() && !drag.is_dragging() && grounded;
+
+                // ポインターへの反応
+                let mut chasing = false;
+                if let Some(follower) = follower.as_mut() {
+                    match backend.pointer_position().filter(|_| free) {
+                        Some(pointer) => {
+                            if let Some((x, y)) =
+                                backend.position().filter(|p| *p != follower.position())
+                            {
+                                follower.set_position(x, y);
+                            }
+                            if follower.update(pointer) {
+                                // 動いている間は落下させない
+                                if let Some(body) = body.as_mut() {
+                                    body.hold();
+                                }
+                                let (x, y) = follower.position();
+                                backend.move_to(x, y);
+                                chasing = true;
+                            }
+                        }
+                        None => follower.pause(),
+                    }
+                }
+                let facing = follower.as_ref().and_then(|f| f.select(&faces));
+
+                // 歩く (ポインターに反応している間も止める)
+                let mut moving = false;
+                if let Some(walker) = walker.as_mut() {
+                    if free && !chasing && facing.is_none() {
+                        // 歩いている間は落下させない
+                        if let Some(body) = body.as_mut() {
+                            body.hold();
+                        }
+                        let stale =
+                            platforms_updated.is_none_or(|at| now >= at + PLATFORM_INTERVAL);
+                        if walker.settings.climb && stale {
+                            walker.set_platforms(backend.other_windows());
+                            platforms_updated = Some(now);
+                        }
+                        if let Some((x, y)) = backend.position().filter(|p| *p != walker.position())
+                        {
+                            walker.set_position(x, y);
+                        }
+                        if walker.update() {
+                            let (x, y) = walker.position();
+                            backend.move_to(x, y);
+                        }
+                        moving = walker.motion() != walk::Motion::Sitting;
+                    } else {
+                        walker.pause();
+                    }
+                }
+                for name in pointer_events {
+                    let moved = behavior
+                        .as_mut()
+                        .filter(|_| appearance.is_none())
+                        .and_then(|b| {
+                            b.machine
+                                .event(name)
+                                .then(|| b.machine.state().name.clone())
+                        });
+                    if let Some(state) = moved {
+                        entered = Some(state);
+                        (current, player) = idle_player(&animations, &args, behavior.as_ref());
+                        canvas.fill(args.bg_color);
+                    } else if name == "drop" && held.is_some_and(|held| current == held) {
+                        // 掴んでいる間のアニメーションから戻す
+                        (current, player) = idle_player(&animations, &args, behavior.as_ref());
+                        canvas.fill(args.bg_color);
+                    }
+                    if let Some(script) = script.as_mut() {
+                        if let Err(e) = script.event(name) {
+                            eprintln!("{}: {}", script.name(), e);
+                        }
+                    }
+                }
+                if let Some(index) = held.filter(|held| drag.is_dragging() && current != *held) {
+                    current = index;
+                    player = animations.player(index);
+                    canvas.fill(args.bg_color);
+                }
+                // ポインターの方を向いている間は向きに合うアニメーションを、歩いている間は歩くアニメーションを再生し、
+                // 反応を終えたり座ったりしたら戻す
+                let wanted = facing
+                    .map(|(index, _)| index)
+                    .or(walking.filter(|_| moving));
+                if let Some(index) = wanted.filter(|index| current != *index) {
+                    current = index;
+                    player = animations.player(index);
+                    canvas.fill(args.bg_color);
+                    steered = Some(index);
+                } else if wanted.is_none() && steered.take().is_some_and(|index| current == index) {
+                    (current, player) = idle_player(&animations, &args, behavior.as_ref());
+                    canvas.fill(args.bg_color);
+                }
+
+                let index = player.update();
+
+                // 描画 (合成済みのフレームを左上に配置する)
+                let (frame_width, _) = animations.size_of(current);
+                // 歩くアニメーションは右向きなので、左へ歩く場合は左右を反転する
+                let mirrored = match facing {
+                    Some((index, mirrored)) if index == current => mirrored,
+                    _ => {
+                        walking == Some(current)
+                            && walker
+                                .as_ref()
+                                .is_some_and(|w| w.direction() == walk::Direction::Left)
+                    }
                 };
+                if let Some(frame) = animations.frames(current).get(index) {
+                    backend.set_palette(&frame.palette);
+                    let frame_width = frame_width as usize;
+                    for (y, row) in frame.image.chunks_exact(frame_width).enumerate() {
+                        let start = y * width as usize;
+                        let target = &mut canvas[start..start + frame_width];
+                        match mirrored {
+                            true => target
+                                .iter_mut()
+                                .zip(row.iter().rev())
+                                .for_each(|(pixel, source)| *pixel = *source),
+                            false => target.copy_from_slice(row),
+                        }
+                    }
+                }
+                let image = backend.image();
+                match &transition {
+                    // 隠す場合は何もない状態へ切り替える
+                    Some((t, from)) if hiding => {
+                        t.draw(from, &blank, image, width as usize, args.bg_color)
+                    }
+                    Some((t, from)) => t.draw(from, &canvas, image, width as usize, args.bg_color),
+                    None => image.copy_from_slice(&canvas),
+                }
+                if speech.as_ref().is_some_and(|b| b.is_finished()) {
+                    speech = None;
+                }
+                // 別のウィンドウを作成できない描画先では、吹き出しを画像の上部に描画する
+                if let Some(speech) = speech.as_ref().filter(|_| overlay.is_none()) {
+                    let rendered = speech.render(&args.font, width as usize, args.bg_color);
+                    let x = (width as usize).saturating_sub(rendered.width) / 2;
+                    draw_bubble(
+                        image,
+                        width as usize,
+                        &rendered,
+                        (x as i32, 0),
+                        args.bg_color,
+                    );
+                }
+                let drawn = hit::Mask::from_image(image, width as usize, args.bg_color);
+                if drawn != mask {
+                    backend.set_input_mask(&drawn);
+                    mask = drawn;
+                }
+                backend.draw();
+
+                // 吹き出しのウィンドウ (キャラクターの不透明な部分の上端に三角形を合わせる)
+                if let Some(overlay) = overlay.as_mut() {
+                    match speech.as_ref() {
+                        Some(speech) => {
+                            let (bubble_width, bubble_height) =
+                                (BUBBLE_SIZE.0 as usize, BUBBLE_SIZE.1 as usize);
+                            let rendered = speech.render(&args.font, bubble_width, args.bg_color);
+                            let image = overlay.image();
+                            image.fill(args.bg_color);
+                            let at = (
+                                (bubble_width.saturating_sub(rendered.width) / 2) as i32,
+                                bubble_height as i32 - rendered.height as i32,
+                            );
+                            draw_bubble(image, bubble_width, &rendered, at, args.bg_color);
+                            if let Some((x, y)) = backend.position() {
+                                let top = mask.rectangles().first().map_or(0, |r| r.y);
+                                let mut x = x + width as i32 / 2 - bubble_width as i32 / 2;
+                                let mut y = y + top - bubble_height as i32;
+                                // 画面からはみ出さないようにする
+                                if let Some(area) = work_area {
+                                    let right = area.x + area.width as i32 - bubble_width as i32;
+                                    let bottom = area.y + area.height as i32 - bubble_height as i32;
+                                    x = x.clamp(area.x, right.max(area.x));
+                                    y = y.clamp(area.y, bottom.max(area.y));
+                                }
+                                overlay.move_to(x, y);
+                            }
+                            overlay.draw();
+                            if !overlay_shown {
+                                overlay.show();
+                                overlay_shown = true;
+                            }
+                        }
+                        None if overlay_shown => {
+                            overlay.hide();
+                            overlay_shown = false;
+                        }
+                        None => {}
+                    }
+                }
 
-                for y in 0..info.height as usize {
-                    for x in 0..info.width as usize {
-                        let base_i = y * info.width as usize + x;
-                        let core_i =
-                            (y + info.top as usize) * width as usize + x + info.left as usize;
-                        let pale_i = info.buffer[base_i];
-
-                        // 0の場合は上書きしない
-                        if c == 0 || 0 != pale_i {
-                            let color = if bg_color == palette[pale_i as usize] {
-                                // bgによってハンドリングしたい場合はここを変える
-                                // DEFAULT_BG
-                                palette[pale_i as usize]
-                            } else {
-                                palette[pale_i as usize]
+                // フレームに結び付けたイベント (表示した直後に発生させる)
+                let actions: Vec<Action> = cues
+                    .get(current)
+                    .map(|cues| cues.poll(&player).into_iter().cloned().collect())
+                    .unwrap_or_default();
+                let mut switched = false;
+                for action in actions {
+                    match action {
+                        Action::Play(filepath) => {
+                            let index = match animations.load(filepath.as_deref()) {
+                                Ok(index) => index,
+                                Err(e) => {
+                                    eprintln!("{}", e);
+                                    continue;
+                                }
                             };
-                            // core.image[core_i] = color;
-                            core.front.image[core_i] = color;
+                            transition = begin_transition(&args, canvas.clone());
+                            current = index;
+                            player = start_player(&animations, &args, current);
+                            canvas.fill(args.bg_color);
+                            switched = true;
                         }
+                        Action::Run(command) => match spawn(&command) {
+                            Ok(child) => children.push(child),
+                            Err(e) => eprintln!("{}: {}", command, e),
+                        },
+                        Action::Sound(path) => match play_sound(&path) {
+                            Ok(child) => children.push(child),
+                            Err(e) => eprintln!("{}: {}", path.display(), e),
+                        },
                     }
                 }
-                // core.front.image = core.image.clone();
-                // core.draw();
-                // core.call_draw();
-                core.front.draw();
-                core.front.call_draw();
+                children.retain_mut(|child| !matches!(child.try_wait(), Ok(Some(_))));
 
-                let millis = info.delay * 10;
-                timer.interval = std::time::Duration::from_millis(millis as u64);
-                timer.sleep();
+                // 状態遷移 (予定や通知で表示している間と、掴んでいる間、歩いている間、ポインターの方を向いている間は止める)
+                if let Some(machine) = behavior
+                    .as_mut()
+                    .filter(|_| {
+                        appearance.is_none() && !drag.is_dragging() && !moving && facing.is_none()
+                    })
+                    .map(|b| &mut b.machine)
+                {
+                    machine.complete(player.completed());
+                    if machine.poll().is_some() {
+                        entered = Some(machine.state().name.clone());
+                        transition = begin_transition(&args, canvas.clone());
+                        (current, player) = idle_player(&animations, &args, behavior.as_ref());
+                        canvas.fill(args.bg_color);
+                        switched = true;
+                    }
+                }
+
+                // スクリプト (エラーは表示して実行を続ける)
+                if let Some(script) = script.as_mut() {
+                    script.set_position(backend.position());
+                    let mut results = Vec::new();
+                    if player.completed() > 0 {
+                        results.push(script.finished(animations.name_of(current)));
+                    }
+                    if let Some(name) = entered.take() {
+                        results.push(script.state(&name));
+                    }
+                    results.push(script.poll());
+                    for e in results.into_iter().filter_map(Result::err) {
+                        eprintln!("{}: {}", script.name(), e);
+                    }
+                    for command in script.take_commands() {
+                        match command {
+                            script::Command::Play(filepath) => {
+                                let Some(index) = animations.find(filepath.as_deref()) else {
+                                    eprintln!(
+                                        "{}: animation is not loaded: {}",
+                                        script.name(),
+                                        filepath.unwrap_or_default()
+                                    );
+                                    continue;
+                                };
+                                transition = begin_transition(&args, canvas.clone());
+                                current = index;
+                                player = start_player(&animations, &args, current);
+                                canvas.fill(args.bg_color);
+                                switched = true;
+                            }
+                            script::Command::MoveTo(x, y) => backend.move_to(x, y),
+                            script::Command::MoveBy(dx, dy) => {
+                                if let Some((x, y)) = backend.position() {
+                                    backend.move_to(x.saturating_add(dx), y.saturating_add(dy));
+                                }
+                            }
+                            script::Command::Say(text, duration) => {
+                                // 通知のメッセージは上書きしない
+                                if appearance.is_some() && speech.is_some() {
+                                    continue;
+                                }
+                                let hold = duration.unwrap_or(NOTIFICATION_DURATION);
+                                warn_missing_glyphs(&args.font, &text);
+                                speech = Some(bubble::Bubble::new(&text, args.typing, hold));
+                                switched = true;
+                            }
+                            script::Command::Emit(event) => {
+                                let Some(machine) = behavior.as_mut().map(|b| &mut b.machine)
+                                else {
+                                    continue;
+                                };
+                                if machine.event(&event) {
+                                    entered = Some(machine.state().name.clone());
+                                    transition = begin_transition(&args, canvas.clone());
+                                    (current, player) =
+                                        idle_player(&animations, &args, behavior.as_ref());
+                                    canvas.fill(args.bg_color);
+                                    switched = true;
+                                }
+                            }
+                        }
+                    }
+                }
+                if switched {
+                    // 切り替えたアニメーションの最初のフレームをすぐに表示する
+                    continue 'main;
+                }
+
+                let mut deadline = player
+                    .next_change()
+                    .unwrap_or(Instant::now() + IDLE_INTERVAL);
+                if transition.is_some() {
+                    // 切り替えの途中は、フレームが変わらなくても一定間隔で描画する
+                    deadline = deadline.min(Instant::now() + TRANSITION_INTERVAL);
+                }
+                let deadlines = [
+                    // 状態遷移を止めている間は、状態の期限で起きても処理しないため待たない
+                    behavior
+                        .as_ref()
+                        .filter(|_| {
+                            appearance.is_none()
+                                && !drag.is_dragging()
+                                && !moving
+                                && facing.is_none()
+                        })
+                        .and_then(|b| b.machine.next_deadline()),
+                    script.as_ref().and_then(|s| s.next_deadline()),
+                    speech.as_ref().and_then(|b| b.next_change()),
+                    // 落下している間は一定間隔で位置を更新する
+                    body.as_ref()
+                        .filter(|body| !body.is_resting())
+                        .map(|_| Instant::now() + MOVE_INTERVAL),
+                    (moving || chasing).then(|| Instant::now() + MOVE_INTERVAL),
+                    follower.as_ref().map(|_| Instant::now() + POINTER_INTERVAL),
+                ];
+                deadline = deadlines.into_iter().flatten().fold(deadline, Instant::min);
+                let lateness = pacer.wait_until(deadline);
+                stats.frame(Instant::now(), lateness, player.skipped());
             }
         }
     }
+    let _ = std::fs::remove_dir_all(&sound_dir);
+    if args.fps || args.stats.is_some() {
+        eprintln!("jitter ({:?}): {}", pacer.strategy, pacer.jitter());
+        eprintln!("{}", stats.summary());
+    }
+    if let Some(path) = args.stats {
+        let data = match path.extension().is_some_and(|ext| ext == "csv") {
+            true => stats.to_csv(),
+            false => stats.to_json(),
+        };
+        if let Err(e) = std::fs::write(&path, data) {
+            eprintln!("{}: {}", path.display(), e);
+        }
+    }
 }
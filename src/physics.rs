@@ -0,0 +1,313 @@
+//! キャラクターを落下させたり、投げたりするための機能です。
+//!
+//! ウィンドウを1つの矩形として扱い、重力で作業領域の下端まで落とし、端で跳ね返らせます。
+//! 経過時間を一定の間隔 (`STEP`) に区切って計算するので、同じ時間だけ進めれば常に同じ結果になります。
+
+use std::time::{Duration, Instant};
+
+use crate::{
+    hit::Rect,
+    timer::{Clock, SystemClock},
+};
+
+/// 1回の計算で進める時間です。
+pub const STEP: Duration = Duration::from_micros(1_000_000 / 240);
+
+/// これより遅い場合は止まっているとみなす速さ (ピクセル/秒) です。
+const REST_SPEED: f64 = 20.0;
+
+/// 投げた速さを求める際に利用する、直前の移動の期間です。
+const TRACK_WINDOW: Duration = Duration::from_millis(80);
+
+/// 物理演算の設定です。
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Settings {
+    /// 重力加速度 (ピクセル/秒²)
+    pub gravity: f64,
+    /// 床の上を滑る際に、1秒あたりに失う水平方向の速さの割合
+    pub friction: f64,
+    /// 跳ね返る際に保つ速さの割合 (0.0 で跳ね返らず、1.0 で減速せずに跳ね返ります。範囲外の値はこの範囲に収めます)
+    pub restitution: f64,
+}
+
+impl Default for Settings {
+    fn default() -> Self {
+        Self {
+            gravity: 2000.0,
+            friction: 4.0,
+            restitution: 0.4,
+        }
+    }
+}
+
+/// 物理演算で動かす矩形 (ウィンドウ) です。
+///
+/// ```rust
+/// use bkrbk::hit::Rect;
+/// use bkrbk::physics::{Body, Settings};
+/// use bkrbk::timer::MockClock;
+/// use std::time::Duration;
+///
+/// let area = Rect { x: 0, y: 0, width: 800, height: 600 };
+/// let clock = MockClock::new();
+/// let mut body = Body::with_clock(Settings::default(), area, (100, 100), clock.clone());
+/// body.set_position(300, 0);
+///
+/// // 作業領域の下端まで落ちて止まる
+/// for _ in 0..200 {
+///     clock.advance(Duration::from_millis(16));
+///     body.update();
+/// }
+/// assert!(body.is_resting());
+/// assert_eq!(body.position(), (300, 500));
+///
+/// // 右へ投げると右端で跳ね返り、範囲の外には出ない
+/// body.throw(3000.0, -500.0);
+/// let mut bounced = false;
+/// for _ in 0..100 {
+///     clock.advance(Duration::from_millis(16));
+///     body.update();
+///     bounced |= body.velocity().0 < 0.0;
+///     assert!(body.position().0 <= 700);
+/// }
+/// assert!(bounced);
+/// assert!(body.is_resting());
+/// ```
+#[derive(Debug, Clone)]
+pub struct Body<C: Clock = SystemClock> {
+    /// 物理演算の設定
+    pub settings: Settings,
+    /// 動ける範囲 (作業領域)
+    area: Rect,
+    /// 矩形の大きさ
+    size: (u32, u32),
+    /// 位置 (左上の座標)
+    position: (f64, f64),
+    /// 速度 (ピクセル/秒)
+    velocity: (f64, f64),
+    /// 止まっているかどうか
+    resting: bool,
+    /// 掴まれているかどうか (掴まれている間は動かしません)
+    held: bool,
+    /// 最後に計算した時刻
+    last: Instant,
+    /// まだ計算していない経過時間
+    pending: Duration,
+    clock: C,
+}
+
+impl Body {
+    /// 指定した範囲を動く Body 構造体を初期化して返します。
+    pub fn new(settings: Settings, area: Rect, size: (u32, u32)) -> Self {
+        Self::with_clock(settings, area, size, SystemClock)
+    }
+}
+
+impl<C: Clock> Body<C> {
+    /// 指定した時計を利用して、指定した範囲を動く Body 構造体を初期化して返します。
+    ///
+    /// 最初は範囲の左上にあり、落下を始めます。
+    pub fn with_clock(settings: Settings, area: Rect, size: (u32, u32), clock: C) -> Self {
+        Self {
+            settings,
+            area,
+            size,
+            position: (area.x as f64, area.y as f64),
+            velocity: (0.0, 0.0),
+            resting: false,
+            held: false,
+            last: clock.now(),
+            pending: Duration::ZERO,
+            clock,
+        }
+    }
+
+    /// 位置 (左上の座標) を取得します。
+    pub fn position(&self) -> (i32, i32) {
+        (
+            self.position.0.round() as i32,
+            self.position.1.round() as i32,
+        )
+    }
+
+    /// 速度 (ピクセル/秒) を取得します。
+    pub fn velocity(&self) -> (f64, f64) {
+        self.velocity
+    }
+
+    /// 止まっているかどうかを判定します。 (掴まれている間は false)
+    pub fn is_resting(&self) -> bool {
+        self.resting && !self.held
+    }
+
+    /// 掴まれているかどうかを判定します。
+    pub fn is_held(&self) -> bool {
+        self.held
+    }
+
+    /// 動ける範囲を変更します。
+    pub fn set_area(&mut self, area: Rect) {
+        self.area = area;
+        self.resting = false;
+    }
+
+    /// 位置 (左上の座標) を変更します。 (速度は変えません)
+    pub fn set_position(&mut self, x: i32, y: i32) {
+        self.position = (x as f64, y as f64);
+        self.resting = false;
+    }
+
+    /// 掴まれた状態にします。 (速度を失い、離されるまで動きません)
+    pub fn hold(&mut self) {
+        self.held = true;
+        self.velocity = (0.0, 0.0);
+    }
+
+    /// 指定した速度 (ピクセル/秒) で投げます。 (掴まれた状態も解除します)
+    pub fn throw(&mut self, vx: f64, vy: f64) {
+        self.held = false;
+        self.resting = false;
+        self.velocity = (vx, vy);
+        self.last = self.clock.now();
+        self.pending = Duration::ZERO;
+    }
+
+    /// 前回から経過した時間だけ計算を進めます。
+    ///
+    /// 位置が変わった場合は true を返します。
+    pub fn update(&mut self) -> bool {
+        let now = self.clock.now();
+        let elapsed = now.saturating_duration_since(self.last);
+        self.last = now;
+        if self.held || self.resting {
+            return false;
+        }
+        let before = self.position();
+        self.pending += elapsed;
+        while self.pending >= STEP && !self.resting {
+            self.pending -= STEP;
+            self.step(STEP.as_secs_f64());
+        }
+        self.position() != before
+    }
+
+    /// 計算を `dt` 秒だけ進めます。
+    fn step(&mut self, dt: f64) {
+        let Settings {
+            gravity,
+            friction,
+            restitution,
+        } = self.settings;
+        // 跳ね返るたびに速くならないようにする
+        let restitution = restitution.clamp(0.0, 1.0);
+        let left = self.area.x as f64;
+        let top = self.area.y as f64;
+        let right = left + self.area.width as f64 - self.size.0 as f64;
+        let bottom = top + self.area.height as f64 - self.size.1 as f64;
+        let (mut x, mut y) = self.position;
+        let (mut vx, mut vy) = self.velocity;
+
+        vy += gravity * dt;
+        x += vx * dt;
+        y += vy * dt;
+
+        if x < left || x > right {
+            x = x.clamp(left, right.max(left));
+            vx = -vx * restitution;
+        }
+        if y < top {
+            y = top;
+            vy = -vy * restitution;
+        }
+        let grounded = y >= bottom;
+        if grounded {
+            y = bottom.max(top);
+            vy = -vy.abs() * restitution;
+            if vy.abs() < REST_SPEED {
+                vy = 0.0;
+            }
+            vx -= vx * (friction * dt).min(1.0);
+        }
+        self.position = (x, y);
+        self.velocity = (vx, vy);
+        self.resting = grounded && vy == 0.0 && vx.abs() < REST_SPEED;
+        if self.resting {
+            self.velocity = (0.0, 0.0);
+        }
+    }
+}
+
+/// ポインターの動きから、離した時の速さを求めるための構造体です。
+///
+/// ```rust
+/// use bkrbk::physics::Tracker;
+/// use bkrbk::timer::MockClock;
+/// use std::time::Duration;
+///
+/// let clock = MockClock::new();
+/// let mut tracker = Tracker::with_clock(clock.clone());
+/// for i in 0..5 {
+///     tracker.push(i * 10, 0);
+///     clock.advance(Duration::from_millis(10));
+/// }
+/// // 10ms ごとに 10px 動いた = 1000px/秒
+/// let (vx, vy) = tracker.velocity();
+/// assert!((vx - 1000.0).abs() < 1.0);
+/// assert_eq!(vy, 0.0);
+/// ```
+#[derive(Debug, Clone)]
+pub struct Tracker<C: Clock = SystemClock> {
+    /// 直前の位置と時刻
+    samples: Vec<((i32, i32), Instant)>,
+    clock: C,
+}
+
+impl Default for Tracker {
+    fn default() -> Self {
+        Self::with_clock(SystemClock)
+    }
+}
+
+impl<C: Clock> Tracker<C> {
+    /// 指定した時計を利用する Tracker 構造体を初期化して返します。
+    pub fn with_clock(clock: C) -> Self {
+        Self {
+            samples: Vec::new(),
+            clock,
+        }
+    }
+
+    /// 記録した位置を全て消去します。
+    pub fn clear(&mut self) {
+        self.samples.clear();
+    }
+
+    /// 現在の位置を記録します。
+    pub fn push(&mut self, x: i32, y: i32) {
+        let now = self.clock.now();
+        self.samples.push(((x, y), now));
+        self.samples
+            .retain(|(_, at)| now.saturating_duration_since(*at) <= TRACK_WINDOW);
+    }
+
+    /// 直前の速度 (ピクセル/秒) を取得します。 (記録が足りない場合は 0)
+    pub fn velocity(&self) -> (f64, f64) {
+        let now = self.clock.now();
+        let recent: Vec<_> = self
+            .samples
+            .iter()
+            .filter(|(_, at)| now.saturating_duration_since(*at) <= TRACK_WINDOW)
+            .collect();
+        let (Some(first), Some(last)) = (recent.first(), recent.last()) else {
+            return (0.0, 0.0);
+        };
+        let seconds = last.1.saturating_duration_since(first.1).as_secs_f64();
+        if seconds <= 0.0 {
+            return (0.0, 0.0);
+        }
+        (
+            (last.0 .0 - first.0 .0) as f64 / seconds,
+            (last.0 .1 - first.0 .1) as f64 / seconds,
+        )
+    }
+}
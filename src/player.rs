@@ -0,0 +1,738 @@
+//! アニメーションの再生を操作するための機能です。
+//!
+//! 一時停止や再生速度、逆再生などを扱います。フレームの番号を決めるだけなので、
+//! 合成済みのフレーム ( `render::frames` ) と組み合わせることで任意の描画先で利用できます。
+//! 特定のフレームを表示した際のイベントは `Cues` で扱います。
+
+use std::{
+    ops::Range,
+    time::{Duration, Instant},
+};
+
+use crate::timer::{Clock, LatePolicy, SystemClock, DEFAULT_DELAY};
+
+/// 再生速度の最小値です。
+pub const MIN_SPEED: f64 = 0.01;
+/// 再生速度の最大値です。
+pub const MAX_SPEED: f64 = 100.0;
+
+/// 再生の方法です。
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Mode {
+    /// 繰り返し再生します。
+    #[default]
+    Loop,
+    /// 1回だけ再生し、最後のフレームで止まります。
+    Once,
+    /// 端まで再生したら向きを変えて、往復を繰り返します。 (両端のフレームは続けて2回分表示されます)
+    PingPong,
+}
+
+impl std::str::FromStr for Mode {
+    type Err = String;
+
+    /// `loop`, `once`, `ping-pong` のいずれかを受け付けます。
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "loop" => Ok(Self::Loop),
+            "once" => Ok(Self::Once),
+            "ping-pong" => Ok(Self::PingPong),
+            _ => Err(format!("unknown playback mode: {}", s)),
+        }
+    }
+}
+
+/// 再生するフレームの範囲が空か、アニメーションのフレーム数を超えていることを表すエラーです。
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RangeError {
+    /// 指定した範囲
+    pub range: Range<usize>,
+    /// アニメーションのフレーム数
+    pub frame_count: usize,
+}
+
+impl std::fmt::Display for RangeError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self.range.is_empty() {
+            true => write!(f, "frame range is empty"),
+            false => write!(
+                f,
+                "frame range {}-{} is out of the animation ({} frames)",
+                self.range.start,
+                self.range.end - 1,
+                self.frame_count
+            ),
+        }
+    }
+}
+
+impl std::error::Error for RangeError {}
+
+/// 再生位置を進めた間に起きたことを記録するための構造体です。
+#[derive(Debug, Clone, Default)]
+struct Progress {
+    /// 表示が切り替わったフレーム (切り替わった順)
+    frames: Vec<usize>,
+    /// 表示が切り替わった回数 (1周以上進んだ場合は `frames` より多くなります)
+    changes: usize,
+    /// 1回分の再生を終えた回数
+    completed: usize,
+}
+
+/// アニメーションの再生位置を管理するための構造体です。
+///
+/// 時刻は `Clock` から取得し、前回の更新からの経過時間に再生速度を掛けた分だけ再生位置を進めます。
+/// 処理が遅れた場合は、 `LatePolicy` に従ってフレームを飛ばすか、1フレームずつ追いつきます。
+///
+/// ```rust
+/// use bkrbk::player::{Mode, Player};
+/// use bkrbk::timer::{Clock, LatePolicy, MockClock};
+/// use std::time::Duration;
+///
+/// let clock = MockClock::new();
+/// let delays = [Duration::from_millis(100); 4];
+/// let mut player = Player::with_clock(&delays, clock.clone());
+/// assert_eq!(player.update(), 0);
+///
+/// // 2倍速
+/// player.set_speed(2.0);
+/// clock.advance(Duration::from_millis(100));
+/// assert_eq!(player.update(), 2);
+/// assert_eq!(player.skipped(), 1);
+///
+/// // 範囲外の再生速度は無視する
+/// player.set_speed(1e300);
+/// assert_eq!(player.speed(), 2.0);
+///
+/// // 一時停止中は進まない
+/// player.pause();
+/// clock.advance(Duration::from_secs(1));
+/// assert_eq!(player.update(), 2);
+///
+/// // 逆再生
+/// player.play();
+/// player.set_speed(1.0);
+/// player.set_reverse(true);
+/// clock.advance(Duration::from_millis(50));
+/// assert_eq!(player.update(), 1);
+///
+/// // 1-2番目のフレームだけを往復する
+/// player.set_reverse(false);
+/// player.set_mode(Mode::PingPong);
+/// player.set_range(1..3).unwrap();
+/// let frames: Vec<usize> = (0..6)
+///     .map(|_| {
+///         clock.advance(Duration::from_millis(100));
+///         player.update()
+///     })
+///     .collect();
+/// assert_eq!(frames, vec![2, 2, 1, 1, 2, 2]);
+///
+/// // 空の範囲やフレーム数を超える範囲は設定できない
+/// assert!(player.set_range(10..13).is_err());
+/// assert!(player.set_range(2..2).is_err());
+/// assert_eq!(player.range(), 1..3);
+///
+/// // 遅れた場合に1フレームずつ追いつく
+/// let mut player = Player::with_clock(&delays, clock.clone());
+/// player.set_late_policy(LatePolicy::CatchUp);
+/// player.update();
+/// clock.advance(Duration::from_millis(250));
+/// assert_eq!(player.update(), 1);
+/// assert_eq!(player.skipped(), 0);
+/// assert_eq!(player.next_change(), Some(clock.now()));
+/// assert_eq!(player.update(), 2);
+/// assert_eq!(player.next_change(), Some(clock.now() + Duration::from_millis(50)));
+/// ```
+pub struct Player<C: Clock = SystemClock> {
+    /// 各フレームの開始位置
+    starts: Vec<Duration>,
+    /// 全てのフレームの終了位置
+    end: Duration,
+    /// 再生するフレームの範囲
+    range: Range<usize>,
+    /// 再生する向きに沿って測った、範囲の先頭からの再生位置
+    ///
+    /// `Mode::PingPong` の場合は往復分 (範囲の長さの2倍) の位置を表します。
+    phase: Duration,
+    /// 再生速度
+    speed: f64,
+    /// 逆再生するかどうか
+    reverse: bool,
+    /// 再生の方法
+    mode: Mode,
+    /// 一時停止中かどうか
+    paused: bool,
+    /// 遅れた場合の扱い
+    late: LatePolicy,
+    /// 遅れたために、まだ再生位置に反映していない経過時間 (再生速度を掛けた後の時間)
+    backlog: Duration,
+    /// `Mode::Once` で最後まで再生したかどうか
+    finished: bool,
+    /// 前回の更新からの進み具合 (次の更新で `progress` に移します)
+    pending: Progress,
+    /// 前回の更新での進み具合
+    progress: Progress,
+    /// 最後に再生位置を更新した時刻
+    last: Instant,
+    /// 時刻の取得に利用する時計
+    clock: C,
+}
+
+impl Player {
+    /// 各フレームの表示時間から構造体を初期化して返します。
+    pub fn new(delays: &[Duration]) -> Self {
+        Self::with_clock(delays, SystemClock)
+    }
+}
+
+impl<C: Clock> Player<C> {
+    /// 指定した時計を利用する Player 構造体を初期化して返します。
+    ///
+    /// 表示時間が0のフレームは `timer::DEFAULT_DELAY` の間表示します。
+    pub fn with_clock(delays: &[Duration], clock: C) -> Self {
+        let mut starts = Vec::with_capacity(delays.len());
+        let mut end = Duration::ZERO;
+        for delay in delays {
+            starts.push(end);
+            end += match delay.is_zero() {
+                true => DEFAULT_DELAY,
+                false => *delay,
+            };
+        }
+        Self {
+            range: 0..starts.len(),
+            starts,
+            end,
+            phase: Duration::ZERO,
+            speed: 1.0,
+            reverse: false,
+            mode: Mode::default(),
+            paused: false,
+            late: LatePolicy::Skip,
+            backlog: Duration::ZERO,
+            finished: false,
+            // 最初のフレームは表示が切り替わったものとして扱う
+            pending: Progress {
+                frames: delays.first().map(|_| 0).into_iter().collect(),
+                ..Default::default()
+            },
+            progress: Progress::default(),
+            last: clock.now(),
+            clock,
+        }
+    }
+
+    /// フレームの数を取得します。
+    pub fn frame_count(&self) -> usize {
+        self.starts.len()
+    }
+
+    /// フレームの開始位置を取得します。
+    fn start_of(&self, index: usize) -> Duration {
+        self.starts.get(index).copied().unwrap_or(self.end)
+    }
+
+    /// 再生範囲の (開始位置, 終了位置) を取得します。
+    fn bounds(&self) -> (Duration, Duration) {
+        (
+            self.start_of(self.range.start),
+            self.start_of(self.range.end),
+        )
+    }
+
+    /// 再生範囲の長さを取得します。
+    fn length(&self) -> Duration {
+        let (start, end) = self.bounds();
+        end - start
+    }
+
+    /// 現在の再生位置が、逆向き (後ろから前) に進んでいるかどうかを判定します。
+    fn backward(&self) -> bool {
+        let returning = self.mode == Mode::PingPong && self.phase >= self.length();
+        self.reverse != returning
+    }
+
+    /// アニメーションの先頭からの再生位置を取得します。
+    pub fn position(&self) -> Duration {
+        let (start, end) = self.bounds();
+        let length = end - start;
+        let phase = match self.phase >= length {
+            true => (length * 2).saturating_sub(self.phase),
+            false => self.phase,
+        };
+        match self.reverse {
+            true => end - phase.min(length),
+            false => start + phase.min(length),
+        }
+    }
+
+    /// 現在表示するフレームの番号を取得します。
+    pub fn frame(&self) -> usize {
+        if self.range.is_empty() {
+            return self.range.start.min(self.frame_count().saturating_sub(1));
+        }
+        let position = self.position();
+        // 進む向きに合わせて、フレームの境界をどちらのフレームに含めるかを変える
+        let count = match self.backward() {
+            true => self.starts.partition_point(|start| *start < position),
+            false => self.starts.partition_point(|start| *start <= position),
+        };
+        count
+            .saturating_sub(1)
+            .clamp(self.range.start, self.range.end - 1)
+    }
+
+    /// 経過時間分だけ再生位置を進めます。
+    fn advance(&mut self) {
+        let now = self.clock.now();
+        let elapsed = now.saturating_duration_since(self.last);
+        self.last = now;
+        let length = self.length();
+        if self.paused || self.finished || length.is_zero() {
+            return;
+        }
+
+        let mut step = elapsed.mul_f64(self.speed) + std::mem::take(&mut self.backlog);
+        if self.late == LatePolicy::CatchUp {
+            // 次のフレームより先へは進めず、残りは次の更新に回す
+            let remaining = self.remaining();
+            if !remaining.is_zero() && step > remaining {
+                self.backlog = step - remaining;
+                step = remaining;
+            }
+        }
+        let phase = self.phase + step;
+        self.cross(phase);
+        self.phase = match self.mode {
+            Mode::Loop => Self::wrap(phase, length),
+            Mode::PingPong => Self::wrap(phase, length * 2),
+            Mode::Once if phase >= length => {
+                self.finished = true;
+                self.pending.completed += 1;
+                length
+            }
+            Mode::Once => phase,
+        };
+    }
+
+    /// 現在の再生位置から `phase` までの間に表示が切り替わるフレームを、切り替わる順に記録します。
+    ///
+    /// 1周以上進んだ場合、フレームは最後の1周分だけを記録します。
+    fn cross(&mut self, phase: Duration) {
+        let (start, end) = self.bounds();
+        let length = end - start;
+        // フレームが切り替わる再生位置と、切り替わった後のフレームの一覧
+        let mut boundaries: Vec<(Duration, usize)> = (self.range.start + 1..self.range.end)
+            .map(|i| match self.reverse {
+                true => (end - self.starts[i], i - 1),
+                false => (self.starts[i] - start, i),
+            })
+            .collect();
+        let period = match self.mode {
+            Mode::PingPong => {
+                // 折り返した後は、同じ位置を逆向きに通る
+                let returning: Vec<_> = boundaries
+                    .iter()
+                    .map(|(b, i)| match self.reverse {
+                        true => (length * 2 - *b, i + 1),
+                        false => (length * 2 - *b, i - 1),
+                    })
+                    .collect();
+                boundaries.extend(returning);
+                length * 2
+            }
+            Mode::Loop => {
+                // 範囲の端は次の周期の先頭と同じ位置
+                boundaries.push(match self.reverse {
+                    true => (length, self.range.end - 1),
+                    false => (length, self.range.start),
+                });
+                length
+            }
+            Mode::Once => length,
+        };
+
+        let (from, to) = (self.phase.as_nanos() as i128, phase.as_nanos() as i128);
+        let period = period.as_nanos() as i128;
+        let recent = from.max(to - period);
+        let mut crossed = Vec::new();
+        for (b, index) in boundaries {
+            let b = b.as_nanos() as i128;
+            // from < b + k * period <= to を満たす k の範囲
+            let (first, last) = match self.mode {
+                Mode::Once => (0, (from < b && b <= to) as i128 - 1),
+                _ => (
+                    (from - b).div_euclid(period) + 1,
+                    (to - b).div_euclid(period),
+                ),
+            };
+            self.pending.changes += (last - first + 1).max(0) as usize;
+            let first = first.max((recent - b).div_euclid(period) + 1);
+            crossed.extend((first..=last).map(|k| (b + k * period, index)));
+        }
+        crossed.sort_by_key(|(at, _)| *at);
+        self.pending
+            .frames
+            .extend(crossed.into_iter().map(|(_, index)| index));
+        if self.mode != Mode::Once {
+            self.pending.completed += (to.div_euclid(period) - from.div_euclid(period)) as usize;
+        }
+    }
+
+    /// 位置を `length` で割った余りを取得します。
+    fn wrap(phase: Duration, length: Duration) -> Duration {
+        Duration::from_nanos((phase.as_nanos() % length.as_nanos()) as u64)
+    }
+
+    /// 表示しているフレームが切り替わるまでの再生位置の長さを取得します。
+    fn remaining(&self) -> Duration {
+        let index = self.frame();
+        let position = self.position();
+        match self.backward() {
+            true => position.saturating_sub(self.start_of(index)),
+            false => self.start_of(index + 1).saturating_sub(position),
+        }
+    }
+
+    /// アニメーションの先頭からの位置を再生位置に設定します。
+    ///
+    /// 遅れて反映していない経過時間は捨てます。
+    fn set_position(&mut self, position: Duration) {
+        self.backlog = Duration::ZERO;
+        let (start, end) = self.bounds();
+        let position = position.clamp(start, end);
+        self.phase = match self.reverse {
+            true => end - position,
+            false => position - start,
+        };
+        self.finished = self.mode == Mode::Once && self.phase >= end - start;
+    }
+
+    /// 再生位置を移動し、表示するフレームが変わった場合は切り替わったものとして記録します。
+    fn jump(&mut self, f: impl FnOnce(&mut Self)) {
+        let before = self.frame();
+        f(self);
+        let after = self.frame();
+        if before != after {
+            self.pending.frames.push(after);
+        }
+    }
+
+    /// 再生位置を更新し、表示するフレームの番号を取得します。
+    pub fn update(&mut self) -> usize {
+        self.advance();
+        self.progress = std::mem::take(&mut self.pending);
+        self.frame()
+    }
+
+    /// 前回の更新で飛ばしたフレームの数を取得します。
+    ///
+    /// 処理が遅れて、1回の更新の間に2つ以上先のフレームまで進んだ場合に1以上になります。
+    pub fn skipped(&self) -> usize {
+        self.progress.changes.saturating_sub(1)
+    }
+
+    /// 前回の更新までに表示が切り替わったフレームの番号を、切り替わった順に取得します。
+    ///
+    /// 飛ばしたフレームも含みます。最初の更新では最初に表示するフレームを含みます。
+    /// 処理が遅れて1周以上進んだ場合は、最後の1周分だけを返します。
+    pub fn passed(&self) -> &[usize] {
+        &self.progress.frames
+    }
+
+    /// 前回の更新までに1回分の再生を終えた回数を取得します。
+    ///
+    /// `Mode::Loop` では先頭に戻った回数、 `Mode::PingPong` では往復を終えた回数、
+    /// `Mode::Once` では最後まで再生した場合に1を返します。
+    pub fn completed(&self) -> usize {
+        self.progress.completed
+    }
+
+    /// 1回分の再生にかかる時間を取得します。 (再生速度は考慮しません)
+    ///
+    /// `Mode::PingPong` の場合は往復にかかる時間を返します。
+    pub fn duration(&self) -> Duration {
+        match self.mode {
+            Mode::PingPong => self.length() * 2,
+            Mode::Loop | Mode::Once => self.length(),
+        }
+    }
+
+    /// 表示するフレームが次に切り替わる時刻を取得します。
+    ///
+    /// 一時停止中や再生を終えた場合は None を返します。
+    /// `LatePolicy::CatchUp` で遅れを取り戻している間は、前回の更新の時刻を返します。
+    pub fn next_change(&self) -> Option<Instant> {
+        if self.paused || self.finished || self.range.is_empty() {
+            return None;
+        }
+        let remaining = self.remaining().saturating_sub(self.backlog);
+        Some(self.last + remaining.div_f64(self.speed))
+    }
+
+    /// 再生します。
+    pub fn play(&mut self) {
+        self.advance();
+        self.paused = false;
+    }
+
+    /// 一時停止します。
+    pub fn pause(&mut self) {
+        self.advance();
+        self.paused = true;
+    }
+
+    /// 再生と一時停止を切り替えます。
+    pub fn toggle(&mut self) {
+        match self.paused {
+            true => self.play(),
+            false => self.pause(),
+        }
+    }
+
+    /// 一時停止中かどうかを判定します。
+    pub fn is_paused(&self) -> bool {
+        self.paused
+    }
+
+    /// `Mode::Once` で最後まで再生したかどうかを判定します。
+    pub fn is_finished(&self) -> bool {
+        self.finished
+    }
+
+    /// 指定したフレームの先頭 (逆再生の場合は末尾) に移動します。
+    ///
+    /// 再生範囲の外を指定した場合は、範囲内に収めます。
+    pub fn seek_frame(&mut self, index: usize) {
+        self.advance();
+        let index = index.clamp(self.range.start, self.range.end.max(1) - 1);
+        let position = match self.reverse {
+            true => self.start_of(index + 1),
+            false => self.start_of(index),
+        };
+        self.jump(|player| player.set_position(position));
+    }
+
+    /// アニメーションの先頭からの時間で指定した位置に移動します。
+    ///
+    /// 再生範囲の外を指定した場合は、範囲内に収めます。
+    pub fn seek(&mut self, position: Duration) {
+        self.advance();
+        self.jump(|player| player.set_position(position));
+    }
+
+    /// 再生速度を取得します。
+    pub fn speed(&self) -> f64 {
+        self.speed
+    }
+
+    /// 再生速度を設定します。 ( `1.0` が等速で、 `MIN_SPEED` から `MAX_SPEED` の範囲外の値は無視します)
+    pub fn set_speed(&mut self, speed: f64) {
+        if (MIN_SPEED..=MAX_SPEED).contains(&speed) {
+            self.advance();
+            self.speed = speed;
+        }
+    }
+
+    /// 遅れた場合の扱いを取得します。
+    pub fn late_policy(&self) -> LatePolicy {
+        self.late
+    }
+
+    /// 遅れた場合の扱いを設定します。 (デフォルトは `LatePolicy::Skip` です)
+    pub fn set_late_policy(&mut self, late: LatePolicy) {
+        self.advance();
+        self.late = late;
+        self.backlog = Duration::ZERO;
+    }
+
+    /// 逆再生するかどうかを取得します。
+    pub fn is_reverse(&self) -> bool {
+        self.reverse
+    }
+
+    /// 逆再生するかどうかを設定します。現在の再生位置から向きだけを変えます。
+    pub fn set_reverse(&mut self, reverse: bool) {
+        self.advance();
+        if self.reverse != reverse {
+            let position = self.position();
+            self.reverse = reverse;
+            self.set_position(position);
+        }
+    }
+
+    /// 再生の方法を取得します。
+    pub fn mode(&self) -> Mode {
+        self.mode
+    }
+
+    /// 再生の方法を設定します。現在の再生位置から続けて再生します。
+    pub fn set_mode(&mut self, mode: Mode) {
+        self.advance();
+        let position = self.position();
+        self.mode = mode;
+        self.set_position(position);
+    }
+
+    /// 再生するフレームの範囲を設定します。
+    ///
+    /// 現在の再生位置が範囲外の場合は、範囲の先頭から再生します。
+    /// 範囲が空の場合や、アニメーションのフレーム数を超える場合はエラーを返します。 (範囲は変更しません)
+    pub fn set_range(&mut self, range: Range<usize>) -> Result<(), RangeError> {
+        if range.is_empty() || range.end > self.frame_count() {
+            return Err(RangeError {
+                range,
+                frame_count: self.frame_count(),
+            });
+        }
+        self.advance();
+        let position = self.position();
+        self.range = range;
+        let (start, end) = self.bounds();
+        match (start..end).contains(&position) {
+            true => self.set_position(position),
+            false => self.seek_frame(match self.reverse {
+                true => self.range.end.saturating_sub(1),
+                false => self.range.start,
+            }),
+        }
+        Ok(())
+    }
+
+    /// 再生するフレームの範囲を全体に戻します。
+    pub fn clear_range(&mut self) {
+        // フレームが無い場合は、範囲も空のまま
+        let _ = self.set_range(0..self.frame_count());
+    }
+
+    /// 再生するフレームの範囲を取得します。
+    pub fn range(&self) -> Range<usize> {
+        self.range.clone()
+    }
+
+    /// 最初から再生し直します。 (再生速度などの設定は維持します)
+    pub fn restart(&mut self) {
+        self.advance();
+        self.jump(|player| {
+            player.phase = Duration::ZERO;
+            player.finished = false;
+        });
+    }
+}
+
+/// イベントを発生させるタイミングです。
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Cue {
+    /// 指定した番号のフレームに切り替わった時
+    Frame(usize),
+    /// アニメーションの最後のフレームに切り替わった時
+    Last,
+    /// 1回分の再生を終えた時 ( `Player::completed` を参照)
+    Complete,
+}
+
+impl std::str::FromStr for Cue {
+    type Err = String;
+
+    /// フレームの番号、 `last` 、 `complete` のいずれかを受け付けます。
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "last" => Ok(Self::Last),
+            "complete" => Ok(Self::Complete),
+            _ => s
+                .parse()
+                .map(Self::Frame)
+                .map_err(|_| format!("invalid cue: {}", s)),
+        }
+    }
+}
+
+/// フレームに結び付けたイベントの一覧です。
+///
+/// 再生位置を更新した後に `poll` を呼び出すと、その間に切り替わったフレームのイベントを順番に返します。
+/// 処理が遅れて飛ばしたフレームのイベントも返すので、効果音などを取りこぼしません。
+///
+/// ```rust
+/// use bkrbk::player::{Cue, Cues, Player};
+/// use bkrbk::timer::MockClock;
+/// use std::time::Duration;
+///
+/// let clock = MockClock::new();
+/// let mut player = Player::with_clock(&[Duration::from_millis(100); 4], clock.clone());
+/// let mut cues = Cues::new();
+/// cues.add(Cue::Frame(1), "step");
+/// cues.add(Cue::Last, "land");
+/// cues.add(Cue::Complete, "idle");
+///
+/// player.update();
+/// assert!(cues.poll(&player).is_empty());
+///
+/// // 1-3番目のフレームを飛ばしても、イベントは順番に発生する
+/// clock.advance(Duration::from_millis(350));
+/// player.update();
+/// assert_eq!(cues.poll(&player), vec![&"step", &"land"]);
+///
+/// clock.advance(Duration::from_millis(100));
+/// player.update();
+/// assert_eq!(cues.poll(&player), vec![&"idle"]);
+/// ```
+#[derive(Debug, Clone)]
+pub struct Cues<E> {
+    cues: Vec<(Cue, E)>,
+}
+
+impl<E> Default for Cues<E> {
+    fn default() -> Self {
+        Self { cues: Vec::new() }
+    }
+}
+
+impl<E> Cues<E> {
+    /// 空の一覧を作成します。
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// イベントを追加します。同じタイミングのイベントは追加した順に発生します。
+    pub fn add(&mut self, cue: Cue, event: E) {
+        self.cues.push((cue, event));
+    }
+
+    /// イベントの数を取得します。
+    pub fn len(&self) -> usize {
+        self.cues.len()
+    }
+
+    /// イベントが1つもないかどうかを判定します。
+    pub fn is_empty(&self) -> bool {
+        self.cues.is_empty()
+    }
+
+    /// 前回の更新で発生したイベントを、発生した順に取得します。
+    ///
+    /// 1回分の再生を終えたイベントは、フレームのイベントの後に返します。
+    pub fn poll<C: Clock>(&self, player: &Player<C>) -> Vec<&E> {
+        let last = player.frame_count().saturating_sub(1);
+        let mut events = Vec::new();
+        for &index in player.passed() {
+            events.extend(self.cues.iter().filter_map(|(cue, event)| {
+                let fired = match cue {
+                    Cue::Frame(frame) => *frame == index,
+                    Cue::Last => index == last,
+                    Cue::Complete => false,
+                };
+                fired.then_some(event)
+            }));
+        }
+        for _ in 0..player.completed() {
+            events.extend(
+                self.cues
+                    .iter()
+                    .filter(|(cue, _)| *cue == Cue::Complete)
+                    .map(|(_, event)| event),
+            );
+        }
+        events
+    }
+}
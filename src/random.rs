@@ -0,0 +1,77 @@
+//! 乱数を扱うための機能です。
+//!
+//! シード値を指定すると毎回同じ乱数列を生成するので、ランダムな振る舞いを再現できます。
+
+use std::time::Duration;
+
+/// SplitMix64 による疑似乱数生成器です。
+///
+/// ```rust
+/// use bkrbk::random::Rng;
+///
+/// let mut a = Rng::new(42);
+/// let mut b = Rng::new(42);
+/// assert_eq!(a.next_u64(), b.next_u64());
+///
+/// let n = a.range(10, 20);
+/// assert!((10..20).contains(&n));
+/// ```
+#[derive(Debug, Clone)]
+pub struct Rng {
+    state: u64,
+}
+
+impl Default for Rng {
+    fn default() -> Self {
+        Self::from_entropy()
+    }
+}
+
+impl Rng {
+    /// シード値から構造体を初期化して返します。
+    pub fn new(seed: u64) -> Self {
+        Self { state: seed }
+    }
+
+    /// 実行ごとに異なるシード値で構造体を初期化して返します。
+    pub fn from_entropy() -> Self {
+        use std::hash::{BuildHasher, Hasher};
+
+        let mut hasher = std::collections::hash_map::RandomState::new().build_hasher();
+        if let Ok(elapsed) = std::time::UNIX_EPOCH.elapsed() {
+            hasher.write_u128(elapsed.as_nanos());
+        }
+        Self::new(hasher.finish())
+    }
+
+    /// 64bit の乱数を取得します。
+    pub fn next_u64(&mut self) -> u64 {
+        self.state = self.state.wrapping_add(0x9E37_79B9_7F4A_7C15);
+        let mut z = self.state;
+        z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+        z ^ (z >> 31)
+    }
+
+    /// `0.0` 以上 `1.0` 未満の乱数を取得します。
+    pub fn next_f64(&mut self) -> f64 {
+        (self.next_u64() >> 11) as f64 / (1u64 << 53) as f64
+    }
+
+    /// `low` 以上 `high` 未満の乱数を取得します。 ( `low >= high` の場合は `low` を返します)
+    pub fn range(&mut self, low: u64, high: u64) -> u64 {
+        match high.checked_sub(low) {
+            Some(span) if span > 0 => low + ((self.next_u64() as u128 * span as u128) >> 64) as u64,
+            _ => low,
+        }
+    }
+
+    /// `min` 以上 `max` 以下の時間を一様に選びます。
+    pub fn duration(&mut self, min: Duration, max: Duration) -> Duration {
+        if max <= min {
+            return min;
+        }
+        let span = (max - min).as_nanos().min(u64::MAX as u128 - 1) as u64;
+        min + Duration::from_nanos(self.range(0, span + 1))
+    }
+}
@@ -0,0 +1,448 @@
+//! ポモドーロタイマーとリマインダーを扱うための機能です。
+//!
+//! 状態はテキストファイルに保存できるので、アプリケーションを再起動しても予定は失われません。
+//! 終了している間に時刻を過ぎた予定は、次に読み込んだ際に通知します。
+
+use std::{
+    path::{Path, PathBuf},
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+/// 状態ファイルの1行目に書き込む見出しです。
+const HEADER: &str = "# bkrbk reminders v1";
+
+/// 状態の読み書きで発生するエラーです。
+#[derive(Debug)]
+pub enum Error {
+    /// ファイルの読み書きに失敗しました。
+    Io(std::io::Error),
+    /// ファイルの形式が正しくありません。 (行番号, 内容)
+    Parse(usize, String),
+}
+
+impl std::fmt::Display for Error {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Io(e) => write!(f, "{}", e),
+            Self::Parse(line, s) => write!(f, "line {}: invalid entry: {}", line, s),
+        }
+    }
+}
+
+impl std::error::Error for Error {}
+
+impl From<std::io::Error> for Error {
+    fn from(value: std::io::Error) -> Self {
+        Self::Io(value)
+    }
+}
+
+/// キャラクターに表示させる通知です。
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Notification {
+    /// 表示するメッセージ
+    pub message: String,
+    /// 再生するアニメーションのファイルパス (None の場合は通常のアニメーション)
+    pub animation: Option<String>,
+}
+
+/// 指定した時刻に1回だけ通知する予定です。
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Reminder {
+    /// 削除する際に利用する番号
+    pub id: u64,
+    /// 通知する時刻
+    pub due: SystemTime,
+    /// 通知の内容
+    pub notification: Notification,
+}
+
+/// ポモドーロタイマーの段階です。
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Phase {
+    /// 作業中
+    Focus,
+    /// 短い休憩中
+    ShortBreak,
+    /// 長い休憩中
+    LongBreak,
+}
+
+impl Phase {
+    /// 保存する際の名前を取得します。
+    fn name(&self) -> &'static str {
+        match self {
+            Self::Focus => "focus",
+            Self::ShortBreak => "short_break",
+            Self::LongBreak => "long_break",
+        }
+    }
+
+    /// 保存した名前から段階を取得します。
+    fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "focus" => Some(Self::Focus),
+            "short_break" => Some(Self::ShortBreak),
+            "long_break" => Some(Self::LongBreak),
+            _ => None,
+        }
+    }
+}
+
+/// ポモドーロタイマーの設定です。
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PomodoroSettings {
+    /// 作業時間
+    pub focus: Duration,
+    /// 短い休憩の時間
+    pub short_break: Duration,
+    /// 長い休憩の時間
+    pub long_break: Duration,
+    /// 長い休憩を取るまでの作業の回数
+    pub long_break_every: u32,
+    /// 作業が終わった (休憩を始める) 際の通知
+    pub break_notification: Notification,
+    /// 休憩が終わった (作業を始める) 際の通知
+    pub focus_notification: Notification,
+}
+
+impl Default for PomodoroSettings {
+    fn default() -> Self {
+        Self {
+            focus: Duration::from_secs(25 * 60),
+            short_break: Duration::from_secs(5 * 60),
+            long_break: Duration::from_secs(15 * 60),
+            long_break_every: 4,
+            break_notification: Notification {
+                message: "Break time!".to_string(),
+                animation: None,
+            },
+            focus_notification: Notification {
+                message: "Back to work!".to_string(),
+                animation: None,
+            },
+        }
+    }
+}
+
+/// 実行中のポモドーロタイマーです。
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Pomodoro {
+    /// 設定
+    pub settings: PomodoroSettings,
+    /// 現在の段階
+    pub phase: Phase,
+    /// 現在の段階が終わる時刻
+    pub until: SystemTime,
+    /// 終えた作業の回数
+    pub completed: u32,
+}
+
+impl Pomodoro {
+    /// 現在の段階を終えて次の段階に進み、その際の通知を返します。
+    fn advance(&mut self) -> Notification {
+        let (phase, duration, notification) = match self.phase {
+            Phase::Focus => {
+                self.completed += 1;
+                match self
+                    .completed
+                    .is_multiple_of(self.settings.long_break_every.max(1))
+                {
+                    true => (
+                        Phase::LongBreak,
+                        self.settings.long_break,
+                        &self.settings.break_notification,
+                    ),
+                    false => (
+                        Phase::ShortBreak,
+                        self.settings.short_break,
+                        &self.settings.break_notification,
+                    ),
+                }
+            }
+            Phase::ShortBreak | Phase::LongBreak => (
+                Phase::Focus,
+                self.settings.focus,
+                &self.settings.focus_notification,
+            ),
+        };
+        let notification = notification.clone();
+        self.phase = phase;
+        self.until += duration.max(Duration::from_secs(1));
+        notification
+    }
+}
+
+/// ポモドーロタイマーとリマインダーの状態を管理するための構造体です。
+///
+/// 時刻は引数で受け取るので、任意の時刻で動作を確認できます。
+///
+/// ```rust
+/// use bkrbk::reminder::{PomodoroSettings, Reminders};
+/// use std::time::{Duration, UNIX_EPOCH};
+///
+/// let now = UNIX_EPOCH + Duration::from_secs(1_700_000_000);
+/// let mut reminders = Reminders::default();
+/// reminders.add(now + Duration::from_secs(45 * 60), "Stand up", None);
+/// reminders.start_pomodoro(now, PomodoroSettings::default());
+///
+/// // 25分後に作業が終わり、45分後にリマインダーが通知される
+/// let events = reminders.poll(now + Duration::from_secs(25 * 60));
+/// assert_eq!(events[0].message, "Break time!");
+/// let events = reminders.poll(now + Duration::from_secs(45 * 60));
+/// assert_eq!(events.len(), 2);
+/// assert_eq!(events[1].message, "Stand up");
+///
+/// // 保存した内容から復元できる
+/// let restored = Reminders::parse(&reminders.to_text()).unwrap();
+/// assert_eq!(restored.pomodoro(), reminders.pomodoro());
+/// ```
+#[derive(Debug, Default)]
+pub struct Reminders {
+    reminders: Vec<Reminder>,
+    pomodoro: Option<Pomodoro>,
+    next_id: u64,
+    /// 保存先
+    path: Option<PathBuf>,
+}
+
+impl Reminders {
+    /// ファイルから状態を読み込みます。ファイルが無い場合は空の状態を返します。
+    ///
+    /// 読み込んだファイルは `save` の保存先になります。
+    pub fn load(path: &Path) -> Result<Self, Error> {
+        let mut result = match std::fs::read_to_string(path) {
+            Ok(text) => Self::parse(&text)?,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Self::default(),
+            Err(e) => return Err(e.into()),
+        };
+        result.path = Some(path.to_path_buf());
+        Ok(result)
+    }
+
+    /// 読み込んだファイルに状態を保存します。 ( `load` で読み込んでいない場合は何もしません)
+    pub fn save(&self) -> Result<(), Error> {
+        let Some(path) = self.path.as_ref() else {
+            return Ok(());
+        };
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        // 書き込み途中で終了しても壊れないように、一時ファイルに書いてから置き換える
+        let temp = path.with_extension("tmp");
+        std::fs::write(&temp, self.to_text())?;
+        std::fs::rename(&temp, path)?;
+        Ok(())
+    }
+
+    /// 状態をテキストに変換します。
+    pub fn to_text(&self) -> String {
+        let mut result = format!("{}\n", HEADER);
+        for reminder in &self.reminders {
+            result += &format!(
+                "reminder\t{}\t{}\t{}\n",
+                to_millis(reminder.due),
+                escape(reminder.notification.animation.as_deref().unwrap_or("")),
+                escape(&reminder.notification.message)
+            );
+        }
+        if let Some(pomodoro) = &self.pomodoro {
+            let settings = &pomodoro.settings;
+            result += &format!(
+                "pomodoro\t{}\t{}\t{}\t{}\t{}\t{}\t{}\t{}\t{}\t{}\t{}\n",
+                pomodoro.phase.name(),
+                to_millis(pomodoro.until),
+                pomodoro.completed,
+                settings.focus.as_millis(),
+                settings.short_break.as_millis(),
+                settings.long_break.as_millis(),
+                settings.long_break_every,
+                escape(
+                    settings
+                        .break_notification
+                        .animation
+                        .as_deref()
+                        .unwrap_or("")
+                ),
+                escape(&settings.break_notification.message),
+                escape(
+                    settings
+                        .focus_notification
+                        .animation
+                        .as_deref()
+                        .unwrap_or("")
+                ),
+                escape(&settings.focus_notification.message)
+            );
+        }
+        result
+    }
+
+    /// `to_text` で変換したテキストから状態を復元します。
+    pub fn parse(text: &str) -> Result<Self, Error> {
+        let mut result = Self::default();
+        for (i, line) in text.lines().enumerate() {
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let error = || Error::Parse(i + 1, line.to_string());
+            let fields: Vec<&str> = line.split('\t').collect();
+            let millis = |s: &str| s.parse::<i64>().ok().map(from_millis).ok_or_else(error);
+            let duration = |s: &str| s.parse().ok().map(Duration::from_millis).ok_or_else(error);
+            let notification = |animation: &str, message: &str| Notification {
+                message: unescape(message),
+                animation: (!animation.is_empty()).then(|| unescape(animation)),
+            };
+            match fields[..] {
+                ["reminder", due, animation, message] => {
+                    result.reminders.push(Reminder {
+                        id: result.next_id,
+                        due: millis(due)?,
+                        notification: notification(animation, message),
+                    });
+                    result.next_id += 1;
+                }
+                ["pomodoro", phase, until, completed, focus, short_break, long_break, every, break_animation, break_message, focus_animation, focus_message] =>
+                {
+                    result.pomodoro = Some(Pomodoro {
+                        settings: PomodoroSettings {
+                            focus: duration(focus)?,
+                            short_break: duration(short_break)?,
+                            long_break: duration(long_break)?,
+                            long_break_every: every.parse().map_err(|_| error())?,
+                            break_notification: notification(break_animation, break_message),
+                            focus_notification: notification(focus_animation, focus_message),
+                        },
+                        phase: Phase::from_name(phase).ok_or_else(error)?,
+                        until: millis(until)?,
+                        completed: completed.parse().map_err(|_| error())?,
+                    });
+                }
+                _ => return Err(error()),
+            }
+        }
+        Ok(result)
+    }
+
+    /// リマインダーを追加し、削除する際に利用する番号を返します。
+    pub fn add(&mut self, due: SystemTime, message: &str, animation: Option<&str>) -> u64 {
+        let id = self.next_id;
+        self.next_id += 1;
+        self.reminders.push(Reminder {
+            id,
+            due,
+            notification: Notification {
+                message: message.to_string(),
+                animation: animation.map(str::to_string),
+            },
+        });
+        id
+    }
+
+    /// リマインダーを削除します。
+    pub fn remove(&mut self, id: u64) -> Option<Reminder> {
+        let index = self.reminders.iter().position(|r| r.id == id)?;
+        Some(self.reminders.remove(index))
+    }
+
+    /// 登録されているリマインダーを取得します。
+    pub fn reminders(&self) -> &[Reminder] {
+        &self.reminders
+    }
+
+    /// ポモドーロタイマーを作業の段階から開始します。 (実行中の場合はやり直します)
+    pub fn start_pomodoro(&mut self, now: SystemTime, settings: PomodoroSettings) {
+        self.pomodoro = Some(Pomodoro {
+            until: now + settings.focus,
+            settings,
+            phase: Phase::Focus,
+            completed: 0,
+        });
+    }
+
+    /// ポモドーロタイマーを停止します。
+    pub fn stop_pomodoro(&mut self) {
+        self.pomodoro = None;
+    }
+
+    /// 実行中のポモドーロタイマーを取得します。
+    pub fn pomodoro(&self) -> Option<&Pomodoro> {
+        self.pomodoro.as_ref()
+    }
+
+    /// 次に通知する時刻を取得します。
+    pub fn next_due(&self) -> Option<SystemTime> {
+        let reminder = self.reminders.iter().map(|r| r.due).min();
+        let pomodoro = self.pomodoro.as_ref().map(|p| p.until);
+        reminder.into_iter().chain(pomodoro).min()
+    }
+
+    /// 時刻を過ぎた通知を時刻順に取得します。
+    ///
+    /// 時刻を過ぎたリマインダーは削除します。ポモドーロタイマーが複数の段階を過ぎていた場合は、
+    /// 最後の段階の通知だけを返します。
+    pub fn poll(&mut self, now: SystemTime) -> Vec<Notification> {
+        let mut due = Vec::new();
+        self.reminders.retain(|reminder| match reminder.due <= now {
+            true => {
+                due.push((reminder.due, reminder.notification.clone()));
+                false
+            }
+            false => true,
+        });
+        if let Some(pomodoro) = self.pomodoro.as_mut() {
+            let mut last = None;
+            while pomodoro.until <= now {
+                let until = pomodoro.until;
+                last = Some((until, pomodoro.advance()));
+            }
+            due.extend(last);
+        }
+        due.sort_by_key(|(time, _)| *time);
+        due.into_iter()
+            .map(|(_, notification)| notification)
+            .collect()
+    }
+}
+
+/// 時刻を UNIX 時間 (ミリ秒) に変換します。
+fn to_millis(time: SystemTime) -> i64 {
+    match time.duration_since(UNIX_EPOCH) {
+        Ok(d) => d.as_millis() as i64,
+        Err(e) => -(e.duration().as_millis() as i64),
+    }
+}
+
+/// UNIX 時間 (ミリ秒) を時刻に変換します。
+fn from_millis(millis: i64) -> SystemTime {
+    match millis >= 0 {
+        true => UNIX_EPOCH + Duration::from_millis(millis as u64),
+        false => UNIX_EPOCH - Duration::from_millis(millis.unsigned_abs()),
+    }
+}
+
+/// タブと改行を含む文字列を1つの項目として保存できるように変換します。
+fn escape(s: &str) -> String {
+    s.replace('\\', "\\\\")
+        .replace('\t', "\\t")
+        .replace('\n', "\\n")
+}
+
+/// `escape` で変換した文字列を元に戻します。
+fn unescape(s: &str) -> String {
+    let mut result = String::with_capacity(s.len());
+    let mut chars = s.chars();
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            result.push(c);
+            continue;
+        }
+        match chars.next() {
+            Some('t') => result.push('\t'),
+            Some('n') => result.push('\n'),
+            Some(c) => result.push(c),
+            None => result.push('\\'),
+        }
+    }
+    result
+}
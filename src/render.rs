@@ -0,0 +1,188 @@
+//! アニメーションを画像ファイルとして書き出すための機能です。
+//!
+//! ウィンドウに表示する場合と同じ手順でフレームを合成するので、表示内容の確認やスナップショットテストに利用できます。
+
+use std::{path::Path, time::Duration};
+
+use crate::{font, img::Gif};
+
+/// コンタクトシートの背景色
+const SHEET_BG: u32 = 0x00_FF_FF_FF;
+/// 透過部分を表す市松模様の色
+const CHECKER: [u32; 2] = [0x00_CC_CC_CC, 0x00_F0_F0_F0];
+/// 市松模様の1マスの大きさ (画素)
+const CHECKER_SIZE: usize = 8;
+/// 注釈の文字色
+const LABEL_COLOR: u32 = 0x00_00_00_00;
+/// 注釈の文字の拡大率
+const LABEL_SCALE: usize = 2;
+/// フレーム同士の間隔 (画素)
+const PADDING: usize = 4;
+
+/// 書き出しで発生するエラーです。
+#[derive(Debug)]
+pub enum Error {
+    /// ファイルの作成に失敗しました。
+    Io(std::io::Error),
+    /// PNG への変換に失敗しました。
+    Png(png::EncodingError),
+}
+
+impl std::fmt::Display for Error {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Io(e) => write!(f, "{}", e),
+            Self::Png(e) => write!(f, "failed to encode PNG: {}", e),
+        }
+    }
+}
+
+impl std::error::Error for Error {}
+
+impl From<std::io::Error> for Error {
+    fn from(value: std::io::Error) -> Self {
+        Self::Io(value)
+    }
+}
+
+impl From<png::EncodingError> for Error {
+    fn from(value: png::EncodingError) -> Self {
+        Self::Png(value)
+    }
+}
+
+/// 合成済みのフレームです。
+pub struct Frame {
+    /// 画像 ( `0x00_RR_GG_BB` 形式)
+    pub image: Vec<u32>,
+    /// 元の GIF のフレームのパレット
+    pub palette: Vec<u32>,
+    /// 表示時間
+    pub delay: Duration,
+}
+
+/// 全てのフレームを順番に合成します。
+///
+/// 描画範囲を透過色で塗りつぶした状態から、各フレームを前のフレームに重ねて描画します。
+/// ```rust
+/// let gif = bkrbk::img::Gif::open("share/jump.gif").unwrap();
+/// let frames = bkrbk::render::frames(&gif, 0x00_00_00_FF);
+/// assert_eq!(frames.len(), gif.info.len());
+/// assert_eq!(frames[0].image.len(), 160 * 180);
+/// ```
+pub fn frames(gif: &Gif, bg: u32) -> Vec<Frame> {
+    let Some((width, height)) = gif.size() else {
+        return Vec::new();
+    };
+    let mut image = vec![bg; width as usize * height as usize];
+    (0..gif.info.len())
+        .map(|i| {
+            gif.draw(i, &mut image, width as usize);
+            Frame {
+                image: image.clone(),
+                palette: gif.palette(i),
+                delay: Duration::from_millis(gif.info[i].delay as u64 * 10),
+            }
+        })
+        .collect()
+}
+
+/// 画像を PNG ファイルとして保存します。
+///
+/// `bg` を指定した場合はその色の画素を透明にします。
+pub fn write_png(
+    path: &Path,
+    image: &[u32],
+    width: usize,
+    height: usize,
+    bg: Option<u32>,
+) -> Result<(), Error> {
+    let file = std::io::BufWriter::new(std::fs::File::create(path)?);
+    let mut encoder = png::Encoder::new(file, width as u32, height as u32);
+    encoder.set_color(png::ColorType::Rgba);
+    encoder.set_depth(png::BitDepth::Eight);
+    let mut writer = encoder.write_header()?;
+
+    let data: Vec<u8> = image
+        .iter()
+        .flat_map(|&pixel| match Some(pixel) == bg {
+            true => [0, 0, 0, 0],
+            false => {
+                let [_, r, g, b] = pixel.to_be_bytes();
+                [r, g, b, 0xFF]
+            }
+        })
+        .collect();
+    writer.write_image_data(&data)?;
+    writer.finish()?;
+    Ok(())
+}
+
+/// 全てのフレームを1枚に並べた画像です。
+pub struct ContactSheet {
+    pub image: Vec<u32>,
+    pub width: usize,
+    pub height: usize,
+}
+
+impl ContactSheet {
+    /// フレームを `columns` 列で並べ、各フレームの下にフレーム番号と表示時間を描画します。
+    ///
+    /// 透過色の部分は市松模様で表示します。
+    /// ```rust
+    /// let gif = bkrbk::img::Gif::open("share/jump.gif").unwrap();
+    /// let frames = bkrbk::render::frames(&gif, 0x00_00_00_FF);
+    /// let sheet = bkrbk::render::ContactSheet::new(&frames, 160, 180, 4, 0x00_00_00_FF);
+    /// let rows = frames.len().div_ceil(4);
+    /// assert_eq!(sheet.width, 4 * (160 + 4) + 4);
+    /// assert_eq!(sheet.image.len(), sheet.width * sheet.height);
+    /// assert!(sheet.height > rows * 180);
+    /// ```
+    pub fn new(frames: &[Frame], width: usize, height: usize, columns: usize, bg: u32) -> Self {
+        let columns = columns.clamp(1, frames.len().max(1));
+        let rows = frames.len().div_ceil(columns);
+        let label_height = font::GLYPH_HEIGHT * LABEL_SCALE + PADDING;
+        let cell_width = width + PADDING;
+        let cell_height = height + label_height + PADDING;
+
+        let sheet_width = columns * cell_width + PADDING;
+        let sheet_height = rows * cell_height + PADDING;
+        let mut image = vec![SHEET_BG; sheet_width * sheet_height];
+
+        for (i, frame) in frames.iter().enumerate() {
+            let left = PADDING + (i % columns) * cell_width;
+            let top = PADDING + (i / columns) * cell_height;
+            for y in 0..height {
+                for x in 0..width {
+                    let pixel = match frame.image[y * width + x] {
+                        pixel if pixel == bg => CHECKER[(x / CHECKER_SIZE + y / CHECKER_SIZE) % 2],
+                        pixel => pixel,
+                    };
+                    image[(top + y) * sheet_width + left + x] = pixel;
+                }
+            }
+
+            let label = format!("#{} {}ms", i, frame.delay.as_millis());
+            font::draw_text(
+                &mut image,
+                sheet_width,
+                left,
+                top + height + PADDING,
+                &label,
+                LABEL_COLOR,
+                LABEL_SCALE,
+            );
+        }
+
+        Self {
+            image,
+            width: sheet_width,
+            height: sheet_height,
+        }
+    }
+
+    /// PNG ファイルとして保存します。
+    pub fn write_png(&self, path: &Path) -> Result<(), Error> {
+        write_png(path, &self.image, self.width, self.height, None)
+    }
+}
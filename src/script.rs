@@ -0,0 +1,527 @@
+//! スクリプトでキャラクターの振る舞いを記述するための機能です。
+//!
+//! スクリプトには [Rhai](https://rhai.rs) を利用します。再ビルドせずに振る舞いを変えられます。
+//!
+//! ```text
+//! // トップレベルは読み込んだ時に一度だけ実行する
+//! load("jump.gif");
+//!
+//! fn on_start() {
+//!     this.clicks = 0;
+//!     set_interval("walk", 5000);
+//! }
+//!
+//! fn on_timer(name) {
+//!     if name == "walk" {
+//!         move_by(random(-40, 41), 0);
+//!     }
+//! }
+//!
+//! fn on_event(name) {
+//!     if name == "click" {
+//!         this.clicks += 1;
+//!         say(`clicked ${this.clicks} times`, 2000);
+//!         play("jump.gif");
+//!     }
+//! }
+//!
+//! fn on_finished(animation) {
+//!     if animation == "jump.gif" {
+//!         play();
+//!     }
+//! }
+//! ```
+//!
+//! スクリプトからは次の関数を呼び出せます。
+//!
+//! * `load(name)` : アニメーションを読み込みます。 (トップレベルでのみ呼び出せます、スクリプトからの相対パス)
+//! * `play(name)` / `play()` : アニメーションを切り替えます。 (引数が無い場合は通常のアニメーション)
+//! * `move_to(x, y)` / `move_by(dx, dy)` : ウィンドウを移動します。
+//! * `position()` : ウィンドウの位置を `[x, y]` で取得します。 (位置を扱えない描画先では `()`)
+//! * `say(text)` / `say(text, ms)` : メッセージを表示します。
+//! * `emit(name)` : 状態遷移にイベントを送ります。
+//! * `set_timer(name, ms)` / `set_interval(name, ms)` / `clear_timer(name)` : タイマーを操作します。
+//! * `random()` / `random(low, high)` : 乱数を取得します。 (`low` 以上 `high` 未満の整数)
+//!
+//! スクリプトに次の関数を定義すると、対応する出来事が起きた時に呼び出します。
+//!
+//! * `on_start()` : 表示を始めた時
+//! * `on_timer(name)` : タイマーの時刻になった時
+//! * `on_finished(animation)` : アニメーションを1回再生し終えた時
+//! * `on_state(name)` : 状態遷移で状態が移った時
+//! * `on_event(name)` : 入力などのイベントを受け取った時
+//!
+//! 関数の中では、呼び出しの間で保持する値を `this` (マップ) で扱えます。
+//!
+//! スクリプトはファイルやプロセス、他のスクリプトを扱えません。
+//! 1回の呼び出しで実行できる処理の量や、文字列・配列の大きさにも上限があります。
+//! 実行中のエラーは呼び出し元に返すだけなので、キャラクターの動作は止まりません。
+
+use std::{
+    cell::RefCell,
+    path::{Path, PathBuf},
+    rc::Rc,
+    time::{Duration, Instant},
+};
+
+use rhai::{
+    module_resolvers::DummyModuleResolver, CallFnOptions, Dynamic, Engine, EvalAltResult, Map,
+    Scope, AST, INT,
+};
+
+use crate::{
+    random::Rng,
+    timer::{Clock, SystemClock},
+};
+
+/// 1回の呼び出しで実行できる処理の量の上限です。
+const MAX_OPERATIONS: u64 = 1_000_000;
+/// 関数の呼び出しの深さの上限です。
+const MAX_CALL_LEVELS: usize = 32;
+/// 文字列の長さの上限です。
+const MAX_STRING_SIZE: usize = 64 * 1024;
+/// 配列とマップの要素数の上限です。
+const MAX_COLLECTION_SIZE: usize = 10_000;
+/// タイマーの数の上限です。
+const MAX_TIMERS: usize = 64;
+
+/// スクリプトの読み込みや実行で発生するエラーです。
+#[derive(Debug)]
+pub enum Error {
+    /// ファイルの読み込みに失敗しました。
+    Io(std::io::Error),
+    /// スクリプトの構文が正しくありません。
+    Parse(rhai::ParseError),
+    /// スクリプトの実行中にエラーが発生しました。
+    Runtime(String),
+}
+
+impl std::fmt::Display for Error {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Io(e) => write!(f, "{}", e),
+            Self::Parse(e) => write!(f, "{}", e),
+            Self::Runtime(message) => write!(f, "{}", message),
+        }
+    }
+}
+
+impl std::error::Error for Error {}
+
+impl From<std::io::Error> for Error {
+    fn from(value: std::io::Error) -> Self {
+        Self::Io(value)
+    }
+}
+
+impl From<rhai::ParseError> for Error {
+    fn from(value: rhai::ParseError) -> Self {
+        Self::Parse(value)
+    }
+}
+
+impl From<Box<EvalAltResult>> for Error {
+    fn from(value: Box<EvalAltResult>) -> Self {
+        Self::Runtime(value.to_string())
+    }
+}
+
+/// スクリプトから依頼された操作です。
+#[derive(Debug, Clone, PartialEq)]
+pub enum Command {
+    /// アニメーションを切り替えます。 (None の場合は通常のアニメーション)
+    Play(Option<String>),
+    /// ウィンドウを指定した位置へ移動します。
+    MoveTo(i32, i32),
+    /// ウィンドウを現在の位置から移動します。
+    MoveBy(i32, i32),
+    /// メッセージを表示します。 (None の場合は既定の時間だけ表示します)
+    Say(String, Option<Duration>),
+    /// 状態遷移にイベントを送ります。
+    Emit(String),
+}
+
+/// スクリプトで設定したタイマーです。
+#[derive(Debug, Clone)]
+struct Timer {
+    name: String,
+    delay: Duration,
+    /// 繰り返すかどうか
+    repeat: bool,
+    /// 次に発火する時刻 (None の場合は設定した直後で、まだ時刻を決めていない)
+    at: Option<Instant>,
+}
+
+/// スクリプトに公開する関数が共有する状態です。
+struct Host {
+    commands: Vec<Command>,
+    timers: Vec<Timer>,
+    /// ウィンドウの位置
+    position: Option<(i32, i32)>,
+    /// 読み込むアニメーション
+    loads: Vec<String>,
+    /// トップレベルの実行を終えたかどうか
+    started: bool,
+    rng: Rng,
+}
+
+/// スクリプト内で発生させるエラーを作成します。
+fn runtime_error(message: impl Into<String>) -> Box<EvalAltResult> {
+    message.into().into()
+}
+
+/// ミリ秒を時間に変換します。 (負の値はエラー)
+fn millis(ms: INT) -> Result<Duration, Box<EvalAltResult>> {
+    u64::try_from(ms)
+        .map(Duration::from_millis)
+        .map_err(|_| runtime_error(format!("invalid duration: {}ms", ms)))
+}
+
+/// スクリプトの整数を座標に変換します。
+fn coordinate(value: INT) -> Result<i32, Box<EvalAltResult>> {
+    i32::try_from(value).map_err(|_| runtime_error(format!("invalid coordinate: {}", value)))
+}
+
+/// 上限と公開する関数を設定したエンジンを作成します。
+fn engine(name: &str, host: &Rc<RefCell<Host>>) -> Engine {
+    let mut engine = Engine::new();
+    engine
+        .set_max_operations(MAX_OPERATIONS)
+        .set_max_call_levels(MAX_CALL_LEVELS)
+        .set_max_string_size(MAX_STRING_SIZE)
+        .set_max_array_size(MAX_COLLECTION_SIZE)
+        .set_max_map_size(MAX_COLLECTION_SIZE)
+        // 他のファイルを読み込ませない
+        .set_module_resolver(DummyModuleResolver::new())
+        .set_max_modules(0)
+        .disable_symbol("eval");
+    let label = name.to_string();
+    engine.on_print(move |text| eprintln!("{}: {}", label, text));
+    let label = name.to_string();
+    engine.on_debug(move |text, _, position| eprintln!("{}: {} {}", label, position, text));
+
+    let push = |host: &Rc<RefCell<Host>>| {
+        let host = host.clone();
+        move |command: Command| host.borrow_mut().commands.push(command)
+    };
+    let h = host.clone();
+    engine.register_fn("load", move |name: &str| {
+        let mut host = h.borrow_mut();
+        if host.started {
+            return Err(runtime_error("load() can only be called at the top level"));
+        }
+        host.loads.push(name.to_string());
+        Ok(())
+    });
+    let p = push(host);
+    engine.register_fn("play", move |name: &str| {
+        p(Command::Play(Some(name.into())))
+    });
+    let p = push(host);
+    engine.register_fn("play", move || p(Command::Play(None)));
+    let p = push(host);
+    engine.register_fn("move_to", move |x: INT, y: INT| {
+        p(Command::MoveTo(coordinate(x)?, coordinate(y)?));
+        Ok::<_, Box<EvalAltResult>>(())
+    });
+    let p = push(host);
+    engine.register_fn("move_by", move |dx: INT, dy: INT| {
+        p(Command::MoveBy(coordinate(dx)?, coordinate(dy)?));
+        Ok::<_, Box<EvalAltResult>>(())
+    });
+    let h = host.clone();
+    engine.register_fn("position", move || -> Dynamic {
+        match h.borrow().position {
+            Some((x, y)) => vec![Dynamic::from(x as INT), Dynamic::from(y as INT)].into(),
+            None => Dynamic::UNIT,
+        }
+    });
+    let p = push(host);
+    engine.register_fn("say", move |text: &str| p(Command::Say(text.into(), None)));
+    let p = push(host);
+    engine.register_fn("say", move |text: &str, ms: INT| {
+        p(Command::Say(text.into(), Some(millis(ms)?)));
+        Ok::<_, Box<EvalAltResult>>(())
+    });
+    let p = push(host);
+    engine.register_fn("emit", move |name: &str| p(Command::Emit(name.into())));
+
+    for (function, repeat) in [("set_timer", false), ("set_interval", true)] {
+        let h = host.clone();
+        engine.register_fn(function, move |name: &str, ms: INT| {
+            let delay = millis(ms)?;
+            if repeat && delay.is_zero() {
+                return Err(runtime_error("interval must be longer than 0ms"));
+            }
+            let mut host = h.borrow_mut();
+            host.timers.retain(|timer| timer.name != name);
+            if host.timers.len() >= MAX_TIMERS {
+                return Err(runtime_error("too many timers"));
+            }
+            host.timers.push(Timer {
+                name: name.to_string(),
+                delay,
+                repeat,
+                at: None,
+            });
+            Ok(())
+        });
+    }
+    let h = host.clone();
+    engine.register_fn("clear_timer", move |name: &str| {
+        h.borrow_mut().timers.retain(|timer| timer.name != name);
+    });
+    let h = host.clone();
+    engine.register_fn("random", move || h.borrow_mut().rng.next_f64());
+    let h = host.clone();
+    engine.register_fn("random", move |low: INT, high: INT| -> INT {
+        if low >= high {
+            return low;
+        }
+        let span = high.abs_diff(low);
+        low.wrapping_add(h.borrow_mut().rng.range(0, span) as INT)
+    });
+    engine
+}
+
+/// スクリプトを実行するための構造体です。
+///
+/// ```rust
+/// use bkrbk::script::{Command, Script};
+/// use bkrbk::timer::{Clock, MockClock};
+/// use std::time::Duration;
+///
+/// let source = r#"
+///     fn on_start() {
+///         this.count = 0;
+///         set_interval("tick", 1000);
+///     }
+///     fn on_timer(name) {
+///         this.count += 1;
+///         say(`tick ${this.count}`);
+///     }
+///     fn on_event(name) {
+///         throw "broken";
+///     }
+/// "#;
+/// let clock = MockClock::new();
+/// let mut script = Script::with_clock("test.rhai", source, clock.clone(), 0).unwrap();
+/// script.start().unwrap();
+///
+/// clock.advance(Duration::from_millis(1000));
+/// script.poll().unwrap();
+/// assert_eq!(script.take_commands(), [Command::Say("tick 1".into(), None)]);
+///
+/// // エラーは返すだけで、その後も呼び出せる
+/// assert!(script.event("click").is_err());
+/// clock.advance(Duration::from_millis(1000));
+/// script.poll().unwrap();
+/// assert_eq!(script.take_commands(), [Command::Say("tick 2".into(), None)]);
+///
+/// // 遅れた場合は過ぎた回数分を繰り返さずに1回だけ発火し、今から数え直す
+/// clock.advance(Duration::from_millis(10_500));
+/// script.poll().unwrap();
+/// assert_eq!(script.take_commands(), [Command::Say("tick 3".into(), None)]);
+/// assert_eq!(script.next_deadline(), Some(clock.now() + Duration::from_millis(1000)));
+///
+/// // 無限ループは処理の量の上限で止まる
+/// let mut script = Script::with_clock("loop.rhai", "fn on_start() { loop {} }", clock, 0).unwrap();
+/// assert!(script.start().is_err());
+///
+/// // 他のファイルや eval は利用できない
+/// let mut script = Script::with_clock("import.rhai", r#"import "other" as other;"#, MockClock::new(), 0).unwrap();
+/// assert!(script.start().is_err());
+/// assert!(Script::with_clock("eval.rhai", r#"eval("1")"#, MockClock::new(), 0).is_err());
+/// ```
+pub struct Script<C: Clock = SystemClock> {
+    /// エラーメッセージに表示する名前
+    name: String,
+    /// アニメーションのファイルパスの基準にするディレクトリ
+    base: PathBuf,
+    engine: Engine,
+    ast: AST,
+    /// 呼び出しの間で保持する値 (関数内の `this`)
+    this: Dynamic,
+    host: Rc<RefCell<Host>>,
+    clock: C,
+}
+
+impl Script {
+    /// ファイルからスクリプトを読み込みます。
+    pub fn load(path: &Path) -> Result<Self, Error> {
+        let source = std::fs::read_to_string(path)?;
+        let name = path.display().to_string();
+        let mut result =
+            Self::with_clock(&name, &source, SystemClock, Rng::from_entropy().next_u64())?;
+        result.base = path.parent().unwrap_or(Path::new("")).to_path_buf();
+        Ok(result)
+    }
+}
+
+impl<C: Clock> Script<C> {
+    /// 指定した時計と乱数のシード値を利用して、スクリプトを読み込みます。
+    ///
+    /// `name` は出力するメッセージに利用します。
+    pub fn with_clock(name: &str, source: &str, clock: C, seed: u64) -> Result<Self, Error> {
+        let host = Rc::new(RefCell::new(Host {
+            commands: Vec::new(),
+            timers: Vec::new(),
+            position: None,
+            loads: Vec::new(),
+            started: false,
+            rng: Rng::new(seed),
+        }));
+        let engine = engine(name, &host);
+        let ast = engine.compile(source)?;
+        Ok(Self {
+            name: name.to_string(),
+            base: PathBuf::new(),
+            engine,
+            ast,
+            this: Map::new().into(),
+            host,
+            clock,
+        })
+    }
+
+    /// スクリプトの名前を取得します。
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// トップレベルを実行した後、 `on_start()` を呼び出します。
+    ///
+    /// トップレベルで `load()` したアニメーションは `loads()` で取得できます。
+    pub fn start(&mut self) -> Result<(), Error> {
+        let result = self.engine.run_ast(&self.ast);
+        self.host.borrow_mut().started = true;
+        result?;
+        self.call("on_start", ())
+    }
+
+    /// トップレベルで `load()` したアニメーションの一覧を取得します。
+    pub fn loads(&self) -> Vec<String> {
+        self.host.borrow().loads.clone()
+    }
+
+    /// スクリプトで指定したアニメーションのファイルパスを、スクリプトのあるディレクトリからのパスに変換します。
+    pub fn resolve(&self, filepath: &str) -> PathBuf {
+        self.base.join(filepath)
+    }
+
+    /// `position()` で返すウィンドウの位置を設定します。
+    pub fn set_position(&mut self, position: Option<(i32, i32)>) {
+        self.host.borrow_mut().position = position;
+    }
+
+    /// スクリプトから依頼された操作を取り出します。
+    pub fn take_commands(&mut self) -> Vec<Command> {
+        std::mem::take(&mut self.host.borrow_mut().commands)
+    }
+
+    /// イベントを知らせ、 `on_event(name)` を呼び出します。
+    pub fn event(&mut self, name: &str) -> Result<(), Error> {
+        self.call("on_event", (name.to_string(),))
+    }
+
+    /// アニメーションを1回再生し終えたことを知らせ、 `on_finished(animation)` を呼び出します。
+    pub fn finished(&mut self, animation: &str) -> Result<(), Error> {
+        self.call("on_finished", (animation.to_string(),))
+    }
+
+    /// 状態が移ったことを知らせ、 `on_state(name)` を呼び出します。
+    pub fn state(&mut self, name: &str) -> Result<(), Error> {
+        self.call("on_state", (name.to_string(),))
+    }
+
+    /// 時刻になったタイマーの `on_timer(name)` を呼び出します。
+    ///
+    /// 1回の呼び出しで各タイマーを最大1回だけ発火します。
+    /// エラーが発生した場合は、残りのタイマーを次の呼び出しで処理します。
+    pub fn poll(&mut self) -> Result<(), Error> {
+        self.schedule();
+        let now = self.clock.now();
+        // 呼び出しの間に時刻になったタイマーは次の呼び出しで処理する
+        let mut due: Vec<(Instant, String)> = self
+            .host
+            .borrow()
+            .timers
+            .iter()
+            .filter_map(|timer| Some((timer.at.filter(|at| now >= *at)?, timer.name.clone())))
+            .collect();
+        due.sort();
+        for (_, name) in due {
+            {
+                let mut host = self.host.borrow_mut();
+                // 先に呼び出したタイマーで解除 (または設定し直) されたものは除く
+                let Some(i) = host
+                    .timers
+                    .iter()
+                    .position(|timer| timer.name == name && timer.at.is_some_and(|at| now >= at))
+                else {
+                    continue;
+                };
+                let timer = &mut host.timers[i];
+                match timer.repeat {
+                    // 遅れて何回分も時刻を過ぎた場合は、まとめて1回だけ発火して今から数え直す
+                    true => {
+                        timer.at = timer.at.map(|at| match at + timer.delay {
+                            next if next > now => next,
+                            _ => now + timer.delay,
+                        })
+                    }
+                    false => {
+                        host.timers.remove(i);
+                    }
+                }
+            }
+            let result = self.call("on_timer", (name,));
+            self.schedule();
+            result?;
+        }
+        Ok(())
+    }
+
+    /// 次にタイマーの時刻になる時刻を取得します。
+    pub fn next_deadline(&self) -> Option<Instant> {
+        self.host
+            .borrow()
+            .timers
+            .iter()
+            .filter_map(|timer| timer.at)
+            .min()
+    }
+
+    /// 設定した直後のタイマーの時刻を決めます。
+    fn schedule(&mut self) {
+        let now = self.clock.now();
+        for timer in &mut self.host.borrow_mut().timers {
+            timer.at.get_or_insert(now + timer.delay);
+        }
+    }
+
+    /// スクリプトの関数が定義されていれば呼び出します。
+    fn call(&mut self, function: &str, args: impl rhai::FuncArgs) -> Result<(), Error> {
+        let mut values = Vec::new();
+        args.parse(&mut values);
+        let defined = self
+            .ast
+            .iter_functions()
+            .any(|f| f.name == function && f.params.len() == values.len());
+        if !defined {
+            return Ok(());
+        }
+        let options = CallFnOptions::new()
+            .eval_ast(false)
+            .bind_this_ptr(&mut self.this);
+        let result = self.engine.call_fn_with_options::<Dynamic>(
+            options,
+            &mut Scope::new(),
+            &self.ast,
+            function,
+            values,
+        );
+        self.schedule();
+        result.map(|_| ()).map_err(Error::from)
+    }
+}
@@ -0,0 +1,604 @@
+//! キャラクターの振る舞いを状態遷移で扱うための機能です。
+//!
+//! 各状態には再生するアニメーションと再生方法、他の状態へ移る条件を結び付けます。
+//! 状態はテキストで定義するので、コードを変更せずに振る舞いを変えられます。
+//!
+//! ```text
+//! # 最初に書いた状態から始まる
+//! [idle]
+//! animation = child.gif
+//! on after 10s = jump
+//! on chance 20% every 1s = jump
+//! on click = jump
+//!
+//! [jump]
+//! animation = jump.gif
+//! mode = once
+//! on finished = idle
+//! ```
+//!
+//! 状態には次の項目を指定できます。
+//!
+//! * `animation` : 再生するアニメーションのファイルパス (必須、定義ファイルからの相対パス)
+//! * `mode` : 再生の方法 (`loop`, `once`, `ping-pong`)
+//! * `speed` : 再生速度 (倍率、0.01 から 100 まで)
+//! * `loops` : `finished` とみなすまでに再生する回数
+//! * `on <条件> = <状態>` : 他の状態へ移る条件 (上に書いたものから順に判定します)
+//! * `pick <状態> = <重み>[, cooldown <時間>][, between HH:MM-HH:MM]` : 移る先を抽選で選ぶ場合の候補
+//!
+//! 移る先の状態に `*` を指定すると、その状態の `pick` の候補から重み付きの抽選で選びます。
+//! `cooldown` を指定した候補は一度選ばれてから指定した時間が経つまで、
+//! `between` を指定した候補は指定した時間帯 (ローカルタイム) 以外では選ばれません。
+//! 選べる候補が無い場合は、その条件では移りません。
+//!
+//! ```text
+//! [idle]
+//! animation = child.gif
+//! mode = once
+//! on finished = *
+//! pick idle = 5
+//! pick jump = 1, cooldown 30s
+//! pick sleep = 3, between 22:00-06:00
+//! ```
+//!
+//! 条件には次のものがあります。
+//!
+//! * `finished` : アニメーションを `loops` 回再生した時
+//! * `after <時間>` : 状態に入ってから指定した時間が経った時
+//! * `chance <確率>% every <時間>` : 指定した時間ごとに、指定した確率で
+//! * それ以外の名前 : `StateMachine::event` で同じ名前のイベントを受け取った時 (`click` など)
+
+use std::{
+    path::Path,
+    time::{Duration, Instant},
+};
+
+use crate::{
+    behavior::{Behaviors, Choice},
+    player::{self, Mode},
+    random::Rng,
+    timer::{parse_duration, Clock, SystemClock},
+};
+
+/// 状態の定義の読み込みで発生するエラーです。
+#[derive(Debug)]
+pub enum Error {
+    /// ファイルの読み込みに失敗しました。
+    Io(std::io::Error),
+    /// 定義が正しくありません。 (行番号と内容の一覧)
+    Parse(Vec<(usize, String)>),
+}
+
+impl std::fmt::Display for Error {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Io(e) => write!(f, "{}", e),
+            Self::Parse(problems) => {
+                let lines: Vec<String> = problems
+                    .iter()
+                    .map(|(line, message)| format!("line {}: {}", line, message))
+                    .collect();
+                write!(f, "{}", lines.join("\n"))
+            }
+        }
+    }
+}
+
+impl std::error::Error for Error {}
+
+impl From<std::io::Error> for Error {
+    fn from(value: std::io::Error) -> Self {
+        Self::Io(value)
+    }
+}
+
+/// 他の状態へ移る条件です。
+#[derive(Debug, Clone, PartialEq)]
+pub enum Trigger {
+    /// アニメーションを `loops` 回再生した時
+    Finished,
+    /// 状態に入ってから指定した時間が経った時
+    After(Duration),
+    /// `every` ごとに `probability` (0.0 から 1.0) の確率で
+    Chance { probability: f64, every: Duration },
+    /// 同じ名前のイベントを受け取った時
+    Event(String),
+}
+
+impl std::str::FromStr for Trigger {
+    type Err = String;
+
+    /// `finished`, `after <時間>`, `chance <確率>% every <時間>`, `<イベント名>` のいずれかを受け付けます。
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let words: Vec<&str> = s.split_whitespace().collect();
+        let duration = |s: &str| parse_duration(s).ok_or(format!("invalid duration: {}", s));
+        match words[..] {
+            ["finished"] => Ok(Self::Finished),
+            ["after", after] => Ok(Self::After(duration(after)?)),
+            ["chance", probability, "every", every] => {
+                let probability = probability
+                    .strip_suffix('%')
+                    .and_then(|p| p.parse::<f64>().ok())
+                    .filter(|p| (0.0..=100.0).contains(p))
+                    .ok_or(format!("invalid probability: {}", probability))?;
+                let every = duration(every)?;
+                match every.is_zero() {
+                    true => Err("interval must not be zero".to_string()),
+                    false => Ok(Self::Chance {
+                        probability: probability / 100.0,
+                        every,
+                    }),
+                }
+            }
+            [name]
+                if name
+                    .chars()
+                    .all(|c| c.is_alphanumeric() || c == '-' || c == '_') =>
+            {
+                Ok(Self::Event(name.to_string()))
+            }
+            _ => Err(format!("invalid trigger: {}", s)),
+        }
+    }
+}
+
+/// 移る先の状態です。
+///
+/// ```rust
+/// use bkrbk::state::{StateMachine, States};
+/// use bkrbk::timer::MockClock;
+/// use std::time::Duration;
+///
+/// let text = "[idle]\nanimation = child.gif\non click = *\npick jump = 1, cooldown 1m\npick wave = 1, cooldown 1m\n\n\
+///     [jump]\nanimation = jump.gif\non click = idle\n\n[wave]\nanimation = wave.gif\non click = idle\n";
+/// let clock = MockClock::new();
+/// let mut machine = StateMachine::with_clock(States::parse(text).unwrap(), clock.clone(), 1);
+///
+/// // 候補はクールダウンの間は選ばれないので、2回目は必ず別の状態に移る
+/// let mut visited = Vec::new();
+/// for _ in 0..2 {
+///     assert!(machine.event("click"));
+///     visited.push(machine.state().name.clone());
+///     machine.event("click");
+/// }
+/// visited.sort();
+/// assert_eq!(visited, ["jump", "wave"]);
+///
+/// // 選べる候補が無ければ移らない
+/// assert!(!machine.event("click"));
+/// clock.advance(Duration::from_secs(60));
+/// assert!(machine.event("click"));
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Target {
+    /// 指定した番号の状態
+    State(usize),
+    /// `State::picks` から抽選で選んだ状態
+    Pick,
+}
+
+/// 状態から他の状態へ移る条件と、移る先の状態です。
+#[derive(Debug, Clone, PartialEq)]
+pub struct Transition {
+    /// 条件
+    pub trigger: Trigger,
+    /// 移る先の状態
+    pub target: Target,
+}
+
+/// キャラクターの状態です。
+#[derive(Debug, Clone, PartialEq)]
+pub struct State {
+    /// 状態の名前
+    pub name: String,
+    /// 再生するアニメーションのファイルパス
+    pub animation: String,
+    /// 再生の方法
+    pub mode: Mode,
+    /// 再生速度
+    pub speed: f64,
+    /// `Trigger::Finished` とみなすまでに再生する回数
+    pub loops: usize,
+    /// 他の状態へ移る条件 (先頭から順に判定します)
+    pub transitions: Vec<Transition>,
+    /// `Target::Pick` で移る先の候補 (移る先の状態の番号)
+    pub picks: Vec<Choice<usize>>,
+}
+
+/// 状態の定義の一覧です。 (先頭の状態から始まります)
+#[derive(Debug, Clone, PartialEq)]
+pub struct States {
+    pub states: Vec<State>,
+}
+
+impl States {
+    /// ファイルから状態の定義を読み込みます。
+    ///
+    /// アニメーションのファイルパスは、定義ファイルのあるディレクトリからの相対パスとして扱います。
+    pub fn load(path: &Path) -> Result<Self, Error> {
+        let mut result = Self::parse(&std::fs::read_to_string(path)?)?;
+        let base = path.parent().unwrap_or(Path::new(""));
+        for state in &mut result.states {
+            state.animation = base.join(&state.animation).to_string_lossy().into_owned();
+        }
+        Ok(result)
+    }
+
+    /// テキストから状態の定義を読み込みます。
+    ///
+    /// 問題のある行を全て集めてエラーとして返します。
+    /// ```rust
+    /// use bkrbk::state::Target;
+    ///
+    /// let text = "[idle]\nanimation = child.gif\non click = jump\n\n[jump]\nanimation = jump.gif\nmode = once\non finished = idle\n";
+    /// let states = bkrbk::state::States::parse(text).unwrap();
+    /// assert_eq!(states.states.len(), 2);
+    /// assert_eq!(states.states[0].transitions[0].target, Target::State(1));
+    ///
+    /// let error = bkrbk::state::States::parse("[idle]\nmode = fast\non click = sleep\n").unwrap_err();
+    /// assert_eq!(
+    ///     error.to_string(),
+    ///     "line 2: unknown playback mode: fast\nline 3: unknown state: sleep\nline 1: missing animation in state idle"
+    /// );
+    /// ```
+    pub fn parse(text: &str) -> Result<Self, Error> {
+        Self::parse_lines(text.lines().enumerate().map(|(i, line)| (i + 1, line)))
+    }
+
+    /// 行番号と行の組の一覧から状態の定義を読み込みます。
+    ///
+    /// 他の形式のファイルの一部に状態を定義する場合に、元のファイルでの行番号を保ったまま読み込めます。
+    pub fn parse_lines<'a>(
+        lines: impl IntoIterator<Item = (usize, &'a str)>,
+    ) -> Result<Self, Error> {
+        let mut states: Vec<State> = Vec::new();
+        // 状態の名前を解決する前の遷移 (状態の番号, 行番号, 条件, 移る先の状態の名前)
+        let mut transitions = Vec::new();
+        // 状態の名前を解決する前の抽選の候補 (状態の番号, 行番号, 移る先の状態の名前, 候補)
+        let mut picks = Vec::new();
+        // 各状態を定義した行番号
+        let mut headers = Vec::new();
+        let mut problems = Vec::new();
+        for (line_number, line) in lines {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            if let Some(name) = line.strip_prefix('[').and_then(|l| l.strip_suffix(']')) {
+                let name = name.trim();
+                if states.iter().any(|state| state.name == name) {
+                    problems.push((line_number, format!("duplicate state: {}", name)));
+                }
+                states.push(State {
+                    name: name.to_string(),
+                    animation: String::new(),
+                    mode: Mode::default(),
+                    speed: 1.0,
+                    loops: 1,
+                    transitions: Vec::new(),
+                    picks: Vec::new(),
+                });
+                headers.push(line_number);
+                continue;
+            }
+            let Some(state) = states.last_mut() else {
+                problems.push((line_number, "entry outside of a state".to_string()));
+                continue;
+            };
+            let Some((key, value)) = line.split_once('=') else {
+                problems.push((line_number, format!("invalid entry: {}", line)));
+                continue;
+            };
+            let (key, value) = (key.trim(), value.trim());
+            let result = match key {
+                "animation" if !value.is_empty() => {
+                    state.animation = value.to_string();
+                    Ok(())
+                }
+                "mode" => value.parse().map(|mode| state.mode = mode),
+                "speed" => value
+                    .parse()
+                    .ok()
+                    .filter(|speed| (player::MIN_SPEED..=player::MAX_SPEED).contains(speed))
+                    .map(|speed| state.speed = speed)
+                    .ok_or(format!("invalid speed: {}", value)),
+                "loops" => value
+                    .parse()
+                    .ok()
+                    .filter(|loops| *loops > 0)
+                    .map(|loops| state.loops = loops)
+                    .ok_or(format!("invalid loops: {}", value)),
+                _ => match (key.strip_prefix("on "), key.strip_prefix("pick ")) {
+                    (Some(trigger), _) => trigger.parse().map(|trigger| {
+                        transitions.push((states.len() - 1, line_number, trigger, value));
+                    }),
+                    (_, Some(target)) => parse_choice(value).map(|choice| {
+                        picks.push((states.len() - 1, line_number, target.trim(), choice));
+                    }),
+                    _ => Err(format!("invalid entry: {}", line)),
+                },
+            };
+            if let Err(message) = result {
+                problems.push((line_number, message));
+            }
+        }
+
+        for (index, line_number, target, choice) in picks {
+            match states.iter().position(|state| state.name == target) {
+                Some(target) => states[index].picks.push(Choice {
+                    event: target,
+                    ..choice
+                }),
+                None => problems.push((line_number, format!("unknown state: {}", target))),
+            }
+        }
+        for (index, line_number, trigger, target) in transitions {
+            let target = match target {
+                "*" if states[index].picks.is_empty() => {
+                    let name = &states[index].name;
+                    problems.push((line_number, format!("no pick entries in state {}", name)));
+                    continue;
+                }
+                "*" => Target::Pick,
+                _ => match states.iter().position(|state| state.name == target) {
+                    Some(target) => Target::State(target),
+                    None => {
+                        problems.push((line_number, format!("unknown state: {}", target)));
+                        continue;
+                    }
+                },
+            };
+            states[index]
+                .transitions
+                .push(Transition { trigger, target });
+        }
+        for (state, line_number) in states.iter().zip(headers) {
+            if state.animation.is_empty() {
+                problems.push((
+                    line_number,
+                    format!("missing animation in state {}", state.name),
+                ));
+            }
+        }
+        if states.is_empty() {
+            problems.push((1, "no state is defined".to_string()));
+        }
+        match problems.is_empty() {
+            true => Ok(Self { states }),
+            false => Err(Error::Parse(problems)),
+        }
+    }
+}
+
+/// `pick` の値 (`<重み>[, cooldown <時間>][, between HH:MM-HH:MM]`) を読み込みます。
+///
+/// 移る先の状態は名前を解決した後で設定するため、仮の値になります。
+fn parse_choice(value: &str) -> Result<Choice<usize>, String> {
+    let mut parts = value.split(',').map(str::trim);
+    let weight = parts.next().unwrap_or_default();
+    let weight = weight
+        .parse()
+        .ok()
+        .filter(|weight: &f64| weight.is_finite() && *weight >= 0.0)
+        .ok_or(format!("invalid weight: {}", weight))?;
+    let mut choice = Choice::new(0, weight);
+    for part in parts {
+        match part.split_once(char::is_whitespace) {
+            Some(("cooldown", duration)) => {
+                choice.cooldown = parse_duration(duration)
+                    .ok_or(format!("invalid duration: {}", duration.trim()))?
+            }
+            Some(("between", window)) => choice.window = Some(window.trim().parse()?),
+            _ => return Err(format!("invalid pick option: {}", part)),
+        }
+    }
+    Ok(choice)
+}
+
+/// 状態遷移を実行するための構造体です。
+///
+/// ```rust
+/// use bkrbk::state::{StateMachine, States};
+/// use bkrbk::timer::{Clock, MockClock};
+/// use std::time::Duration;
+///
+/// let text = "[idle]\nanimation = child.gif\non after 10s = jump\non click = jump\n\n[jump]\nanimation = jump.gif\nloops = 2\non finished = idle\n";
+/// let clock = MockClock::new();
+/// let mut machine = StateMachine::with_clock(States::parse(text).unwrap(), clock.clone(), 0);
+/// assert_eq!(machine.state().name, "idle");
+///
+/// // クリックで移る
+/// assert!(machine.event("click"));
+/// assert_eq!(machine.state().name, "jump");
+///
+/// // 2回再生したら戻る
+/// machine.complete(1);
+/// assert_eq!(machine.poll(), None);
+/// machine.complete(1);
+/// assert_eq!(machine.poll(), Some(0));
+///
+/// // 一定時間が経つと移る
+/// assert_eq!(machine.next_deadline(), Some(clock.now() + Duration::from_secs(10)));
+/// clock.advance(Duration::from_secs(10));
+/// assert_eq!(machine.poll(), Some(1));
+///
+/// // 抽選で選べる候補が無い場合は、同じ時間が経ってから判定し直す
+/// let text = "[idle]\nanimation = child.gif\non after 10s = *\npick jump = 1, cooldown 1h\n\n[jump]\nanimation = jump.gif\non after 1s = idle\n";
+/// let mut machine = StateMachine::with_clock(States::parse(text).unwrap(), clock.clone(), 0);
+/// clock.advance(Duration::from_secs(10));
+/// assert_eq!(machine.poll(), Some(1));
+/// clock.advance(Duration::from_secs(1));
+/// assert_eq!(machine.poll(), Some(0));
+/// clock.advance(Duration::from_secs(10));
+/// assert_eq!(machine.poll(), None);
+/// assert_eq!(machine.next_deadline(), Some(clock.now() + Duration::from_secs(10)));
+/// ```
+pub struct StateMachine<C: Clock = SystemClock> {
+    states: States,
+    /// 現在の状態の番号
+    current: usize,
+    /// 現在の状態でアニメーションを再生した回数
+    completed: usize,
+    /// 時間や確率による条件を次に判定する時刻 (条件ごと)
+    deadlines: Vec<Option<Instant>>,
+    /// 抽選で移る先を選ぶための候補 (状態ごと)
+    behaviors: Vec<Behaviors<usize, C>>,
+    clock: C,
+    rng: Rng,
+}
+
+impl StateMachine {
+    /// 最初の状態から始めます。
+    pub fn new(states: States) -> Self {
+        Self::with_clock(states, SystemClock, Rng::from_entropy().next_u64())
+    }
+}
+
+impl<C: Clock + Clone> StateMachine<C> {
+    /// 指定した時計と乱数のシード値を利用して、最初の状態から始めます。
+    pub fn with_clock(states: States, clock: C, seed: u64) -> Self {
+        let mut rng = Rng::new(seed);
+        let behaviors = states
+            .states
+            .iter()
+            .map(|state| {
+                let mut behaviors = Behaviors::with_clock(clock.clone(), rng.next_u64());
+                for choice in &state.picks {
+                    behaviors.add(choice.clone());
+                }
+                behaviors
+            })
+            .collect();
+        let mut result = Self {
+            states,
+            current: 0,
+            completed: 0,
+            deadlines: Vec::new(),
+            behaviors,
+            clock,
+            rng,
+        };
+        result.enter(0);
+        result
+    }
+
+    /// 状態の定義の一覧を取得します。
+    pub fn states(&self) -> &[State] {
+        &self.states.states
+    }
+
+    /// 現在の状態の番号を取得します。
+    pub fn current(&self) -> usize {
+        self.current
+    }
+
+    /// 現在の状態を取得します。
+    pub fn state(&self) -> &State {
+        &self.states.states[self.current]
+    }
+
+    /// 指定した状態に移ります。 (同じ状態を指定した場合は入り直します)
+    pub fn enter(&mut self, index: usize) {
+        let now = self.clock.now();
+        self.current = index.min(self.states.states.len() - 1);
+        self.completed = 0;
+        self.deadlines = self
+            .state()
+            .transitions
+            .iter()
+            .map(|transition| match transition.trigger {
+                Trigger::After(after) => Some(now + after),
+                Trigger::Chance { every, .. } => Some(now + every),
+                _ => None,
+            })
+            .collect();
+    }
+
+    /// アニメーションを指定した回数だけ再生し終えたことを知らせます。 ( `Player::completed` を渡します)
+    pub fn complete(&mut self, count: usize) {
+        self.completed += count;
+    }
+
+    /// 移る先の状態の番号を決めます。 (抽選で選べる候補が無い場合は None を返します)
+    fn resolve(&mut self, target: Target) -> Option<usize> {
+        match target {
+            Target::State(index) => Some(index),
+            Target::Pick => self.behaviors[self.current].pick().copied(),
+        }
+    }
+
+    /// イベントを知らせ、同じ名前の条件があればその状態に移ります。
+    ///
+    /// 状態を移った場合は true を返します。
+    pub fn event(&mut self, name: &str) -> bool {
+        let target = self
+            .state()
+            .transitions
+            .iter()
+            .find(
+                |transition| matches!(&transition.trigger, Trigger::Event(event) if event == name),
+            )
+            .map(|transition| transition.target);
+        let target = target.and_then(|target| self.resolve(target));
+        if let Some(target) = target {
+            self.enter(target);
+        }
+        target.is_some()
+    }
+
+    /// 時間や再生回数、確率による条件を判定し、状態を移った場合は移った先の状態の番号を返します。
+    pub fn poll(&mut self) -> Option<usize> {
+        let now = self.clock.now();
+        let mut target = None;
+        for (i, transition) in self.states.states[self.current]
+            .transitions
+            .iter()
+            .enumerate()
+        {
+            let behaviors = &mut self.behaviors[self.current];
+            let fired = match &transition.trigger {
+                Trigger::Finished => self.completed >= self.states.states[self.current].loops,
+                Trigger::After(after) => match self.deadlines[i].filter(|at| now >= *at) {
+                    Some(_) => {
+                        // 移る先が無かった場合は、同じ時間が経ってから判定し直す
+                        self.deadlines[i] = Some(now + *after);
+                        true
+                    }
+                    None => false,
+                },
+                Trigger::Chance { probability, every } => {
+                    match self.deadlines[i].filter(|at| now >= *at) {
+                        Some(at) => {
+                            // 判定し損ねた回はまとめて1回だけ判定する
+                            let next = at + *every;
+                            self.deadlines[i] = Some(if next <= now { now + *every } else { next });
+                            self.rng.next_f64() < *probability
+                        }
+                        None => false,
+                    }
+                }
+                Trigger::Event(_) => false,
+            };
+            if !fired {
+                continue;
+            }
+            // 抽選で選べる候補が無い場合は、次の条件を判定する
+            target = match transition.target {
+                Target::State(index) => Some(index),
+                Target::Pick => behaviors.pick().copied(),
+            };
+            if target.is_some() {
+                break;
+            }
+        }
+        if let Some(target) = target {
+            self.enter(target);
+        }
+        target
+    }
+
+    /// 時間や確率による条件を次に判定する時刻を取得します。
+    pub fn next_deadline(&self) -> Option<Instant> {
+        self.deadlines.iter().flatten().min().copied()
+    }
+}
@@ -0,0 +1,180 @@
+//! 画像を表示できる端末に、画素単位で正確に描画するための機能です。
+
+use std::io::Write;
+
+/// 画像の出力形式です。
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Protocol {
+    /// Sixel
+    Sixel,
+    /// Kitty graphics protocol
+    Kitty,
+}
+
+impl Protocol {
+    /// 画像をエスケープシーケンスに変換します。
+    pub fn encode(&self, image: &[u32], width: usize, height: usize, bg: u32) -> Vec<u8> {
+        match self {
+            Self::Sixel => super::sixel::encode(image, width, height, bg),
+            Self::Kitty => super::kitty::encode(image, width, height, bg),
+        }
+    }
+}
+
+/// 端末の画像表示機能を描画先とするバックエンドです。
+pub struct Graphics<W: Write> {
+    out: W,
+    protocol: Protocol,
+    width: u32,
+    height: u32,
+    /// 画面に描画する情報です。
+    pub image: Vec<u32>,
+    /// 前回描画した画像 (変化が無い場合は出力しません)
+    previous: Option<Vec<u32>>,
+    /// 画像の元になった GIF のパレット (Sixel の色の指定に利用します)
+    palette: Vec<u32>,
+    pub is_hide: bool,
+    /// 出力に失敗したかどうか (失敗した後は `update()` で None を返します)
+    failed: bool,
+
+    /// 透過色
+    pub bg: u32,
+}
+
+impl Graphics<std::io::Stdout> {
+    /// 標準出力に描画するバックエンドを作成します。 (Ctrl-C で終了する際も表示を元に戻します)
+    pub fn stdout(protocol: Protocol, width: u32, height: u32, bg: u32) -> Self {
+        super::catch_interrupt();
+        Self::new(std::io::stdout(), protocol, width, height, bg)
+    }
+}
+
+impl<W: Write> Graphics<W> {
+    /// 指定した出力先に描画するバックエンドを作成します。
+    pub fn new(out: W, protocol: Protocol, width: u32, height: u32, bg: u32) -> Self {
+        Self {
+            out,
+            protocol,
+            width,
+            height,
+            image: vec![bg; (width * height) as usize],
+            previous: None,
+            palette: Vec::new(),
+            is_hide: true,
+            failed: false,
+            bg,
+        }
+    }
+
+    /// 出力先を取得します。
+    pub fn output(&self) -> &W {
+        &self.out
+    }
+
+    /// 前回の描画から変化があった場合に画像を出力します。
+    pub fn draw(&mut self) -> std::io::Result<()> {
+        if self.is_hide || self.previous.as_ref() == Some(&self.image) {
+            return Ok(());
+        }
+
+        let (width, height) = (self.width as usize, self.height as usize);
+        let data = match self.protocol {
+            Protocol::Sixel => {
+                let palette =
+                    super::sixel::Palette::with_colors(&self.image, self.bg, &self.palette);
+                // 透過部分は上書きされないので、前のフレームで描画した画素が透過色に変わった場合は背景色で塗りつぶす
+                let fill = self.previous.as_ref().is_some_and(|previous| {
+                    previous
+                        .iter()
+                        .zip(&self.image)
+                        .any(|(before, after)| *before != self.bg && *after == self.bg)
+                });
+                super::sixel::encode_palette(&palette, width, height, fill)
+            }
+            Protocol::Kitty => super::kitty::encode(&self.image, width, height, self.bg),
+        };
+        // 画面を消去すると点滅するので、同じ位置に上書きする
+        self.out.write_all(b"\x1b[H")?;
+        self.out.write_all(&data)?;
+        self.out.flush()?;
+        self.previous = Some(self.image.clone());
+        Ok(())
+    }
+
+    /// 画面を消去してから描画します。
+    pub fn show(&mut self) -> std::io::Result<()> {
+        self.out.write_all(b"\x1b[?25l\x1b[2J")?;
+        self.previous = None;
+        self.is_hide = false;
+        self.draw()
+    }
+
+    /// 描画した画像を消去します。
+    pub fn hide(&mut self) -> std::io::Result<()> {
+        if self.protocol == Protocol::Kitty {
+            // 表示中の画像を全て削除する
+            self.out.write_all(b"\x1b_Ga=d,q=2\x1b\\")?;
+        }
+        self.out.write_all(b"\x1b[2J\x1b[1;1H\x1b[?25h")?;
+        self.out.flush()?;
+        self.is_hide = true;
+        Ok(())
+    }
+
+    /// 出力に失敗した場合はエラーを表示し、次の `update()` でアプリケーションを終了させます。
+    fn check(&mut self, result: std::io::Result<()>) {
+        if let Err(e) = result {
+            eprintln!("{}", e);
+            self.failed = true;
+        }
+    }
+}
+
+impl<W: Write> Drop for Graphics<W> {
+    /// 描画した画像を消去し、カーソルの表示を元に戻します。
+    fn drop(&mut self) {
+        if !self.is_hide && !self.failed {
+            let _ = self.hide();
+        }
+        let _ = self.out.write_all(b"\x1b[?25h");
+        let _ = self.out.flush();
+    }
+}
+
+impl<W: Write> crate::backend::Backend for Graphics<W> {
+    fn update(&mut self) -> Option<bool> {
+        match self.failed || super::interrupted() {
+            true => None,
+            false => Some(false),
+        }
+    }
+
+    fn image(&mut self) -> &mut [u32] {
+        &mut self.image
+    }
+
+    fn draw(&mut self) {
+        let result = Graphics::draw(self);
+        self.check(result);
+    }
+
+    fn show(&mut self) {
+        let result = Graphics::show(self);
+        self.check(result);
+    }
+
+    fn hide(&mut self) {
+        let result = Graphics::hide(self);
+        self.check(result);
+    }
+
+    fn set_palette(&mut self, palette: &[u32]) {
+        if self.palette != palette {
+            self.palette = palette.to_vec();
+        }
+    }
+
+    fn size(&self) -> (u32, u32) {
+        (self.width, self.height)
+    }
+}
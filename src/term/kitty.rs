@@ -0,0 +1,73 @@
+//! Kitty graphics protocol で画像を出力するための機能です。
+
+/// 1つのエスケープシーケンスに含めるデータの最大長です。
+const CHUNK_SIZE: usize = 4096;
+
+/// 画像の ID (同じ ID で送信すると前の画像を置き換えます)
+const IMAGE_ID: u32 = 1;
+
+/// 画像を Kitty graphics protocol のエスケープシーケンスに変換します。
+///
+/// 画素は RGBA (32bit) で送信し、透過色の画素はアルファ値を0にします。
+/// データは 4096 バイトごとに分割して送信します。
+/// ```rust
+/// let image = [0x00_FF_00_00, 0x00_00_00_FF];
+/// let kitty = bkrbk::term::kitty::encode(&image, 1, 2, 0x00_00_00_FF);
+/// assert_eq!(
+///     String::from_utf8(kitty).unwrap(),
+///     "\x1b_Ga=T,f=32,s=1,v=2,i=1,p=1,q=2,C=1,m=0;/wAA/wAAAAA=\x1b\\",
+/// );
+/// ```
+pub fn encode(image: &[u32], width: usize, height: usize, bg: u32) -> Vec<u8> {
+    let rgba: Vec<u8> = image
+        .iter()
+        .flat_map(|&pixel| match pixel == bg {
+            true => [0, 0, 0, 0],
+            false => {
+                let [_, r, g, b] = pixel.to_be_bytes();
+                [r, g, b, 0xFF]
+            }
+        })
+        .collect();
+    let data = base64(&rgba);
+
+    let mut result = String::new();
+    let chunks: Vec<&[u8]> = data.as_bytes().chunks(CHUNK_SIZE).collect();
+    for (i, chunk) in chunks.iter().enumerate() {
+        let more = (i + 1 < chunks.len()) as u8;
+        result += "\x1b_G";
+        if i == 0 {
+            // 送信と同時に表示し、応答は返さない。カーソルは移動しない。
+            result += &format!(
+                "a=T,f=32,s={},v={},i={},p=1,q=2,C=1,",
+                width, height, IMAGE_ID
+            );
+        }
+        result += &format!("m={};", more);
+        // base64 の文字は ASCII のみ
+        result += std::str::from_utf8(chunk).unwrap();
+        result += "\x1b\\";
+    }
+    result.into_bytes()
+}
+
+/// バイト列を base64 (パディングあり) に変換します。
+fn base64(data: &[u8]) -> String {
+    const TABLE: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+    let mut result = String::with_capacity(data.len().div_ceil(3) * 4);
+    for chunk in data.chunks(3) {
+        let bytes = [
+            chunk[0],
+            chunk.get(1).copied().unwrap_or(0),
+            chunk.get(2).copied().unwrap_or(0),
+        ];
+        let value = ((bytes[0] as u32) << 16) | ((bytes[1] as u32) << 8) | bytes[2] as u32;
+        for i in 0..4 {
+            match i <= chunk.len() {
+                true => result.push(TABLE[(value >> (18 - i * 6)) as usize & 0x3F] as char),
+                false => result.push('='),
+            }
+        }
+    }
+    result
+}
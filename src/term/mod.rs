@@ -0,0 +1,302 @@
+//! 端末上に描画するための機能です。
+//!
+//! 上半分のブロック文字 ( `▀` ) の前景色と背景色に縦2画素分の色を割り当て、24bit カラーのエスケープシーケンスで描画します。
+//! 前回のフレームから変化したセルだけを出力するので、SSH 越しや CI のログでも確認できます。
+//!
+//! 画像を表示できる端末向けに、 Sixel と Kitty graphics protocol での出力にも対応しています ( `graphics` ) 。
+
+use std::{
+    io::Write,
+    sync::atomic::{AtomicBool, Ordering},
+};
+
+pub mod graphics;
+pub mod kitty;
+pub mod sixel;
+
+use crate::color::Color;
+
+/// 上半分のブロック文字
+const UPPER_HALF_BLOCK: char = '▀';
+/// 下半分のブロック文字
+const LOWER_HALF_BLOCK: char = '▄';
+
+/// Ctrl-C が押されたかどうか
+static INTERRUPTED: AtomicBool = AtomicBool::new(false);
+
+/// Ctrl-C ですぐに終了せず、次の `update()` で終了するようにします。
+///
+/// 終了する前に端末の表示を元に戻すためです。2回目の Ctrl-C ではすぐに終了します。
+fn catch_interrupt() {
+    #[cfg(target_os = "linux")]
+    {
+        extern "C" fn handle(_: libc::c_int) {
+            INTERRUPTED.store(true, Ordering::SeqCst);
+            unsafe { libc::signal(libc::SIGINT, libc::SIG_DFL) };
+        }
+        let handle: extern "C" fn(libc::c_int) = handle;
+        unsafe { libc::signal(libc::SIGINT, handle as libc::sighandler_t) };
+    }
+}
+
+/// Ctrl-C が押されたかどうかを判定します。
+fn interrupted() -> bool {
+    INTERRUPTED.load(Ordering::SeqCst)
+}
+
+/// 1セル分 (縦2画素) の表示内容です。
+///
+/// 透過色の画素は None として扱い、端末のデフォルトの色で表示します。
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+struct Cell {
+    top: Option<u32>,
+    bottom: Option<u32>,
+}
+
+impl Cell {
+    /// セルを描画するための文字と前景色・背景色を取得します。
+    fn glyph(&self) -> (char, Option<u32>, Option<u32>) {
+        match (self.top, self.bottom) {
+            (None, None) => (' ', None, None),
+            (None, Some(bottom)) => (LOWER_HALF_BLOCK, Some(bottom), None),
+            (Some(top), bottom) => (UPPER_HALF_BLOCK, Some(top), bottom),
+        }
+    }
+}
+
+/// 端末を描画先とするバックエンドです。
+pub struct Terminal<W: Write> {
+    out: W,
+    width: u32,
+    height: u32,
+    /// 画面に描画する情報です。
+    pub image: Vec<u32>,
+    /// 前回描画したセルの一覧 (None の場合は全てのセルを描画します)
+    previous: Option<Vec<Cell>>,
+    pub is_hide: bool,
+    /// 出力に失敗したかどうか (失敗した後は `update()` で None を返します)
+    failed: bool,
+
+    /// 透過色
+    pub bg: u32,
+}
+
+impl Terminal<std::io::Stdout> {
+    /// 標準出力に描画するバックエンドを作成します。 (Ctrl-C で終了する際も表示を元に戻します)
+    pub fn stdout(width: u32, height: u32, bg: u32) -> Self {
+        catch_interrupt();
+        Self::new(std::io::stdout(), width, height, bg)
+    }
+}
+
+impl<W: Write> Terminal<W> {
+    /// 指定した出力先に描画するバックエンドを作成します。
+    ///
+    /// ```rust
+    /// use bkrbk::term::Terminal;
+    /// let mut term = Terminal::new(Vec::new(), 1, 2, 0x00_00_00_FF);
+    /// term.show().unwrap();
+    /// term.output_mut().clear();
+    ///
+    /// term.image.copy_from_slice(&[0x00_FF_00_00, 0x00_00_FF_00]);
+    /// term.draw().unwrap();
+    /// assert_eq!(
+    ///     String::from_utf8_lossy(term.output()),
+    ///     "\x1b[1;1H\x1b[38;2;255;0;0m\x1b[48;2;0;255;0m▀\x1b[0m\x1b[2;1H",
+    /// );
+    ///
+    /// // 変化が無い場合はカーソルの移動のみ出力します
+    /// term.output_mut().clear();
+    /// term.draw().unwrap();
+    /// assert_eq!(String::from_utf8_lossy(term.output()), "\x1b[2;1H");
+    ///
+    /// // 出力に失敗した場合は終了させる
+    /// use bkrbk::backend::Backend;
+    /// struct Closed;
+    /// impl std::io::Write for Closed {
+    ///     fn write(&mut self, _: &[u8]) -> std::io::Result<usize> {
+    ///         Err(std::io::ErrorKind::BrokenPipe.into())
+    ///     }
+    ///     fn flush(&mut self) -> std::io::Result<()> {
+    ///         Ok(())
+    ///     }
+    /// }
+    /// let mut term = Terminal::new(Closed, 1, 2, 0x00_00_00_FF);
+    /// assert_eq!(term.update(), Some(false));
+    /// Backend::show(&mut term);
+    /// assert_eq!(term.update(), None);
+    /// ```
+    pub fn new(out: W, width: u32, height: u32, bg: u32) -> Self {
+        Self {
+            out,
+            width,
+            height,
+            image: vec![bg; (width * height) as usize],
+            previous: None,
+            is_hide: true,
+            failed: false,
+            bg,
+        }
+    }
+
+    /// 出力先を取得します。
+    pub fn output(&self) -> &W {
+        &self.out
+    }
+
+    /// 出力先を可変参照で取得します。
+    pub fn output_mut(&mut self) -> &mut W {
+        &mut self.out
+    }
+
+    /// 端末の行数 (縦2画素で1行) を取得します。
+    fn rows(&self) -> u32 {
+        self.height.div_ceil(2)
+    }
+
+    /// 画像をセルの一覧に変換します。
+    fn cells(&self) -> Vec<Cell> {
+        let width = self.width as usize;
+        let pixel = |x: usize, y: usize| {
+            self.image
+                .get(y * width + x)
+                .filter(|&&color| color != self.bg)
+                .copied()
+        };
+        let mut result = Vec::with_capacity(width * self.rows() as usize);
+        for row in 0..self.rows() as usize {
+            for x in 0..width {
+                let top = pixel(x, row * 2);
+                let bottom = match row * 2 + 1 < self.height as usize {
+                    true => pixel(x, row * 2 + 1),
+                    false => None,
+                };
+                result.push(Cell { top, bottom });
+            }
+        }
+        result
+    }
+
+    /// 前回の描画から変化したセルを出力します。
+    pub fn draw(&mut self) -> std::io::Result<()> {
+        if self.is_hide {
+            return Ok(());
+        }
+
+        let cells = self.cells();
+        let width = self.width as usize;
+        let mut buffer = String::new();
+        // 直前に出力したセルの位置と色 (連続するセルではカーソルの移動と色の指定を省略する)
+        let mut cursor: Option<usize> = None;
+        let mut colors: Option<(Option<u32>, Option<u32>)> = None;
+
+        for (i, cell) in cells.iter().enumerate() {
+            if let Some(previous) = &self.previous {
+                if previous[i] == *cell {
+                    continue;
+                }
+            }
+
+            if cursor != Some(i) || i % width == 0 {
+                buffer += &format!("\x1b[{};{}H", i / width + 1, i % width + 1);
+            }
+
+            let (glyph, fg, bg) = cell.glyph();
+            if colors != Some((fg, bg)) {
+                if colors.is_some() {
+                    buffer += "\x1b[0m";
+                }
+                if let Some(fg) = fg {
+                    let fg = Color::new(fg);
+                    buffer += &format!("\x1b[38;2;{};{};{}m", fg.red, fg.green, fg.blue);
+                }
+                if let Some(bg) = bg {
+                    let bg = Color::new(bg);
+                    buffer += &format!("\x1b[48;2;{};{};{}m", bg.red, bg.green, bg.blue);
+                }
+                colors = Some((fg, bg));
+            }
+            buffer.push(glyph);
+            cursor = Some(i + 1);
+        }
+        if colors.is_some() {
+            buffer += "\x1b[0m";
+        }
+
+        // 他の出力で画像が崩れないように、カーソルを画像の下に移動しておく
+        buffer += &format!("\x1b[{};1H", self.rows() + 1);
+
+        self.out.write_all(buffer.as_bytes())?;
+        self.out.flush()?;
+        self.previous = Some(cells);
+        Ok(())
+    }
+
+    /// 画面を消去してから描画します。
+    pub fn show(&mut self) -> std::io::Result<()> {
+        // カーソルを隠して画面を消去する
+        self.out.write_all(b"\x1b[?25l\x1b[2J")?;
+        self.previous = None;
+        self.is_hide = false;
+        self.draw()
+    }
+
+    /// 描画した範囲を消去します。
+    pub fn hide(&mut self) -> std::io::Result<()> {
+        self.out.write_all(b"\x1b[0m\x1b[2J\x1b[1;1H\x1b[?25h")?;
+        self.out.flush()?;
+        self.is_hide = true;
+        Ok(())
+    }
+
+    /// 出力に失敗した場合はエラーを表示し、次の `update()` でアプリケーションを終了させます。
+    fn check(&mut self, result: std::io::Result<()>) {
+        if let Err(e) = result {
+            eprintln!("{}", e);
+            self.failed = true;
+        }
+    }
+}
+
+impl<W: Write> Drop for Terminal<W> {
+    /// 描画した範囲を消去し、文字の色とカーソルの表示を元に戻します。
+    fn drop(&mut self) {
+        if !self.is_hide && !self.failed {
+            let _ = self.hide();
+        }
+        let _ = self.out.write_all(b"\x1b[0m\x1b[?25h");
+        let _ = self.out.flush();
+    }
+}
+
+impl<W: Write> crate::backend::Backend for Terminal<W> {
+    fn update(&mut self) -> Option<bool> {
+        match self.failed || interrupted() {
+            true => None,
+            false => Some(false),
+        }
+    }
+
+    fn image(&mut self) -> &mut [u32] {
+        &mut self.image
+    }
+
+    fn draw(&mut self) {
+        let result = Terminal::draw(self);
+        self.check(result);
+    }
+
+    fn show(&mut self) {
+        let result = Terminal::show(self);
+        self.check(result);
+    }
+
+    fn hide(&mut self) {
+        let result = Terminal::hide(self);
+        self.check(result);
+    }
+
+    fn size(&self) -> (u32, u32) {
+        (self.width, self.height)
+    }
+}
@@ -0,0 +1,207 @@
+//! Sixel 形式で画像を出力するための機能です。
+
+use std::collections::HashMap;
+
+use crate::color::Color;
+
+/// Sixel で利用できる色の最大数です。
+const MAX_COLORS: usize = 256;
+
+/// 画像をパレット番号の一覧に変換するための構造体です。
+///
+/// GIF の画像は元々256色以下のパレットで表現されているので、通常はそのままの色を利用します。
+/// 複数のパレットを重ねた結果256色を超えた場合は、各色の下位ビットを切り捨てて減色します。
+pub struct Palette {
+    /// パレットの色
+    pub colors: Vec<u32>,
+    /// 画素ごとのパレット番号 (透過色の場合は None)
+    pub indices: Vec<Option<u8>>,
+}
+
+impl Palette {
+    /// 画像からパレットを作成します。
+    ///
+    /// ```rust
+    /// use bkrbk::term::sixel::Palette;
+    /// let image = [0x00_FF_00_00, 0x00_00_00_FF, 0x00_FF_00_00];
+    /// let palette = Palette::new(&image, 0x00_00_00_FF);
+    /// assert_eq!(palette.colors, vec![0x00_FF_00_00]);
+    /// assert_eq!(palette.indices, vec![Some(0), None, Some(0)]);
+    ///
+    /// // 256色を超える場合は減色します
+    /// let image: Vec<u32> = (0..1024).map(|i| i * 0x1_01_01).collect();
+    /// let palette = Palette::new(&image, 0x00_00_00_FF);
+    /// assert!(palette.colors.len() <= 256);
+    /// ```
+    pub fn new(image: &[u32], bg: u32) -> Self {
+        Self::with_colors(image, bg, &[])
+    }
+
+    /// GIF のパレットの色をその順番のまま利用してパレットを作成します。
+    ///
+    /// パレットに無い色は後ろに追加し、256色を超える場合は減色します。
+    ///
+    /// ```rust
+    /// use bkrbk::term::sixel::Palette;
+    /// let image = [0x00_FF_00_00, 0x00_00_00_FF, 0x00_00_FF_00];
+    /// let palette = Palette::with_colors(&image, 0x00_00_00_FF, &[0x00_00_FF_00, 0x00_FF_00_00, 0x00_FF_00_00]);
+    /// assert_eq!(palette.colors, vec![0x00_00_FF_00, 0x00_FF_00_00]);
+    /// assert_eq!(palette.indices, vec![Some(1), None, Some(0)]);
+    /// ```
+    pub fn with_colors(image: &[u32], bg: u32, colors: &[u32]) -> Self {
+        let mut result = Self {
+            colors: Vec::new(),
+            indices: Vec::with_capacity(image.len()),
+        };
+        let mut lookup: HashMap<u32, u8> = HashMap::new();
+        for &color in colors
+            .iter()
+            .chain(image.iter().filter(|&&pixel| pixel != bg))
+        {
+            if lookup.contains_key(&color) {
+                continue;
+            }
+            if result.colors.len() == MAX_COLORS {
+                return Self::quantize(image, bg);
+            }
+            lookup.insert(color, result.colors.len() as u8);
+            result.colors.push(color);
+        }
+        result.indices = image
+            .iter()
+            .map(|pixel| match *pixel == bg {
+                true => None,
+                false => Some(lookup[pixel]),
+            })
+            .collect();
+        result
+    }
+
+    /// 下位ビットを切り捨てて256色以下に減色したパレットを作成します。
+    fn quantize(image: &[u32], bg: u32) -> Self {
+        // 256色に収まるまで下位ビットを切り捨てる
+        for shift in 0..8 {
+            let mask = Self::channel_mask(shift);
+            let mut colors: Vec<u32> = Vec::new();
+            let mut indices = Vec::with_capacity(image.len());
+            let mut overflow = false;
+            for &pixel in image {
+                if pixel == bg {
+                    indices.push(None);
+                    continue;
+                }
+                let color = pixel & mask;
+                let index = match colors.iter().position(|&c| c == color) {
+                    Some(index) => index,
+                    None if colors.len() < MAX_COLORS => {
+                        colors.push(color);
+                        colors.len() - 1
+                    }
+                    None => {
+                        overflow = true;
+                        break;
+                    }
+                };
+                indices.push(Some(index as u8));
+            }
+            if !overflow {
+                return Self { colors, indices };
+            }
+        }
+        unreachable!("下位7bitを切り捨てると8色以下になる")
+    }
+
+    /// 各色の下位 `shift` ビットを切り捨てるためのマスクを取得します。
+    fn channel_mask(shift: u32) -> u32 {
+        let channel = (0xFF << shift) & 0xFF;
+        (channel << 16) | (channel << 8) | channel
+    }
+}
+
+/// 画像を Sixel 形式のエスケープシーケンスに変換します。
+///
+/// 透過色の画素は何も描画しない (端末の背景が見える) ようにします。
+/// ```rust
+/// let image = [0x00_FF_00_00, 0x00_00_00_FF];
+/// let sixel = bkrbk::term::sixel::encode(&image, 1, 2, 0x00_00_00_FF);
+/// assert_eq!(
+///     String::from_utf8(sixel).unwrap(),
+///     "\x1bP0;1;0q\"1;1;1;2#0;2;100;0;0#0@-\x1b\\",
+/// );
+/// ```
+pub fn encode(image: &[u32], width: usize, height: usize, bg: u32) -> Vec<u8> {
+    encode_palette(&Palette::new(image, bg), width, height, false)
+}
+
+/// パレット番号に変換済みの画像を Sixel 形式のエスケープシーケンスに変換します。
+///
+/// `fill` が true の場合は、透過色の画素を端末の背景色で塗りつぶします。 (前に描画した画像を消すために利用します)
+pub fn encode_palette(palette: &Palette, width: usize, height: usize, fill: bool) -> Vec<u8> {
+    // P2=1 で値が0の画素を透過させ、 P2=0 で背景色で塗りつぶす
+    let mut result = format!("\x1bP0;{};0q", if fill { 0 } else { 1 });
+    result += &format!("\"1;1;{};{}", width, height);
+    for (i, &color) in palette.colors.iter().enumerate() {
+        let color = Color::new(color);
+        let percent = |value: u8| (value as u32 * 100 + 127) / 255;
+        result += &format!(
+            "#{};2;{};{};{}",
+            i,
+            percent(color.red),
+            percent(color.green),
+            percent(color.blue)
+        );
+    }
+
+    // 6行ごとの帯に分けて出力する
+    for top in (0..height).step_by(6) {
+        let rows = (height - top).min(6);
+        let mut first = true;
+        for color in 0..palette.colors.len() {
+            // 帯の中の列ごとのビットパターン
+            let bits: Vec<u8> = (0..width)
+                .map(|x| {
+                    (0..rows).fold(0, |bits, dy| {
+                        match palette.indices[(top + dy) * width + x] == Some(color as u8) {
+                            true => bits | (1 << dy),
+                            false => bits,
+                        }
+                    })
+                })
+                .collect();
+            if bits.iter().all(|&b| b == 0) {
+                continue;
+            }
+
+            if !first {
+                // 同じ帯の先頭に戻る
+                result.push('$');
+            }
+            first = false;
+            result += &format!("#{}", color);
+            push_run_length(&mut result, &bits);
+        }
+        result.push('-');
+    }
+
+    result += "\x1b\\";
+    result.into_bytes()
+}
+
+/// ビットパターンの一覧を Sixel の文字に変換し、連続する文字はランレングス圧縮して追加します。
+fn push_run_length(result: &mut String, bits: &[u8]) {
+    // 末尾の空白は出力しない
+    let length = bits.iter().rposition(|&b| b != 0).map_or(0, |i| i + 1);
+    let mut i = 0;
+    while i < length {
+        let count = bits[i..length]
+            .iter()
+            .take_while(|&&b| b == bits[i])
+            .count();
+        let c = (bits[i] + 0x3F) as char;
+        match count {
+            1..=3 => (0..count).for_each(|_| result.push(c)),
+            _ => *result += &format!("!{}{}", count, c),
+        }
+        i += count;
+    }
+}
@@ -0,0 +1,19 @@
+//! アニメーションのフレームの表示時間と、処理が遅れた場合の扱いを定めるための機能です。
+//!
+//! フレームの切り替え時刻は `player::Player` が再生開始からの経過時間で計算します。
+
+use std::time::Duration;
+
+/// 表示時間が0のフレームの代わりに利用する表示時間です。
+///
+/// 多くのブラウザと同様に、表示時間が0のフレームは 100 ミリ秒表示します。
+pub const DEFAULT_DELAY: Duration = Duration::from_millis(100);
+
+/// 処理が遅れて次のフレームの時刻を過ぎてしまった場合の扱いです。
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LatePolicy {
+    /// 現在時刻に表示すべきフレームまで飛ばします。
+    Skip,
+    /// 1フレームずつ順番に進めます。 (待機せずに追いつきます)
+    CatchUp,
+}
@@ -0,0 +1,136 @@
+//! 現在時刻の取得と待機を差し替えるための機能です。
+//!
+//! 通常は `SystemClock` を利用し、テストでは手動で時刻を進める `MockClock` を利用することで、
+//! 実際に待機することなく時間に依存する処理を確認できます。
+
+use std::{
+    sync::{Arc, Mutex},
+    time::{Duration, Instant, SystemTime},
+};
+
+/// 時刻を扱うためのトレイトです。
+pub trait Clock {
+    /// 現在時刻を取得します。
+    fn now(&self) -> Instant;
+
+    /// 現在の日時を取得します。
+    ///
+    /// 時刻 ( `now` ) と異なり、OS の時計の変更などによって前後することがあります。
+    fn system_time(&self) -> SystemTime {
+        SystemTime::now()
+    }
+
+    /// 指定した時間分待機します。
+    fn sleep(&self, duration: Duration);
+
+    /// ビジーループで待機する際に、1回のループごとに呼び出されます。
+    fn spin(&self) {
+        std::hint::spin_loop();
+    }
+
+    /// 他のスレッドに処理を譲りながら待機する際に、1回のループごとに呼び出されます。
+    fn yield_now(&self) {
+        std::thread::yield_now();
+    }
+}
+
+/// OS の時刻を利用する時計です。
+#[derive(Debug, Clone, Copy, Default)]
+pub struct SystemClock;
+
+impl Clock for SystemClock {
+    fn now(&self) -> Instant {
+        Instant::now()
+    }
+
+    fn sleep(&self, duration: Duration) {
+        super::sleep(duration);
+    }
+}
+
+/// 手動で時刻を進める時計です。
+///
+/// 複製した時計は同じ時刻を共有します。待機した場合は待機した時間分だけ時刻が進みます。
+/// 日時 ( `system_time` ) も時刻と同じだけ進みます。
+/// ```rust
+/// use bkrbk::timer::{Clock, MockClock};
+/// use std::time::Duration;
+///
+/// let clock = MockClock::new();
+/// let start = clock.now();
+/// let shared = clock.clone();
+/// clock.advance(Duration::from_millis(10));
+/// shared.sleep(Duration::from_millis(5));
+/// assert_eq!(clock.now() - start, Duration::from_millis(15));
+/// ```
+#[derive(Debug, Clone)]
+pub struct MockClock {
+    now: Arc<Mutex<(Instant, SystemTime)>>,
+}
+
+impl Default for MockClock {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl MockClock {
+    /// ビジーループ1回ごとに進める時間です。
+    pub const SPIN_STEP: Duration = Duration::from_micros(1);
+
+    /// 現在時刻から始まる時計を作成します。
+    pub fn new() -> Self {
+        Self::starting_at(Instant::now())
+    }
+
+    /// 指定した時刻から始まる時計を作成します。
+    pub fn starting_at(now: Instant) -> Self {
+        Self {
+            now: Arc::new(Mutex::new((now, SystemTime::now()))),
+        }
+    }
+
+    /// 時刻を指定した時間分進めます。
+    pub fn advance(&self, duration: Duration) {
+        let mut now = self.now.lock().unwrap();
+        now.0 += duration;
+        now.1 += duration;
+    }
+
+    /// 時刻を指定した時刻に設定します。 (日時も同じだけ前後します)
+    pub fn set(&self, instant: Instant) {
+        let mut now = self.now.lock().unwrap();
+        now.1 = match instant >= now.0 {
+            true => now.1 + (instant - now.0),
+            false => now.1 - (now.0 - instant),
+        };
+        now.0 = instant;
+    }
+
+    /// 日時を指定した日時に設定します。 (時刻は変わりません)
+    pub fn set_system_time(&self, time: SystemTime) {
+        self.now.lock().unwrap().1 = time;
+    }
+}
+
+impl Clock for MockClock {
+    fn now(&self) -> Instant {
+        self.now.lock().unwrap().0
+    }
+
+    fn system_time(&self) -> SystemTime {
+        self.now.lock().unwrap().1
+    }
+
+    fn sleep(&self, duration: Duration) {
+        self.advance(duration);
+    }
+
+    fn spin(&self) {
+        self.advance(Self::SPIN_STEP);
+    }
+
+    fn yield_now(&self) {
+        self.advance(Self::SPIN_STEP);
+    }
+}
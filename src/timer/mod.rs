@@ -0,0 +1,156 @@
+//! 処理間隔をハンドリングするための機能です。
+
+use std::time::{Duration, Instant};
+
+mod animation;
+mod clock;
+mod pacer;
+mod schedule;
+mod stats;
+mod time;
+pub use animation::{LatePolicy, DEFAULT_DELAY};
+pub use clock::{Clock, MockClock, SystemClock};
+pub use pacer::{Jitter, Pacer, Strategy};
+pub use schedule::{parse_duration, Cron, CronError, Scheduler, Trigger, MAX_DURATION};
+pub use stats::{FpsPrinter, FrameRecord, FrameStats, StatsSink, Summary};
+pub use time::{DateTime, ParseError, Time, TimeZone};
+
+/// タイマーを扱うための構造体です。
+///
+/// 時刻の取得と待機は `Clock` を通して行います。
+pub struct Timer<C: Clock = SystemClock> {
+    /// 待機時間
+    pub interval: Duration,
+
+    /// 構造体の初期化を行った時刻
+    initialize_time: Instant,
+
+    /// 最後に更新を行った時刻
+    last_updated: Instant,
+
+    /// 更新間隔の実測値の統計
+    pub stats: FrameStats,
+
+    /// 時刻の取得と待機に利用する時計
+    clock: C,
+}
+
+impl Default for Timer {
+    fn default() -> Self {
+        Self::with_clock(Self::DEFAULT_FPS, SystemClock)
+    }
+}
+
+impl Timer {
+    /// デフォルトの画面の更新間隔です。
+    const DEFAULT_FPS: u32 = 30;
+
+    /// Timer 構造体を初期化して返します。
+    pub fn new(fps: u32) -> Self {
+        Self::with_clock(fps, SystemClock)
+    }
+}
+
+impl<C: Clock> Timer<C> {
+    /// 指定した時計を利用する Timer 構造体を初期化して返します。
+    pub fn with_clock(fps: u32, clock: C) -> Self {
+        let now = clock.now();
+        Self {
+            interval: Duration::from_secs(1) / fps,
+            initialize_time: now,
+            last_updated: now,
+            stats: FrameStats::default(),
+            clock,
+        }
+    }
+
+    /// 更新時間まで待機します。
+    /// ```rust
+    /// // 30fps
+    /// let fps = 30;
+    /// use bkrbk::timer::{Clock, MockClock, Timer};
+    /// let clock = MockClock::new();
+    /// let mut t = Timer::with_clock(fps, clock.clone());
+    ///
+    /// // 1秒間にcounterを30回更新する
+    /// use std::time::Duration;
+    /// let mut counter = 0;
+    /// let before = clock.now();
+    /// loop {
+    ///     // 1回の処理に10ミリ秒かかるとする
+    ///     clock.advance(Duration::from_millis(10));
+    ///     if clock.now() - before >= Duration::from_secs(1) {
+    ///         break;
+    ///     }
+    ///     counter += 1;
+    ///     t.sleep();
+    /// }
+    /// assert_eq!(counter, fps);
+    /// assert_eq!(t.stats.summary().dropped, 0);
+    /// ```
+    pub fn sleep(&mut self) {
+        // 前回の更新終了時刻から現在時刻を引いた差分の時間を取得
+        let now = self.clock.now();
+        let elapsed = now.saturating_duration_since(self.last_updated);
+
+        // 待機時間を取得 (1ミリ秒未満の間隔も扱えるようにナノ秒単位で計算する)
+        let count = elapsed
+            .as_nanos()
+            .checked_div(self.interval.as_nanos())
+            .unwrap_or(0);
+        let min_elapsed = elapsed.saturating_sub(self.interval * count as u32);
+        let wait_time = self.interval.saturating_sub(min_elapsed);
+
+        // 待機
+        self.clock.sleep(wait_time);
+
+        // 更新時間（予測）の更新
+        self.last_updated = now + wait_time;
+
+        // 統計の記録 (待機時間の計算で読み飛ばした間隔の数を飛ばしたフレームとする)
+        self.stats
+            .frame(self.last_updated, Duration::ZERO, count as usize);
+    }
+
+    /// 統計情報を表示します。
+    pub fn print(&self) {
+        println!("{}", self.stats.summary());
+    }
+
+    /// 初期化時点から経過した秒数を取得します。
+    pub fn get_operating_time(&self) -> u64 {
+        self.clock
+            .now()
+            .saturating_duration_since(self.initialize_time)
+            .as_secs()
+    }
+}
+
+/// 指定した時間分 Sleep します。
+///
+/// NOTE: OS が Windows の場合は、デフォルトの Sleep の精度が悪いため、タイマーの解像度を一時的に上げる処理が入ります。
+/// ただし、 `Windows 10, version 2004` よりも前のバージョンでは「全てのシステムに」影響が出てしまうことに注意してください。
+#[inline(always)]
+pub fn sleep(wait_time: Duration) {
+    #[cfg(target_os = "windows")]
+    unsafe {
+        /// 最小タイマー解像度 (ミリ秒単位)
+        const MINIMUM_TIMER_RESOLUTION: u32 = 1;
+        windows::Win32::Media::timeBeginPeriod(MINIMUM_TIMER_RESOLUTION);
+        std::thread::sleep(wait_time);
+        windows::Win32::Media::timeEndPeriod(MINIMUM_TIMER_RESOLUTION);
+    }
+
+    #[cfg(not(target_os = "windows"))]
+    std::thread::sleep(wait_time);
+}
+
+/// 指定した時刻まで Sleep します。
+///
+/// 既に指定した時刻を過ぎている場合は何もしません。
+pub fn sleep_until(deadline: Instant) {
+    let wait_time = deadline.saturating_duration_since(Instant::now());
+    if !wait_time.is_zero() {
+        sleep(wait_time);
+    }
+}
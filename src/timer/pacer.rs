@@ -0,0 +1,200 @@
+//! 揺らぎ (ジッター) の少ない待機を行うための機能です。
+
+use std::time::{Duration, Instant};
+
+use super::{Clock, SystemClock};
+
+/// デフォルトの許容誤差です。
+///
+/// OS のスリープは数ミリ秒程度遅れることがあるので、この時間分は Sleep 以外の方法で待機します。
+pub const DEFAULT_TOLERANCE: Duration = Duration::from_millis(2);
+
+/// 待機の方法です。
+///
+/// 常駐させる場合は CPU を使い続けないように `Sleep` (デフォルト) を利用してください。
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Strategy {
+    /// OS のスリープのみで待機します。 (CPU 負荷は最も低いですが、誤差が大きくなります)
+    #[default]
+    Sleep,
+    /// 目標時刻の `tolerance` 前まで Sleep し、残りはビジーループで待機します。 (誤差の計測などに利用します)
+    Spin { tolerance: Duration },
+    /// 目標時刻の `tolerance` 前まで Sleep し、残りは他のスレッドに処理を譲りながら待機します。
+    Yield { tolerance: Duration },
+}
+
+impl std::str::FromStr for Strategy {
+    type Err = String;
+
+    /// `sleep`, `spin`, `yield` のいずれかを受け付けます。許容誤差はデフォルトの値を利用します。
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "sleep" => Ok(Self::Sleep),
+            "spin" => Ok(Self::Spin {
+                tolerance: DEFAULT_TOLERANCE,
+            }),
+            "yield" => Ok(Self::Yield {
+                tolerance: DEFAULT_TOLERANCE,
+            }),
+            _ => Err(format!("unknown pacing strategy: {}", s)),
+        }
+    }
+}
+
+/// 目標時刻からの遅れ (ジッター) の計測結果です。
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct Jitter {
+    /// 計測回数
+    pub samples: u64,
+    /// 遅れの平均
+    pub mean: Duration,
+    /// 遅れの最小値
+    pub min: Duration,
+    /// 遅れの最大値
+    pub max: Duration,
+    /// 遅れの標準偏差
+    pub std_dev: Duration,
+}
+
+impl std::fmt::Display for Jitter {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "samples: {}, mean: {:?}, min: {:?}, max: {:?}, std_dev: {:?}",
+            self.samples, self.mean, self.min, self.max, self.std_dev
+        )
+    }
+}
+
+/// 指定した時刻まで待機し、その誤差を記録するための構造体です。
+///
+/// ```rust
+/// use bkrbk::timer::{Clock, MockClock, Pacer, Strategy};
+/// use std::time::Duration;
+///
+/// let clock = MockClock::new();
+/// let mut pacer = Pacer::with_clock(Strategy::default(), clock.clone());
+/// let deadline = clock.now() + Duration::from_millis(5);
+/// pacer.wait_until(deadline);
+/// assert_eq!(clock.now(), deadline);
+/// assert_eq!(pacer.jitter().samples, 1);
+///
+/// // 既に過ぎた時刻は待機せずに遅れとして記録する
+/// clock.advance(Duration::from_millis(3));
+/// assert_eq!(pacer.wait_until(deadline), Duration::from_millis(3));
+/// assert_eq!(pacer.jitter().samples, 2);
+/// assert_eq!(pacer.jitter().max, Duration::from_millis(3));
+/// ```
+pub struct Pacer<C: Clock = SystemClock> {
+    /// 待機の方法
+    pub strategy: Strategy,
+    samples: u64,
+    /// 遅れの合計 (ナノ秒)
+    sum: f64,
+    /// 遅れの二乗の合計 (ナノ秒)
+    sum_sq: f64,
+    min: Duration,
+    max: Duration,
+    /// 時刻の取得と待機に利用する時計
+    clock: C,
+}
+
+impl Pacer {
+    /// Pacer 構造体を初期化して返します。
+    pub fn new(strategy: Strategy) -> Self {
+        Self::with_clock(strategy, SystemClock)
+    }
+}
+
+impl<C: Clock> Pacer<C> {
+    /// 指定した時計を利用する Pacer 構造体を初期化して返します。
+    pub fn with_clock(strategy: Strategy, clock: C) -> Self {
+        Self {
+            strategy,
+            samples: 0,
+            sum: 0.0,
+            sum_sq: 0.0,
+            min: Duration::MAX,
+            max: Duration::ZERO,
+            clock,
+        }
+    }
+
+    /// 指定した時刻まで待機し、目標時刻からの遅れを返します。
+    ///
+    /// 既に指定した時刻を過ぎている場合は待機せず、過ぎた分を遅れとして記録します。
+    pub fn wait_until(&mut self, deadline: Instant) -> Duration {
+        let now = self.clock.now();
+        if now >= deadline {
+            let late = now - deadline;
+            self.record(late);
+            return late;
+        }
+
+        match self.strategy {
+            Strategy::Sleep => self.clock.sleep(deadline - now),
+            Strategy::Spin { tolerance } => {
+                self.coarse_sleep(deadline, tolerance);
+                while self.clock.now() < deadline {
+                    self.clock.spin();
+                }
+            }
+            Strategy::Yield { tolerance } => {
+                self.coarse_sleep(deadline, tolerance);
+                while self.clock.now() < deadline {
+                    self.clock.yield_now();
+                }
+            }
+        }
+
+        let late = self.clock.now().saturating_duration_since(deadline);
+        self.record(late);
+        late
+    }
+
+    /// 目標時刻の `tolerance` 前まで Sleep します。
+    fn coarse_sleep(&self, deadline: Instant, tolerance: Duration) {
+        let wait_time = deadline
+            .saturating_duration_since(self.clock.now())
+            .saturating_sub(tolerance);
+        if !wait_time.is_zero() {
+            self.clock.sleep(wait_time);
+        }
+    }
+
+    /// 遅れを記録します。
+    fn record(&mut self, late: Duration) {
+        let nanos = late.as_nanos() as f64;
+        self.samples += 1;
+        self.sum += nanos;
+        self.sum_sq += nanos * nanos;
+        self.min = self.min.min(late);
+        self.max = self.max.max(late);
+    }
+
+    /// これまでの計測結果を取得します。
+    pub fn jitter(&self) -> Jitter {
+        if self.samples == 0 {
+            return Jitter::default();
+        }
+        let n = self.samples as f64;
+        let mean = self.sum / n;
+        let variance = (self.sum_sq / n - mean * mean).max(0.0);
+        Jitter {
+            samples: self.samples,
+            mean: Duration::from_nanos(mean as u64),
+            min: self.min,
+            max: self.max,
+            std_dev: Duration::from_nanos(variance.sqrt() as u64),
+        }
+    }
+
+    /// 計測結果を初期化します。
+    pub fn reset(&mut self) {
+        self.samples = 0;
+        self.sum = 0.0;
+        self.sum_sq = 0.0;
+        self.min = Duration::MAX;
+        self.max = Duration::ZERO;
+    }
+}
@@ -0,0 +1,419 @@
+//! 決まった日時や間隔でイベントを発生させるための機能です。
+
+use std::time::{Duration, Instant};
+
+use super::{time::MILLIS_PER_DAY, Clock, DateTime, SystemClock, Time};
+use crate::random::Rng;
+
+/// `parse_duration` で受け付ける時間の上限 (10年) です。
+///
+/// 現在時刻に足しても桁あふれしないように制限します。
+pub const MAX_DURATION: Duration = Duration::from_secs(10 * 365 * 24 * 60 * 60);
+
+/// `30s` や `45m` のような単位付きの時間を解析します。
+///
+/// 単位は `ms` , `s` , `m` , `h` に対応し、 `1h30m` のように組み合わせることもできます。
+/// `MAX_DURATION` を超える場合は None を返します。
+///
+/// ```rust
+/// use bkrbk::timer::parse_duration;
+/// use std::time::Duration;
+///
+/// assert_eq!(parse_duration("45m"), Some(Duration::from_secs(45 * 60)));
+/// assert_eq!(parse_duration("1h30m"), Some(Duration::from_secs(90 * 60)));
+/// assert_eq!(parse_duration("250ms"), Some(Duration::from_millis(250)));
+/// assert_eq!(parse_duration("10"), None);
+/// assert_eq!(parse_duration("99999999999999999h"), None);
+/// assert_eq!(parse_duration("87600h1s"), None);
+/// ```
+pub fn parse_duration(s: &str) -> Option<Duration> {
+    let mut total = Duration::ZERO;
+    let mut rest = s.trim();
+    if rest.is_empty() {
+        return None;
+    }
+    while !rest.is_empty() {
+        let digits = rest
+            .find(|c: char| !c.is_ascii_digit())
+            .unwrap_or(rest.len());
+        let value: u64 = rest[..digits].parse().ok()?;
+        rest = &rest[digits..];
+        let unit = rest
+            .find(|c: char| c.is_ascii_digit())
+            .unwrap_or(rest.len());
+        let duration = match &rest[..unit] {
+            "ms" => Duration::from_millis(value),
+            "s" => Duration::from_secs(value),
+            "m" => Duration::from_secs(value.checked_mul(60)?),
+            "h" => Duration::from_secs(value.checked_mul(60 * 60)?),
+            _ => return None,
+        };
+        total = total.checked_add(duration)?;
+        rest = &rest[unit..];
+    }
+    (total <= MAX_DURATION).then_some(total)
+}
+
+/// cron 形式の解析に失敗した際のエラーです。
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CronError(pub String);
+
+impl std::fmt::Display for CronError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "invalid cron expression: {}", self.0)
+    }
+}
+
+impl std::error::Error for CronError {}
+
+/// cron 形式 (分 時 日 月 曜日) で指定した日時を扱うための構造体です。
+///
+/// 各項目は `*` , `5` , `1-5` , `*/15` , `0-30/10` とそれらを `,` で区切った一覧に対応します。
+/// 曜日は日曜日が `0` ( `7` も日曜日) です。
+/// 日と曜日を両方指定した場合は、どちらかに一致する日を対象とします。
+/// `@hourly` , `@daily` , `@weekly` , `@monthly` , `@yearly` も利用できます。
+///
+/// ```rust
+/// use bkrbk::timer::{Cron, Time};
+///
+/// // 平日の 9時から17時まで 30分ごと
+/// let cron: Cron = "*/30 9-17 * * 1-5".parse().unwrap();
+/// let now = Time::parse_iso_8601("2024-06-07T17:45:00+09:00").unwrap(); // 金曜日
+/// let next = cron.next_after(&now).unwrap();
+/// assert_eq!(next.format_iso_8601(), "2024-06-10T09:00:00.000+09:00");
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Cron {
+    minutes: u64,
+    hours: u64,
+    days: u64,
+    months: u64,
+    weekdays: u64,
+    /// 日が `*` かどうか
+    any_day: bool,
+    /// 曜日が `*` かどうか
+    any_weekday: bool,
+}
+
+impl Cron {
+    /// 次の日時を探す最大の日数です。 (2月29日の指定などを考慮して8年分)
+    const SEARCH_DAYS: usize = 366 * 8;
+
+    /// 1つの項目を解析し、一致する値のビットを立てた値と `*` かどうかを返します。
+    fn parse_field(field: &str, min: u64, max: u64) -> Result<(u64, bool), CronError> {
+        let error = || CronError(field.to_string());
+        let mut mask = 0u64;
+        for part in field.split(',') {
+            let (range, step) = match part.split_once('/') {
+                Some((range, step)) => (range, step.parse::<u64>().map_err(|_| error())?),
+                None => (part, 1),
+            };
+            let (low, high) = match range {
+                "*" => (min, max),
+                _ => match range.split_once('-') {
+                    Some((low, high)) => (
+                        low.parse().map_err(|_| error())?,
+                        high.parse().map_err(|_| error())?,
+                    ),
+                    None => {
+                        let value = range.parse().map_err(|_| error())?;
+                        // `5/15` は `5-最大値/15` とみなす
+                        (value, if part.contains('/') { max } else { value })
+                    }
+                },
+            };
+            if step == 0 || low < min || high > max || low > high {
+                return Err(error());
+            }
+            for value in (low..=high).step_by(step as usize) {
+                mask |= 1 << value;
+            }
+        }
+        Ok((mask, field == "*"))
+    }
+
+    /// 指定した日時より後で、最初に一致する日時を取得します。 (秒以下は 0 になります)
+    ///
+    /// 日時は `time` と同じタイムゾーンで計算します。一致する日時が無い場合は None を返します。
+    pub fn next_after(&self, time: &Time) -> Option<Time> {
+        let timezone = time.timezone;
+        // 次の分の始めから探す
+        let mut datetime = time.datetime;
+        datetime.second = 0;
+        datetime.milli_seconds = 0;
+        let mut datetime = DateTime::from_unix_millis(datetime.to_unix_millis() + 60_000);
+
+        for _ in 0..Self::SEARCH_DAYS {
+            if self.matches_date(&datetime) {
+                for hour in datetime.hour..24 {
+                    if self.hours & (1 << hour) == 0 {
+                        continue;
+                    }
+                    let first = if hour == datetime.hour {
+                        datetime.minute
+                    } else {
+                        0
+                    };
+                    if let Some(minute) = (first..60).find(|m| self.minutes & (1 << m) != 0) {
+                        datetime.hour = hour;
+                        datetime.minute = minute;
+                        return Some(Time { datetime, timezone });
+                    }
+                }
+            }
+            // 翌日の 0時0分
+            let day = datetime.to_unix_millis().div_euclid(MILLIS_PER_DAY) + 1;
+            datetime = DateTime::from_unix_millis(day * MILLIS_PER_DAY);
+        }
+        None
+    }
+
+    /// 日付が一致するかどうかを判定します。
+    fn matches_date(&self, datetime: &DateTime) -> bool {
+        if self.months & (1 << datetime.month) == 0 {
+            return false;
+        }
+        let day = self.days & (1 << datetime.day) != 0;
+        let weekday = self.weekdays & (1 << datetime.weekday()) != 0;
+        match (self.any_day, self.any_weekday) {
+            (true, true) => true,
+            (false, true) => day,
+            (true, false) => weekday,
+            (false, false) => day || weekday,
+        }
+    }
+}
+
+impl std::str::FromStr for Cron {
+    type Err = CronError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let s = match s.trim() {
+            "@hourly" => "0 * * * *",
+            "@daily" => "0 0 * * *",
+            "@weekly" => "0 0 * * 0",
+            "@monthly" => "0 0 1 * *",
+            "@yearly" => "0 0 1 1 *",
+            s => s,
+        };
+        let fields: Vec<&str> = s.split_whitespace().collect();
+        let [minute, hour, day, month, weekday] = fields[..] else {
+            return Err(CronError(s.to_string()));
+        };
+        let (minutes, _) = Self::parse_field(minute, 0, 59)?;
+        let (hours, _) = Self::parse_field(hour, 0, 23)?;
+        let (days, any_day) = Self::parse_field(day, 1, 31)?;
+        let (months, _) = Self::parse_field(month, 1, 12)?;
+        let (mut weekdays, any_weekday) = Self::parse_field(weekday, 0, 7)?;
+        // 7 は日曜日として扱う
+        if weekdays & (1 << 7) != 0 {
+            weekdays |= 1;
+        }
+        Ok(Self {
+            minutes,
+            hours,
+            days,
+            months,
+            weekdays,
+            any_day,
+            any_weekday,
+        })
+    }
+}
+
+/// イベントを発生させる条件です。
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Trigger {
+    /// cron 形式で指定した日時 (ローカル時刻)
+    Cron(Cron),
+    /// 一定の間隔
+    Interval(Duration),
+    /// `min` から `max` の間のランダムな間隔
+    Random { min: Duration, max: Duration },
+}
+
+impl std::str::FromStr for Trigger {
+    type Err = String;
+
+    /// `cron:<分 時 日 月 曜日>` , `every:<時間>` , `random:<最短>-<最長>` のいずれかを受け付けます。
+    ///
+    /// ```rust
+    /// use bkrbk::timer::Trigger;
+    /// use std::time::Duration;
+    ///
+    /// let trigger: Trigger = "random:20m-40m".parse().unwrap();
+    /// let (min, max) = (Duration::from_secs(20 * 60), Duration::from_secs(40 * 60));
+    /// assert_eq!(trigger, Trigger::Random { min, max });
+    /// assert!("cron:@daily".parse::<Trigger>().is_ok());
+    /// ```
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let error = || format!("unknown trigger: {}", s);
+        let (kind, value) = s.split_once(':').ok_or_else(error)?;
+        match kind {
+            "cron" => value.parse().map(Self::Cron).map_err(|e| e.to_string()),
+            "every" => parse_duration(value)
+                .filter(|d| !d.is_zero())
+                .map(Self::Interval)
+                .ok_or_else(error),
+            "random" => {
+                let (min, max) = value.split_once('-').ok_or_else(error)?;
+                match (parse_duration(min), parse_duration(max)) {
+                    (Some(min), Some(max)) if min <= max && !max.is_zero() => {
+                        Ok(Self::Random { min, max })
+                    }
+                    _ => Err(error()),
+                }
+            }
+            _ => Err(error()),
+        }
+    }
+}
+
+/// 登録したイベントです。
+struct Entry<E> {
+    id: usize,
+    trigger: Trigger,
+    event: E,
+    /// 次にイベントを発生させる時刻
+    next: Option<Instant>,
+    /// 次にイベントを発生させる日時 ( `Trigger::Cron` の場合のみ)
+    next_time: Option<Time>,
+}
+
+/// 登録した条件に従ってイベントを発生させるための構造体です。
+///
+/// 時刻は `Clock` から取得するので、 `MockClock` を利用すると待たずに動作を確認できます。
+///
+/// ```rust
+/// use bkrbk::timer::{Clock, MockClock, Scheduler, Trigger};
+/// use std::time::Duration;
+///
+/// let clock = MockClock::new();
+/// let mut scheduler = Scheduler::with_clock(clock.clone(), 1);
+/// scheduler.add(Trigger::Interval(Duration::from_secs(60)), "every minute");
+/// let (min, max) = (Duration::from_secs(90), Duration::from_secs(120));
+/// scheduler.add(Trigger::Random { min, max }, "surprise");
+///
+/// clock.advance(Duration::from_secs(59));
+/// assert!(scheduler.poll().is_empty());
+/// clock.advance(Duration::from_secs(1));
+/// assert_eq!(scheduler.poll(), vec!["every minute"]);
+///
+/// clock.advance(Duration::from_secs(60));
+/// let events = scheduler.poll();
+/// assert!(events.contains(&"every minute") && events.contains(&"surprise"));
+/// ```
+pub struct Scheduler<E, C: Clock = SystemClock> {
+    entries: Vec<Entry<E>>,
+    next_id: usize,
+    clock: C,
+    rng: Rng,
+}
+
+impl<E: Clone> Default for Scheduler<E> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<E: Clone> Scheduler<E> {
+    /// Scheduler 構造体を初期化して返します。
+    pub fn new() -> Self {
+        Self {
+            entries: Vec::new(),
+            next_id: 0,
+            clock: SystemClock,
+            rng: Rng::from_entropy(),
+        }
+    }
+}
+
+impl<E: Clone, C: Clock> Scheduler<E, C> {
+    /// 指定した時計と乱数のシード値を利用する Scheduler 構造体を初期化して返します。
+    pub fn with_clock(clock: C, seed: u64) -> Self {
+        Self {
+            entries: Vec::new(),
+            next_id: 0,
+            clock,
+            rng: Rng::new(seed),
+        }
+    }
+
+    /// イベントを登録し、削除する際に利用する番号を返します。
+    pub fn add(&mut self, trigger: Trigger, event: E) -> usize {
+        let id = self.next_id;
+        self.next_id += 1;
+        let mut entry = Entry {
+            id,
+            trigger,
+            event,
+            next: None,
+            next_time: None,
+        };
+        self.arm(&mut entry, self.clock.now());
+        self.entries.push(entry);
+        id
+    }
+
+    /// 登録したイベントを削除します。
+    pub fn remove(&mut self, id: usize) -> Option<E> {
+        let index = self.entries.iter().position(|entry| entry.id == id)?;
+        Some(self.entries.remove(index).event)
+    }
+
+    /// 登録したイベントの数を取得します。
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    /// イベントが登録されていないかどうかを判定します。
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// 次にイベントが発生する時刻を取得します。
+    pub fn next_deadline(&self) -> Option<Instant> {
+        self.entries.iter().filter_map(|entry| entry.next).min()
+    }
+
+    /// 時刻を過ぎたイベントを取得し、次の時刻を設定します。
+    ///
+    /// 処理が遅れて複数回分の時刻を過ぎていた場合でも、1つのイベントにつき1回だけ返します。
+    pub fn poll(&mut self) -> Vec<E> {
+        let now = self.clock.now();
+        let mut events = Vec::new();
+        let mut entries = std::mem::take(&mut self.entries);
+        for entry in entries.iter_mut() {
+            if entry.next.is_some_and(|next| next <= now) {
+                events.push(entry.event.clone());
+                self.arm(entry, now);
+            }
+        }
+        self.entries = entries;
+        events
+    }
+
+    /// 次にイベントを発生させる時刻を設定します。
+    fn arm(&mut self, entry: &mut Entry<E>, now: Instant) {
+        entry.next = match &entry.trigger {
+            Trigger::Interval(interval) => match entry.next {
+                // 遅れを蓄積させないように前回の時刻を基準にする
+                Some(next) if next + *interval > now => Some(next + *interval),
+                _ => Some(now + *interval),
+            },
+            Trigger::Random { min, max } => Some(now + self.rng.duration(*min, *max)),
+            Trigger::Cron(cron) => {
+                let current = Time::from_system_time(self.clock.system_time());
+                // 時計のずれで同じ日時に2回発生しないように、前回の日時以降から探す
+                let after = match entry.next_time {
+                    Some(last) if last > current => last,
+                    _ => current,
+                };
+                entry.next_time = cron.next_after(&after);
+                entry
+                    .next_time
+                    .map(|next| now + next.duration_since(&current).unwrap_or_default())
+            }
+        };
+    }
+}
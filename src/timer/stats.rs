@@ -0,0 +1,312 @@
+//! フレームの処理時間の統計を扱うための機能です。
+
+use std::{
+    collections::VecDeque,
+    time::{Duration, Instant},
+};
+
+/// 記録するフレーム数のデフォルト値です。
+pub const DEFAULT_CAPACITY: usize = 10_000;
+
+/// 1フレーム分の記録です。
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct FrameRecord {
+    /// 前のフレームからの経過時間
+    pub duration: Duration,
+    /// 表示すべき時刻からの遅れ
+    pub lateness: Duration,
+    /// 遅れによって飛ばしたフレームの数
+    pub dropped: usize,
+}
+
+/// 統計情報の集計結果です。
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct Summary {
+    /// 記録したフレームの総数
+    pub frames: u64,
+    /// 遅れたフレームの総数
+    pub late: u64,
+    /// 飛ばしたフレームの総数
+    pub dropped: u64,
+    /// 平均 fps
+    pub fps: f64,
+    /// 経過時間の平均
+    pub mean: Duration,
+    /// 経過時間の中央値
+    pub p50: Duration,
+    /// 経過時間の 90 パーセンタイル
+    pub p90: Duration,
+    /// 経過時間の 99 パーセンタイル
+    pub p99: Duration,
+    /// 経過時間の最大値
+    pub max: Duration,
+    /// 経過時間の標準偏差 (ジッター)
+    pub jitter: Duration,
+}
+
+impl std::fmt::Display for Summary {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "frames: {}, fps: {:.1}, p50: {:?}, p90: {:?}, p99: {:?}, max: {:?}, jitter: {:?}, late: {}, dropped: {}",
+            self.frames,
+            self.fps,
+            self.p50,
+            self.p90,
+            self.p99,
+            self.max,
+            self.jitter,
+            self.late,
+            self.dropped
+        )
+    }
+}
+
+/// フレームが記録されるたびに呼び出される出力先です。
+pub trait StatsSink {
+    /// フレームが記録された際の処理です。
+    fn frame(&mut self, record: &FrameRecord, stats: &FrameStats);
+}
+
+/// フレームごとの処理時間を記録し、統計情報を計算するための構造体です。
+///
+/// ```rust
+/// use bkrbk::timer::{FrameRecord, FrameStats};
+/// use std::time::Duration;
+///
+/// let mut stats = FrameStats::new(100);
+/// for millis in [10, 10, 10, 40] {
+///     stats.record(FrameRecord {
+///         duration: Duration::from_millis(millis),
+///         lateness: Duration::from_millis(millis - 10),
+///         dropped: (millis / 40) as usize,
+///     });
+/// }
+/// let summary = stats.summary();
+/// assert_eq!(summary.frames, 4);
+/// assert_eq!(summary.p50, Duration::from_millis(10));
+/// assert_eq!(summary.max, Duration::from_millis(40));
+/// assert_eq!((summary.late, summary.dropped), (1, 1));
+/// assert_eq!(stats.to_csv().lines().nth(4), Some("3,40000,30000,1"));
+/// ```
+pub struct FrameStats {
+    /// 直近のフレームの記録
+    records: VecDeque<FrameRecord>,
+    /// 記録するフレーム数の上限
+    capacity: usize,
+    frames: u64,
+    late: u64,
+    dropped: u64,
+    /// 最後にフレームを記録した時刻
+    last: Option<Instant>,
+    /// この時間以上遅れたフレームを「遅れた」とみなします。
+    pub late_threshold: Duration,
+    sinks: Vec<Box<dyn StatsSink>>,
+}
+
+impl Default for FrameStats {
+    fn default() -> Self {
+        Self::new(DEFAULT_CAPACITY)
+    }
+}
+
+impl FrameStats {
+    /// 直近 `capacity` フレーム分を記録する構造体を初期化して返します。
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            records: VecDeque::with_capacity(capacity.min(DEFAULT_CAPACITY)),
+            capacity: capacity.max(1),
+            frames: 0,
+            late: 0,
+            dropped: 0,
+            last: None,
+            late_threshold: Duration::from_millis(1),
+            sinks: Vec::new(),
+        }
+    }
+
+    /// 出力先を追加します。
+    pub fn add_sink(&mut self, sink: Box<dyn StatsSink>) {
+        self.sinks.push(sink);
+    }
+
+    /// 出力先を全て削除します。
+    pub fn clear_sinks(&mut self) {
+        self.sinks.clear();
+    }
+
+    /// 指定した時刻にフレームを表示したことを記録します。
+    ///
+    /// 前のフレームからの経過時間は前回の呼び出し時刻から計算します。
+    pub fn frame(&mut self, now: Instant, lateness: Duration, dropped: usize) {
+        let duration = self
+            .last
+            .map_or(Duration::ZERO, |last| now.saturating_duration_since(last));
+        self.last = Some(now);
+        self.record(FrameRecord {
+            duration,
+            lateness,
+            dropped,
+        });
+    }
+
+    /// フレームの記録を追加します。
+    pub fn record(&mut self, record: FrameRecord) {
+        if self.records.len() == self.capacity {
+            self.records.pop_front();
+        }
+        self.records.push_back(record);
+        self.frames += 1;
+        self.dropped += record.dropped as u64;
+        if record.lateness >= self.late_threshold {
+            self.late += 1;
+        }
+
+        let mut sinks = std::mem::take(&mut self.sinks);
+        for sink in sinks.iter_mut() {
+            sink.frame(&record, self);
+        }
+        self.sinks = sinks;
+    }
+
+    /// 直近のフレームの記録を取得します。
+    pub fn records(&self) -> impl Iterator<Item = &FrameRecord> {
+        self.records.iter()
+    }
+
+    /// 直近のフレームの経過時間のパーセンタイル ( `0.0` - `100.0` ) を取得します。
+    pub fn percentile(&self, percent: f64) -> Duration {
+        let mut durations: Vec<Duration> = self.records.iter().map(|r| r.duration).collect();
+        durations.sort();
+        Self::percentile_of(&durations, percent)
+    }
+
+    /// ソート済みの一覧からパーセンタイルを取得します。 (最近傍法)
+    fn percentile_of(sorted: &[Duration], percent: f64) -> Duration {
+        if sorted.is_empty() {
+            return Duration::ZERO;
+        }
+        let rank = (percent.clamp(0.0, 100.0) / 100.0 * sorted.len() as f64).ceil() as usize;
+        sorted[rank.clamp(1, sorted.len()) - 1]
+    }
+
+    /// 統計情報を集計します。
+    pub fn summary(&self) -> Summary {
+        let mut durations: Vec<Duration> = self.records.iter().map(|r| r.duration).collect();
+        durations.sort();
+        let total: Duration = durations.iter().sum();
+        let n = durations.len().max(1) as f64;
+        let mean = total.as_secs_f64() / n;
+        let variance = durations
+            .iter()
+            .map(|d| (d.as_secs_f64() - mean).powi(2))
+            .sum::<f64>()
+            / n;
+
+        Summary {
+            frames: self.frames,
+            late: self.late,
+            dropped: self.dropped,
+            fps: match total.is_zero() {
+                true => 0.0,
+                false => durations.len() as f64 / total.as_secs_f64(),
+            },
+            mean: Duration::from_secs_f64(mean),
+            p50: Self::percentile_of(&durations, 50.0),
+            p90: Self::percentile_of(&durations, 90.0),
+            p99: Self::percentile_of(&durations, 99.0),
+            max: durations.last().copied().unwrap_or_default(),
+            jitter: Duration::from_secs_f64(variance.sqrt()),
+        }
+    }
+
+    /// 直近のフレームの記録を CSV 形式で取得します。 (時間はマイクロ秒単位)
+    pub fn to_csv(&self) -> String {
+        let mut result = String::from("frame,duration_us,lateness_us,dropped\n");
+        let first = self.frames - self.records.len() as u64;
+        for (i, record) in self.records.iter().enumerate() {
+            result += &format!(
+                "{},{},{},{}\n",
+                first + i as u64,
+                record.duration.as_micros(),
+                record.lateness.as_micros(),
+                record.dropped
+            );
+        }
+        result
+    }
+
+    /// 集計結果と直近のフレームの記録を JSON 形式で取得します。 (時間はマイクロ秒単位)
+    pub fn to_json(&self) -> String {
+        let summary = self.summary();
+        let records: Vec<String> = self
+            .records
+            .iter()
+            .map(|record| {
+                format!(
+                    "{{\"duration_us\":{},\"lateness_us\":{},\"dropped\":{}}}",
+                    record.duration.as_micros(),
+                    record.lateness.as_micros(),
+                    record.dropped
+                )
+            })
+            .collect();
+        format!(
+            "{{\"frames\":{},\"late\":{},\"dropped\":{},\"fps\":{:.3},\"mean_us\":{},\"p50_us\":{},\"p90_us\":{},\"p99_us\":{},\"max_us\":{},\"jitter_us\":{},\"records\":[{}]}}",
+            summary.frames,
+            summary.late,
+            summary.dropped,
+            summary.fps,
+            summary.mean.as_micros(),
+            summary.p50.as_micros(),
+            summary.p90.as_micros(),
+            summary.p99.as_micros(),
+            summary.max.as_micros(),
+            summary.jitter.as_micros(),
+            records.join(",")
+        )
+    }
+}
+
+/// fps の実測値を標準エラー出力に表示するための出力先です。
+///
+/// 一定時間ごとに、その間に表示したフレーム数と経過時間から fps を計算して表示します。
+pub struct FpsPrinter {
+    /// 表示間隔
+    interval: Duration,
+    /// 前回の表示から経過した時間
+    elapsed: Duration,
+    /// 前回の表示から経過したフレーム数
+    count: u32,
+}
+
+impl Default for FpsPrinter {
+    fn default() -> Self {
+        Self::new(Duration::from_secs(1))
+    }
+}
+
+impl FpsPrinter {
+    /// 指定した間隔で表示する構造体を初期化して返します。
+    pub fn new(interval: Duration) -> Self {
+        Self {
+            interval,
+            elapsed: Duration::ZERO,
+            count: 0,
+        }
+    }
+}
+
+impl StatsSink for FpsPrinter {
+    fn frame(&mut self, record: &FrameRecord, _: &FrameStats) {
+        self.elapsed += record.duration;
+        self.count += 1;
+        if self.elapsed >= self.interval {
+            let fps = self.count as f64 / self.elapsed.as_secs_f64();
+            // 標準出力は描画に使う場合があるので、標準エラー出力に表示する
+            eprint!("fps: {:.1}\r", fps);
+            self.elapsed = Duration::ZERO;
+            self.count = 0;
+        }
+    }
+}
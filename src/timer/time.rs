@@ -0,0 +1,553 @@
+//! 日時とタイムゾーンを扱うための機能です。
+
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+/// 1日のミリ秒数です。
+pub(super) const MILLIS_PER_DAY: i64 = 24 * 60 * 60 * 1000;
+
+/// タイムゾーンを扱うための構造体です。
+///
+/// UTC より遅れている (西側の) タイムゾーンは `hour` と `minute` の両方を負の値にします。
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct TimeZone {
+    pub hour: i16,
+    pub minute: i16,
+    // NOTE: 必要になったらコメントインする
+    // pub second: u16,
+    // pub milli_seconds: u16,
+}
+
+impl TimeZone {
+    /// 協定世界時です。
+    pub const UTC: Self = Self { hour: 0, minute: 0 };
+
+    /// UTC からのずれ (分単位) から構造体を初期化して返します。
+    ///
+    /// ```rust
+    /// use bkrbk::timer::TimeZone;
+    ///
+    /// let tz = TimeZone::from_offset_minutes(-210);
+    /// assert_eq!((tz.hour, tz.minute), (-3, -30));
+    /// assert_eq!(tz.offset_minutes(), -210);
+    /// ```
+    pub fn from_offset_minutes(minutes: i32) -> Self {
+        Self {
+            hour: (minutes / 60) as i16,
+            minute: (minutes % 60) as i16,
+        }
+    }
+
+    /// UTC からのずれを分単位で取得します。
+    pub fn offset_minutes(&self) -> i32 {
+        self.hour as i32 * 60 + self.minute as i32
+    }
+
+    /// 現在のローカルタイムゾーンを取得します。
+    pub fn local() -> Self {
+        Self::local_at(Time::unix_millis_now())
+    }
+
+    /// 指定した時刻 (UNIX 時間のミリ秒) におけるローカルタイムゾーンを取得します。
+    ///
+    /// 夏時間がある地域では時刻によって結果が変わります。取得できない場合は UTC を返します。
+    pub fn local_at(unix_millis: i64) -> Self {
+        Self::from_offset_minutes(local_offset_seconds(unix_millis.div_euclid(1000)) / 60)
+    }
+}
+
+/// OS の設定からローカルタイムゾーンの UTC からのずれ (秒単位) を取得します。
+#[cfg(target_os = "linux")]
+fn local_offset_seconds(unix_seconds: i64) -> i32 {
+    let time = unix_seconds as libc::time_t;
+    let mut tm = unsafe { std::mem::zeroed::<libc::tm>() };
+    match unsafe { libc::localtime_r(&time, &mut tm) }.is_null() {
+        true => 0,
+        false => tm.tm_gmtoff as i32,
+    }
+}
+
+/// OS の設定からローカルタイムゾーンの UTC からのずれ (秒単位) を取得します。
+#[cfg(target_os = "windows")]
+fn local_offset_seconds(unix_seconds: i64) -> i32 {
+    use windows::Win32::{Foundation::SYSTEMTIME, System::Time::SystemTimeToTzSpecificLocalTime};
+
+    let to_system_time = |datetime: &DateTime| SYSTEMTIME {
+        wYear: datetime.year,
+        wMonth: datetime.month,
+        wDayOfWeek: datetime.weekday(),
+        wDay: datetime.day,
+        wHour: datetime.hour,
+        wMinute: datetime.minute,
+        wSecond: datetime.second,
+        wMilliseconds: 0,
+    };
+    let utc = to_system_time(&DateTime::from_unix_millis(unix_seconds * 1000));
+    let mut local = to_system_time(&DateTime::default());
+    if unsafe { SystemTimeToTzSpecificLocalTime(None, &utc, &mut local) }.is_err() {
+        return 0;
+    }
+    let local = DateTime::new(
+        local.wYear,
+        local.wMonth,
+        local.wDay,
+        local.wHour,
+        local.wMinute,
+        local.wSecond,
+        0,
+    );
+    (local.to_unix_millis() / 1000 - unix_seconds) as i32
+}
+
+/// OS の設定からローカルタイムゾーンの UTC からのずれ (秒単位) を取得します。
+///
+/// 対応していない OS では常に UTC とみなします。
+#[cfg(not(any(target_os = "linux", target_os = "windows")))]
+fn local_offset_seconds(_: i64) -> i32 {
+    0
+}
+
+/// 日時を扱うための構造体です。
+///
+/// タイムゾーンの情報は持たないので、 UNIX 時間との変換は UTC の日時として行います。
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub struct DateTime {
+    pub year: u16,
+    pub month: u16,
+    pub day: u16,
+    pub hour: u16,
+    pub minute: u16,
+    pub second: u16,
+    pub milli_seconds: u16,
+}
+
+impl Default for DateTime {
+    fn default() -> Self {
+        Self::new(1970, 1, 1, 0, 0, 0, 0)
+    }
+}
+
+impl DateTime {
+    pub fn new(
+        year: u16,
+        month: u16,
+        day: u16,
+        hour: u16,
+        minute: u16,
+        second: u16,
+        milli_seconds: u16,
+    ) -> Self {
+        Self {
+            year,
+            month,
+            day,
+            hour,
+            minute,
+            second,
+            milli_seconds,
+        }
+    }
+
+    /// うるう年かどうかを判定します。
+    pub fn is_leap_year(year: u16) -> bool {
+        (year.is_multiple_of(4) && !year.is_multiple_of(100)) || year.is_multiple_of(400)
+    }
+
+    /// 指定した月の日数を取得します。
+    pub fn days_in_month(year: u16, month: u16) -> u16 {
+        match month {
+            1 | 3 | 5 | 7 | 8 | 10 | 12 => 31,
+            4 | 6 | 9 | 11 => 30,
+            2 if Self::is_leap_year(year) => 29,
+            2 => 28,
+            _ => 0,
+        }
+    }
+
+    /// 存在する日時かどうかを判定します。 (うるう秒は扱いません)
+    pub fn is_valid(&self) -> bool {
+        (1..=12).contains(&self.month)
+            && (1..=Self::days_in_month(self.year, self.month)).contains(&self.day)
+            && self.hour < 24
+            && self.minute < 60
+            && self.second < 60
+            && self.milli_seconds < 1000
+    }
+
+    /// 曜日を取得します。 (日曜日が `0` 、土曜日が `6` です)
+    ///
+    /// ```rust
+    /// use bkrbk::timer::DateTime;
+    ///
+    /// // 2024-02-29 は木曜日
+    /// assert_eq!(DateTime::new(2024, 2, 29, 0, 0, 0, 0).weekday(), 4);
+    /// ```
+    pub fn weekday(&self) -> u16 {
+        // 1970-01-01 は木曜日
+        (self.days_since_epoch() + 4).rem_euclid(7) as u16
+    }
+
+    /// 1970-01-01 からの日数を取得します。
+    fn days_since_epoch(&self) -> i64 {
+        let month = self.month as i64;
+        let year = self.year as i64 - (month <= 2) as i64;
+        let era = year.div_euclid(400);
+        let year_of_era = year - era * 400;
+        let day_of_year =
+            (153 * (month + if month > 2 { -3 } else { 9 }) + 2) / 5 + self.day as i64 - 1;
+        let day_of_era = year_of_era * 365 + year_of_era / 4 - year_of_era / 100 + day_of_year;
+        era * 146097 + day_of_era - 719468
+    }
+
+    /// 1970-01-01 からの日数から日付を取得します。 (年, 月, 日)
+    fn civil_from_days(days: i64) -> (u16, u16, u16) {
+        let days = days + 719468;
+        let era = days.div_euclid(146097);
+        let day_of_era = days - era * 146097;
+        let year_of_era =
+            (day_of_era - day_of_era / 1460 + day_of_era / 36524 - day_of_era / 146096) / 365;
+        let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+        let mp = (5 * day_of_year + 2) / 153;
+        let day = day_of_year - (153 * mp + 2) / 5 + 1;
+        let month = if mp < 10 { mp + 3 } else { mp - 9 };
+        let year = year_of_era + era * 400 + (month <= 2) as i64;
+        (year as u16, month as u16, day as u16)
+    }
+
+    /// UNIX 時間 (ミリ秒) から UTC の日時を取得します。
+    ///
+    /// ```rust
+    /// use bkrbk::timer::DateTime;
+    ///
+    /// let datetime = DateTime::from_unix_millis(951_782_400_123);
+    /// assert_eq!(datetime, DateTime::new(2000, 2, 29, 0, 0, 0, 123));
+    /// assert_eq!(datetime.to_unix_millis(), 951_782_400_123);
+    /// ```
+    pub fn from_unix_millis(millis: i64) -> Self {
+        let (year, month, day) = Self::civil_from_days(millis.div_euclid(MILLIS_PER_DAY));
+        let millis = millis.rem_euclid(MILLIS_PER_DAY);
+        Self {
+            year,
+            month,
+            day,
+            hour: (millis / 3_600_000) as u16,
+            minute: (millis / 60_000 % 60) as u16,
+            second: (millis / 1000 % 60) as u16,
+            milli_seconds: (millis % 1000) as u16,
+        }
+    }
+
+    /// UTC の日時とみなして UNIX 時間 (ミリ秒) に変換します。
+    pub fn to_unix_millis(&self) -> i64 {
+        self.days_since_epoch() * MILLIS_PER_DAY
+            + self.hour as i64 * 3_600_000
+            + self.minute as i64 * 60_000
+            + self.second as i64 * 1000
+            + self.milli_seconds as i64
+    }
+}
+
+/// ISO 8601 形式の文字列の解析に失敗した際のエラーです。
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ParseError {
+    /// 形式が正しくありません。
+    Format(String),
+    /// 存在しない日時です。
+    OutOfRange(String),
+}
+
+impl std::fmt::Display for ParseError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Format(s) => write!(f, "invalid ISO 8601 format: {}", s),
+            Self::OutOfRange(s) => write!(f, "date or time out of range: {}", s),
+        }
+    }
+}
+
+impl std::error::Error for ParseError {}
+
+/// 時間を扱うための構造体です。
+///
+/// `datetime` は `timezone` における日時 (ローカル時刻) を表します。
+/// 比較はタイムゾーンを考慮した時刻で行います。
+#[derive(Debug, Clone, Copy)]
+pub struct Time {
+    pub datetime: DateTime,
+    pub timezone: TimeZone,
+}
+
+impl Time {
+    /// 現在時刻の UNIX 時間 (ミリ秒) を取得します。
+    fn unix_millis_now() -> i64 {
+        Self::unix_millis_of(SystemTime::now())
+    }
+
+    /// 時刻を UNIX 時間 (ミリ秒) に変換します。
+    fn unix_millis_of(time: SystemTime) -> i64 {
+        match time.duration_since(UNIX_EPOCH) {
+            Ok(d) => d.as_millis() as i64,
+            Err(e) => -(e.duration().as_millis() as i64),
+        }
+    }
+
+    /// ローカルタイムゾーンの現在時刻を取得します。
+    pub fn now() -> Self {
+        Self::from_system_time(SystemTime::now())
+    }
+
+    /// UTC の現在時刻を取得します。
+    pub fn now_utc() -> Self {
+        Self::from_unix_millis(Self::unix_millis_now(), TimeZone::UTC)
+    }
+
+    /// システムの時刻をローカルタイムゾーンの時刻に変換します。
+    pub fn from_system_time(time: SystemTime) -> Self {
+        let millis = Self::unix_millis_of(time);
+        Self::from_unix_millis(millis, TimeZone::local_at(millis))
+    }
+
+    /// UNIX 時間 (ミリ秒) を指定したタイムゾーンの時刻に変換します。
+    pub fn from_unix_millis(millis: i64, timezone: TimeZone) -> Self {
+        Self {
+            datetime: DateTime::from_unix_millis(
+                millis + timezone.offset_minutes() as i64 * 60_000,
+            ),
+            timezone,
+        }
+    }
+
+    /// UNIX 時間 (ミリ秒) を取得します。
+    pub fn unix_millis(&self) -> i64 {
+        self.datetime.to_unix_millis() - self.timezone.offset_minutes() as i64 * 60_000
+    }
+
+    /// システムの時刻に変換します。
+    pub fn to_system_time(&self) -> SystemTime {
+        let millis = self.unix_millis();
+        match millis >= 0 {
+            true => UNIX_EPOCH + Duration::from_millis(millis as u64),
+            false => UNIX_EPOCH - Duration::from_millis(millis.unsigned_abs()),
+        }
+    }
+
+    /// 同じ時刻を指定したタイムゾーンで表した時間を取得します。
+    pub fn with_timezone(&self, timezone: TimeZone) -> Self {
+        Self::from_unix_millis(self.unix_millis(), timezone)
+    }
+
+    /// 指定したミリ秒数を加算した時間を取得します。 (負の値の場合は減算します)
+    pub fn add_millis(&self, millis: i64) -> Self {
+        Self::from_unix_millis(self.unix_millis() + millis, self.timezone)
+    }
+
+    /// 指定した日数を加算した時間を取得します。 (負の値の場合は減算します)
+    ///
+    /// タイムゾーンは変えずに日付だけを進めるので、時刻はそのまま維持されます。
+    pub fn add_days(&self, days: i64) -> Self {
+        self.add_millis(days * MILLIS_PER_DAY)
+    }
+
+    /// `earlier` からの経過時間を取得します。 `earlier` の方が後の時刻の場合は `None` を返します。
+    pub fn duration_since(&self, earlier: &Time) -> Option<Duration> {
+        let millis = self.unix_millis() - earlier.unix_millis();
+        (millis >= 0).then(|| Duration::from_millis(millis as u64))
+    }
+
+    /// 0時0分からの経過時間を取得します。 (ローカル時刻)
+    pub fn time_of_day(&self) -> Duration {
+        Duration::from_millis(self.datetime.to_unix_millis().rem_euclid(MILLIS_PER_DAY) as u64)
+    }
+
+    pub fn format_iso_8601(&self) -> String {
+        let offset = self.timezone.offset_minutes();
+        format!(
+            "{:>04}-{:>02}-{:>02}T{:>02}:{:>02}:{:>02}.{:>03}{}{:>02}:{:>02}",
+            self.datetime.year,
+            self.datetime.month,
+            self.datetime.day,
+            self.datetime.hour,
+            self.datetime.minute,
+            self.datetime.second,
+            self.datetime.milli_seconds,
+            if offset < 0 { '-' } else { '+' },
+            offset.abs() / 60,
+            offset.abs() % 60
+        )
+    }
+
+    /// ISO 8601 形式の文字列を解析します。
+    ///
+    /// `YYYY-MM-DDTHH:MM[:SS[.fff]]` の後にタイムゾーン ( `Z` , `±HH:MM` , `±HHMM` , `±HH` ) を受け付けます。
+    /// タイムゾーンを省略した場合はローカル時刻とみなします。小数点以下の秒はミリ秒未満を切り捨てます。
+    ///
+    /// ```rust
+    /// use bkrbk::timer::{Time, TimeZone};
+    ///
+    /// let time = Time::parse_iso_8601("2024-12-31T23:30:05.5-03:30").unwrap();
+    /// assert_eq!(time.timezone, TimeZone::from_offset_minutes(-210));
+    /// assert_eq!(time.format_iso_8601(), "2024-12-31T23:30:05.500-03:30");
+    ///
+    /// // 日付の計算
+    /// let utc = time.with_timezone(TimeZone::UTC).add_days(59);
+    /// assert_eq!(utc.to_string(), "2025-03-01T03:00:05.500+00:00");
+    /// assert!(utc > time);
+    ///
+    /// // ミリ秒未満は切り捨てる
+    /// let time = Time::parse_iso_8601("2024-01-01T00:00:00.123456Z").unwrap();
+    /// assert_eq!(time.to_string(), "2024-01-01T00:00:00.123+00:00");
+    /// let time = Time::parse_iso_8601("2024-01-01T00:00:00.99999Z").unwrap();
+    /// assert_eq!(time.to_string(), "2024-01-01T00:00:00.999+00:00");
+    ///
+    /// assert!(Time::parse_iso_8601("2023-02-29T00:00Z").is_err());
+    /// assert!(Time::parse_iso_8601("2024-01-01T00:00:00.Z").is_err());
+    /// ```
+    pub fn parse_iso_8601(s: &str) -> Result<Self, ParseError> {
+        let format_error = || ParseError::Format(s.to_string());
+        let number = |part: &str| -> Result<u16, ParseError> {
+            match !part.is_empty() && part.bytes().all(|b| b.is_ascii_digit()) {
+                true => part.parse().map_err(|_| format_error()),
+                false => Err(format_error()),
+            }
+        };
+
+        let (date, time) = s.split_once(['T', 't', ' ']).ok_or_else(format_error)?;
+
+        // 日付
+        let mut date = date.split('-');
+        let (year, month, day) = match (date.next(), date.next(), date.next(), date.next()) {
+            (Some(y), Some(m), Some(d), None) if y.len() == 4 && m.len() == 2 && d.len() == 2 => {
+                (number(y)?, number(m)?, number(d)?)
+            }
+            _ => return Err(format_error()),
+        };
+
+        // タイムゾーン
+        let (time, timezone) = match time.find(['Z', 'z', '+', '-']) {
+            Some(i) => (
+                &time[..i],
+                Some(Self::parse_timezone(&time[i..]).ok_or_else(format_error)?),
+            ),
+            None => (time, None),
+        };
+
+        // 時刻
+        let (time, fraction) = match time.split_once(['.', ',']) {
+            Some((time, fraction)) => (time, Some(fraction)),
+            None => (time, None),
+        };
+        let mut time = time.split(':');
+        let (hour, minute, second) = match (time.next(), time.next(), time.next(), time.next()) {
+            (Some(h), Some(m), None, None)
+                if h.len() == 2 && m.len() == 2 && fraction.is_none() =>
+            {
+                (number(h)?, number(m)?, 0)
+            }
+            (Some(h), Some(m), Some(s), None) if h.len() == 2 && m.len() == 2 && s.len() == 2 => {
+                (number(h)?, number(m)?, number(s)?)
+            }
+            _ => return Err(format_error()),
+        };
+        let milli_seconds = match fraction {
+            Some(fraction) => {
+                if fraction.is_empty() || !fraction.bytes().all(|b| b.is_ascii_digit()) {
+                    return Err(format_error());
+                }
+                let digits: String = fraction.chars().chain("00".chars()).take(3).collect();
+                number(&digits)?
+            }
+            None => 0,
+        };
+
+        let datetime = DateTime::new(year, month, day, hour, minute, second, milli_seconds);
+        if !datetime.is_valid() {
+            return Err(ParseError::OutOfRange(s.to_string()));
+        }
+        let timezone = timezone.unwrap_or_else(|| {
+            // ローカル時刻から UTC を推定し、その時刻のタイムゾーンを利用する
+            let local = datetime.to_unix_millis();
+            let guess = local - TimeZone::local_at(local).offset_minutes() as i64 * 60_000;
+            TimeZone::local_at(guess)
+        });
+        Ok(Self { datetime, timezone })
+    }
+
+    /// `Z` , `±HH:MM` , `±HHMM` , `±HH` 形式のタイムゾーンを解析します。
+    fn parse_timezone(s: &str) -> Option<TimeZone> {
+        if s.eq_ignore_ascii_case("z") {
+            return Some(TimeZone::UTC);
+        }
+        let sign = match s.as_bytes().first()? {
+            b'+' => 1,
+            b'-' => -1,
+            _ => return None,
+        };
+        let digits: String = s[1..].chars().filter(|c| *c != ':').collect();
+        let valid = match s.len() {
+            3 | 5 => !s.contains(':'),
+            6 => s.as_bytes()[3] == b':',
+            _ => false,
+        };
+        if !valid || !digits.bytes().all(|b| b.is_ascii_digit()) {
+            return None;
+        }
+        let hour: i32 = digits[..2].parse().ok()?;
+        let minute: i32 = digits
+            .get(2..)
+            .filter(|m| !m.is_empty())
+            .map_or(Some(0), |m| m.parse().ok())?;
+        if hour > 23 || minute > 59 {
+            return None;
+        }
+        Some(TimeZone::from_offset_minutes(sign * (hour * 60 + minute)))
+    }
+}
+
+impl std::str::FromStr for Time {
+    type Err = ParseError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Self::parse_iso_8601(s)
+    }
+}
+
+impl PartialEq for Time {
+    fn eq(&self, other: &Self) -> bool {
+        self.unix_millis() == other.unix_millis()
+    }
+}
+
+impl Eq for Time {}
+
+impl PartialOrd for Time {
+    fn partial_cmp(&self, other: &Self) -> Option<std::cmp::Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for Time {
+    fn cmp(&self, other: &Self) -> std::cmp::Ordering {
+        self.unix_millis().cmp(&other.unix_millis())
+    }
+}
+
+impl std::ops::Add<Duration> for Time {
+    type Output = Time;
+
+    fn add(self, rhs: Duration) -> Self::Output {
+        self.add_millis(rhs.as_millis() as i64)
+    }
+}
+
+impl std::ops::Sub<Duration> for Time {
+    type Output = Time;
+
+    fn sub(self, rhs: Duration) -> Self::Output {
+        self.add_millis(-(rhs.as_millis() as i64))
+    }
+}
+
+impl std::fmt::Display for Time {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.format_iso_8601())
+    }
+}
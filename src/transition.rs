@@ -0,0 +1,255 @@
+//! アニメーションを切り替える際の効果 (トランジション) のための機能です。
+//!
+//! 切り替え前後の画像から CPU で合成するので、どの描画先でも利用できます。
+//! 描画先は透過色 (キーカラー) で透明を表すため、半透明の画素は作れません。
+//! 透明な部分との間の中間の状態は、ディザリング (画素を間引いて表示すること) で表現します。
+
+use std::time::{Duration, Instant};
+
+use crate::{
+    color::Color,
+    timer::{Clock, SystemClock},
+};
+
+/// デフォルトの切り替えにかかる時間です。
+pub const DEFAULT_DURATION: Duration = Duration::from_millis(400);
+
+/// ディザリングに利用する 4x4 の閾値の表 (Bayer matrix) です。
+const BAYER: [[u8; 4]; 4] = [[0, 8, 2, 10], [12, 4, 14, 6], [3, 11, 1, 9], [15, 7, 13, 5]];
+
+/// 拡大縮小の最後に行き過ぎてから戻る量です。 (back-out easing の係数)
+const OVERSHOOT: f64 = 1.70158;
+
+/// ワイプで画像を覆っていく向きです。
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Direction {
+    Left,
+    Right,
+    Up,
+    Down,
+}
+
+/// 切り替えの効果です。
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Effect {
+    /// 切り替え前の画像から切り替え後の画像へ、徐々に混ぜ合わせます。
+    Crossfade,
+    /// 切り替え前の画像を消してから、切り替え後の画像を表示します。 (ウィンドウの不透明度の変化をディザリングで表現します)
+    Fade,
+    /// 切り替え後の画像で、指定した向きに覆っていきます。
+    Wipe(Direction),
+    /// 切り替え前の画像を下端の中央に向けて縮めてから、切り替え後の画像を弾むように拡大します。
+    ScalePop,
+}
+
+impl std::str::FromStr for Effect {
+    type Err = String;
+
+    /// `crossfade`, `fade`, `wipe` (右向き), `wipe-left`, `wipe-right`, `wipe-up`, `wipe-down`, `scale-pop` のいずれかを受け付けます。
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "crossfade" => Ok(Self::Crossfade),
+            "fade" => Ok(Self::Fade),
+            "wipe" | "wipe-right" => Ok(Self::Wipe(Direction::Right)),
+            "wipe-left" => Ok(Self::Wipe(Direction::Left)),
+            "wipe-up" => Ok(Self::Wipe(Direction::Up)),
+            "wipe-down" => Ok(Self::Wipe(Direction::Down)),
+            "scale-pop" => Ok(Self::ScalePop),
+            _ => Err(format!("unknown transition: {}", s)),
+        }
+    }
+}
+
+impl Effect {
+    /// 切り替えの途中の画像を合成します。
+    ///
+    /// `from` と `to` は切り替え前後の画像、 `progress` は 0.0 (切り替え前) から 1.0 (切り替え後) までの進み具合です。
+    /// 画像は全て `width` 列の同じ大きさで、 `bg` と一致する画素は透明として扱います。
+    /// ```rust
+    /// use bkrbk::transition::{Direction, Effect};
+    ///
+    /// let bg = 0x00_00_00_FF;
+    /// let from = [0x00_FF_00_00; 4];
+    /// let to = [0x00_00_FF_00; 4];
+    /// let mut out = [0; 4];
+    ///
+    /// // 左半分だけ切り替え後の画像になる
+    /// Effect::Wipe(Direction::Right).draw(&from, &to, &mut out, 4, 0.5, bg);
+    /// assert_eq!(out, [to[0], to[0], from[0], from[0]]);
+    ///
+    /// // 不透明な画素同士は色を混ぜる
+    /// Effect::Crossfade.draw(&from, &to, &mut out, 4, 0.5, bg);
+    /// assert_eq!(out[0], 0x00_80_7F_00);
+    ///
+    /// // 途中で一度全て透明になる
+    /// Effect::Fade.draw(&from, &to, &mut out, 4, 0.5, bg);
+    /// assert_eq!(out, [bg; 4]);
+    /// ```
+    pub fn draw(
+        &self,
+        from: &[u32],
+        to: &[u32],
+        out: &mut [u32],
+        width: usize,
+        progress: f64,
+        bg: u32,
+    ) {
+        let t = progress.clamp(0.0, 1.0);
+        let height = out.len() / width.max(1);
+        match self {
+            Self::Crossfade => {
+                let t = smoothstep(t);
+                for (i, pixel) in out.iter_mut().enumerate() {
+                    let (a, b) = (from[i], to[i]);
+                    *pixel = match (a == bg, b == bg) {
+                        (false, false) => Color::new(a).alpha_blend((t * 255.0) as u8, b),
+                        // 片方が透明な場合は、不透明な方を間引いて表示する
+                        (true, false) => dither(b, t, i % width, i / width, bg),
+                        (false, true) => dither(a, 1.0 - t, i % width, i / width, bg),
+                        (true, true) => bg,
+                    };
+                }
+            }
+            Self::Fade => {
+                let (image, opacity) = match t < 0.5 {
+                    true => (from, 1.0 - t * 2.0),
+                    false => (to, t * 2.0 - 1.0),
+                };
+                for (i, pixel) in out.iter_mut().enumerate() {
+                    *pixel = dither(image[i], opacity, i % width, i / width, bg);
+                }
+            }
+            Self::Wipe(direction) => {
+                let t = smoothstep(t);
+                let columns = (width as f64 * t).round() as usize;
+                let rows = (height as f64 * t).round() as usize;
+                for (i, pixel) in out.iter_mut().enumerate() {
+                    let (x, y) = (i % width, i / width);
+                    let covered = match direction {
+                        Direction::Right => x < columns,
+                        Direction::Left => x >= width - columns,
+                        Direction::Down => y < rows,
+                        Direction::Up => y >= height - rows,
+                    };
+                    *pixel = if covered { to[i] } else { from[i] };
+                }
+            }
+            Self::ScalePop => {
+                let (image, scale) = match t < 0.5 {
+                    true => (from, 1.0 - ease_in(t * 2.0)),
+                    false => (to, back_out(t * 2.0 - 1.0)),
+                };
+                scale_from_bottom(image, out, width, scale, bg);
+            }
+        }
+    }
+}
+
+/// 緩やかに始まり緩やかに終わるように、進み具合を補正します。
+fn smoothstep(t: f64) -> f64 {
+    t * t * (3.0 - 2.0 * t)
+}
+
+/// 緩やかに始まるように、進み具合を補正します。
+fn ease_in(t: f64) -> f64 {
+    t * t
+}
+
+/// 最後に少し行き過ぎてから戻るように、進み具合を補正します。
+fn back_out(t: f64) -> f64 {
+    let t = t - 1.0;
+    1.0 + t * t * ((OVERSHOOT + 1.0) * t + OVERSHOOT)
+}
+
+/// 不透明度に応じて画素を間引きます。 (間引いた画素は透明になります)
+fn dither(pixel: u32, opacity: f64, x: usize, y: usize, bg: u32) -> u32 {
+    let threshold = (BAYER[y % 4][x % 4] as f64 + 0.5) / 16.0;
+    match opacity > threshold {
+        true => pixel,
+        false => bg,
+    }
+}
+
+/// 下端の中央を基準に画像を拡大縮小して描画します。 (最近傍補間)
+fn scale_from_bottom(image: &[u32], out: &mut [u32], width: usize, scale: f64, bg: u32) {
+    let height = out.len() / width.max(1);
+    let center = width as f64 / 2.0;
+    for (i, pixel) in out.iter_mut().enumerate() {
+        let (x, y) = (i % width, i / width);
+        *pixel = bg;
+        if scale <= 0.0 {
+            continue;
+        }
+        // 出力の画素の中心に対応する、元の画像の位置
+        let sx = center + (x as f64 + 0.5 - center) / scale;
+        let sy = height as f64 - (height as f64 - (y as f64 + 0.5)) / scale;
+        if (0.0..width as f64).contains(&sx) && (0.0..height as f64).contains(&sy) {
+            *pixel = image[sy as usize * width + sx as usize];
+        }
+    }
+}
+
+/// 時間の経過に合わせて切り替えの効果を描画するための構造体です。
+///
+/// ```rust
+/// use bkrbk::timer::MockClock;
+/// use bkrbk::transition::{Effect, Transition};
+/// use std::time::Duration;
+///
+/// let clock = MockClock::new();
+/// let transition = Transition::with_clock(Effect::Crossfade, Duration::from_millis(400), clock.clone());
+/// clock.advance(Duration::from_millis(100));
+/// assert_eq!(transition.progress(), 0.25);
+/// assert!(!transition.is_finished());
+///
+/// clock.advance(Duration::from_millis(300));
+/// assert!(transition.is_finished());
+/// ```
+pub struct Transition<C: Clock = SystemClock> {
+    /// 切り替えの効果
+    pub effect: Effect,
+    /// 切り替えにかかる時間
+    pub duration: Duration,
+    /// 切り替えを始めた時刻
+    started: Instant,
+    /// 時刻の取得に利用する時計
+    clock: C,
+}
+
+impl Transition {
+    /// 現在の時刻から切り替えを始めます。
+    pub fn new(effect: Effect, duration: Duration) -> Self {
+        Self::with_clock(effect, duration, SystemClock)
+    }
+}
+
+impl<C: Clock> Transition<C> {
+    /// 指定した時計を利用して、現在の時刻から切り替えを始めます。
+    pub fn with_clock(effect: Effect, duration: Duration, clock: C) -> Self {
+        Self {
+            effect,
+            duration,
+            started: clock.now(),
+            clock,
+        }
+    }
+
+    /// 切り替えの進み具合を 0.0 から 1.0 の範囲で取得します。
+    pub fn progress(&self) -> f64 {
+        if self.duration.is_zero() {
+            return 1.0;
+        }
+        let elapsed = self.clock.now().saturating_duration_since(self.started);
+        (elapsed.as_secs_f64() / self.duration.as_secs_f64()).min(1.0)
+    }
+
+    /// 切り替えを終えたかどうかを判定します。
+    pub fn is_finished(&self) -> bool {
+        self.progress() >= 1.0
+    }
+
+    /// 現在の進み具合で、切り替えの途中の画像を合成します。 ( `Effect::draw` を参照)
+    pub fn draw(&self, from: &[u32], to: &[u32], out: &mut [u32], width: usize, bg: u32) {
+        self.effect.draw(from, to, out, width, self.progress(), bg);
+    }
+}
@@ -0,0 +1,278 @@
+//! キャラクターを画面の下端や他のウィンドウの上に沿って歩かせるための機能です。
+//!
+//! ウィンドウを1つの矩形として扱い、作業領域の端で向きを変えながら一定の速さで横へ動かします。
+//! 足場 (他のウィンドウの上端) を指定した場合は、その上まで登って少しの間だけ座ります。
+//! 物理演算と同じく経過時間を一定の間隔 (`physics::STEP`) に区切って計算します。
+
+use std::time::{Duration, Instant};
+
+use crate::{
+    hit::Rect,
+    physics::STEP,
+    timer::{Clock, SystemClock},
+};
+
+/// 歩く速さ (ピクセル/秒) のデフォルト値です。
+pub const DEFAULT_SPEED: f64 = 60.0;
+
+/// 足場の上に座っている時間のデフォルト値です。
+pub const DEFAULT_SIT: Duration = Duration::from_secs(5);
+
+/// 歩く向きです。
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Direction {
+    Left,
+    Right,
+}
+
+/// 今の動きです。
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Motion {
+    /// 横へ歩いています。
+    Walking,
+    /// 足場へ登っている (または降りている) 途中です。
+    Climbing,
+    /// 足場の上に座っています。
+    Sitting,
+}
+
+/// 歩き方の設定です。
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Settings {
+    /// 歩く速さ (ピクセル/秒、登る速さも同じ)
+    pub speed: f64,
+    /// 他のウィンドウの上に登るかどうか
+    pub climb: bool,
+    /// 足場の上に座っている時間
+    pub sit: Duration,
+}
+
+impl Default for Settings {
+    fn default() -> Self {
+        Self {
+            speed: DEFAULT_SPEED,
+            climb: false,
+            sit: DEFAULT_SIT,
+        }
+    }
+}
+
+/// 歩かせる矩形 (ウィンドウ) です。
+///
+/// ```rust
+/// use bkrbk::hit::Rect;
+/// use bkrbk::timer::MockClock;
+/// use bkrbk::walk::{Direction, Motion, Settings, Walker};
+/// use std::time::Duration;
+///
+/// let area = Rect { x: 0, y: 0, width: 400, height: 300 };
+/// let settings = Settings { speed: 100.0, climb: true, sit: Duration::from_secs(1) };
+/// let clock = MockClock::new();
+/// let mut walker = Walker::with_clock(settings, area, (50, 100), clock.clone());
+/// walker.set_position(0, 200);
+///
+/// // 1秒で 100px 進む
+/// clock.advance(Duration::from_secs(1));
+/// walker.update();
+/// assert_eq!(walker.position(), (100, 200));
+///
+/// // 右端で向きを変える
+/// clock.advance(Duration::from_secs(3));
+/// walker.update();
+/// assert_eq!(walker.direction(), Direction::Left);
+/// assert_eq!(walker.position(), (300, 200));
+///
+/// // 足場があれば登って座る
+/// walker.set_platforms(vec![Rect { x: 0, y: 250, width: 200, height: 50 }]);
+/// clock.advance(Duration::from_millis(1500));
+/// walker.update();
+/// assert_eq!(walker.motion(), Motion::Climbing);
+/// clock.advance(Duration::from_millis(500));
+/// walker.update();
+/// assert_eq!(walker.motion(), Motion::Sitting);
+/// assert_eq!(walker.position(), (175, 150));
+///
+/// // 座り終えたら再び歩く
+/// clock.advance(Duration::from_secs(1));
+/// walker.update();
+/// assert_eq!(walker.motion(), Motion::Walking);
+/// ```
+#[derive(Debug, Clone)]
+pub struct Walker<C: Clock = SystemClock> {
+    /// 歩き方の設定
+    pub settings: Settings,
+    /// 歩ける範囲 (作業領域)
+    area: Rect,
+    /// 矩形の大きさ
+    size: (u32, u32),
+    /// 足場 (他のウィンドウ)
+    platforms: Vec<Rect>,
+    /// 位置 (左上の座標)
+    position: (f64, f64),
+    direction: Direction,
+    motion: Motion,
+    /// 座り終える時刻までの残り時間
+    sitting: Duration,
+    /// 最後に計算した時刻
+    last: Instant,
+    /// まだ計算していない経過時間
+    pending: Duration,
+    clock: C,
+}
+
+impl Walker {
+    /// 指定した範囲を歩く Walker 構造体を初期化して返します。
+    pub fn new(settings: Settings, area: Rect, size: (u32, u32)) -> Self {
+        Self::with_clock(settings, area, size, SystemClock)
+    }
+}
+
+impl<C: Clock> Walker<C> {
+    /// 指定した時計を利用して、指定した範囲を歩く Walker 構造体を初期化して返します。
+    ///
+    /// 最初は範囲の左下にいて、右へ歩きます。
+    pub fn with_clock(settings: Settings, area: Rect, size: (u32, u32), clock: C) -> Self {
+        Self {
+            settings,
+            area,
+            size,
+            platforms: Vec::new(),
+            position: (
+                area.x as f64,
+                (area.y + area.height as i32 - size.1 as i32) as f64,
+            ),
+            direction: Direction::Right,
+            motion: Motion::Walking,
+            sitting: Duration::ZERO,
+            last: clock.now(),
+            pending: Duration::ZERO,
+            clock,
+        }
+    }
+
+    /// 位置 (左上の座標) を取得します。
+    pub fn position(&self) -> (i32, i32) {
+        (
+            self.position.0.round() as i32,
+            self.position.1.round() as i32,
+        )
+    }
+
+    /// 歩く向きを取得します。
+    pub fn direction(&self) -> Direction {
+        self.direction
+    }
+
+    /// 今の動きを取得します。
+    pub fn motion(&self) -> Motion {
+        self.motion
+    }
+
+    /// 歩ける範囲を変更します。
+    pub fn set_area(&mut self, area: Rect) {
+        self.area = area;
+    }
+
+    /// 足場 (他のウィンドウの位置と大きさ) を変更します。 (登らない設定では利用しません)
+    pub fn set_platforms(&mut self, platforms: Vec<Rect>) {
+        self.platforms = platforms;
+    }
+
+    /// 位置 (左上の座標) を変更します。
+    pub fn set_position(&mut self, x: i32, y: i32) {
+        self.position = (x as f64, y as f64);
+    }
+
+    /// 前回からの経過時間を捨てます。 (止めていた間の分だけ一度に進まないように、止めている間は毎回呼び出します)
+    pub fn pause(&mut self) {
+        self.last = self.clock.now();
+        self.pending = Duration::ZERO;
+    }
+
+    /// 前回から経過した時間だけ計算を進めます。
+    ///
+    /// 位置が変わった場合は true を返します。
+    pub fn update(&mut self) -> bool {
+        let now = self.clock.now();
+        self.pending += now.saturating_duration_since(self.last);
+        self.last = now;
+        let before = self.position();
+        while self.pending >= STEP {
+            self.pending -= STEP;
+            self.step(STEP);
+        }
+        self.position() != before
+    }
+
+    /// 指定した横の位置で立つことのできる高さ (矩形の上端の座標) を求めます。
+    ///
+    /// 中央の真下にある最も高い足場の上に立ちます。 (上に矩形が収まらない足場は除きます)
+    fn ground(&self, x: f64) -> f64 {
+        let top = self.area.y as f64;
+        let floor = self.ground_floor();
+        if !self.settings.climb {
+            return floor;
+        }
+        let center = x + self.size.0 as f64 / 2.0;
+        self.platforms
+            .iter()
+            .filter(|p| p.x as f64 <= center && center < p.x as f64 + p.width as f64)
+            .map(|p| p.y as f64 - self.size.1 as f64)
+            .filter(|y| *y >= top && *y < floor)
+            .fold(floor, f64::min)
+    }
+
+    /// 計算を `dt` だけ進めます。
+    fn step(&mut self, dt: Duration) {
+        if self.motion == Motion::Sitting {
+            self.sitting = self.sitting.saturating_sub(dt);
+            if self.sitting.is_zero() {
+                self.motion = Motion::Walking;
+            }
+            return;
+        }
+        let distance = self.settings.speed * dt.as_secs_f64();
+        let (mut x, mut y) = self.position;
+
+        // 足場の高さと違う場合は、横には進まずに登る (または降りる)
+        let ground = self.ground(x);
+        if y != ground {
+            y = match y < ground {
+                true => (y + distance).min(ground),
+                false => (y - distance).max(ground),
+            };
+            self.motion = Motion::Climbing;
+            if y == ground {
+                self.motion = Motion::Walking;
+                // 床より高い足場に登った場合は少しの間座る
+                if ground < self.ground_floor() && !self.settings.sit.is_zero() {
+                    self.motion = Motion::Sitting;
+                    self.sitting = self.settings.sit;
+                }
+            }
+            self.position = (x, y);
+            return;
+        }
+
+        let left = self.area.x as f64;
+        let right = (left + self.area.width as f64 - self.size.0 as f64).max(left);
+        x += match self.direction {
+            Direction::Left => -distance,
+            Direction::Right => distance,
+        };
+        if x <= left {
+            x = left;
+            self.direction = Direction::Right;
+        } else if x >= right {
+            x = right;
+            self.direction = Direction::Left;
+        }
+        self.motion = Motion::Walking;
+        self.position = (x, y);
+    }
+
+    /// 作業領域の下端に立つ場合の高さ (矩形の上端の座標) を求めます。
+    fn ground_floor(&self) -> f64 {
+        self.area.y as f64 + self.area.height as f64 - self.size.1 as f64
+    }
+}
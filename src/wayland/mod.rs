@@ -0,0 +1,709 @@
+//! Waylandに依存する操作を行うための機能です。
+//!
+//! `wlr-layer-shell` プロトコルを利用して、キャラクターを最前面のオーバーレイとして表示します。
+//!
+//! * 画像は共有メモリ ( `wl_shm` ) のバッファで受け渡します。
+//! * 入力範囲をキャラクターの不透明な部分に限定し、それ以外のクリックは透過します。
+//! * 表示位置は画面の端からの余白 (margin) で指定します。移動する場合は左上の端を基準にします。
+//! * 画面上の位置は最初の出力 ( `wl_output` ) の左上を原点とした論理座標です。
+//!   他のサーフェスの位置やサーフェスの外にあるポインターの位置は取得できません。
+//!
+//! ディスプレイが無い環境ではヘッドレスのコンポジタ上で動作を確認できます。 ( `scripts/headless-test.sh` を参照)
+//! ```bash
+//! WLR_BACKENDS=headless sway &
+//! WAYLAND_DISPLAY=wayland-1 cargo run share/jump.gif
+//! ```
+
+use std::{fs::File, os::fd::AsFd, os::unix::fs::FileExt};
+
+use crate::{
+    backend::PointerEvent,
+    hit::{Mask, Rect},
+};
+
+use wayland_client::{
+    backend::WaylandError,
+    delegate_noop,
+    globals::{registry_queue_init, BindError, GlobalError, GlobalListContents},
+    protocol::{
+        wl_buffer::{self, WlBuffer},
+        wl_compositor::WlCompositor,
+        wl_output::{self, WlOutput},
+        wl_pointer::{self, WlPointer},
+        wl_region::WlRegion,
+        wl_registry::WlRegistry,
+        wl_seat::{self, WlSeat},
+        wl_shm::{self, WlShm},
+        wl_shm_pool::WlShmPool,
+        wl_surface::WlSurface,
+    },
+    ConnectError, Connection, Dispatch, DispatchError, EventQueue, QueueHandle, WEnum,
+};
+pub use wayland_protocols_wlr::layer_shell::v1::client::zwlr_layer_surface_v1::Anchor;
+use wayland_protocols_wlr::layer_shell::v1::client::{
+    zwlr_layer_shell_v1::{self, ZwlrLayerShellV1},
+    zwlr_layer_surface_v1::{self, KeyboardInteractivity, ZwlrLayerSurfaceV1},
+};
+
+/// Waylandの操作で発生するエラーです。
+#[derive(Debug)]
+pub enum Error {
+    /// コンポジタに接続できませんでした。
+    Connect(ConnectError),
+    /// グローバルオブジェクトの一覧を取得できませんでした。
+    Global(GlobalError),
+    /// 必要なグローバルオブジェクトがコンポジタにありません。
+    Bind(BindError),
+    /// イベントの処理に失敗しました。
+    Dispatch(DispatchError),
+    /// コンポジタとの通信に失敗しました。
+    Wayland(WaylandError),
+    /// 共有メモリの作成に失敗しました。
+    Io(std::io::Error),
+}
+
+impl std::fmt::Display for Error {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Connect(e) => write!(f, "failed to connect to Wayland compositor: {}", e),
+            Self::Global(e) => write!(f, "failed to list Wayland globals: {}", e),
+            Self::Bind(e) => write!(f, "required Wayland global is missing: {}", e),
+            Self::Dispatch(e) => write!(f, "Wayland dispatch error: {}", e),
+            Self::Wayland(e) => write!(f, "Wayland connection error: {}", e),
+            Self::Io(e) => write!(f, "failed to create shared memory: {}", e),
+        }
+    }
+}
+
+impl std::error::Error for Error {}
+
+impl From<ConnectError> for Error {
+    fn from(value: ConnectError) -> Self {
+        Self::Connect(value)
+    }
+}
+
+impl From<GlobalError> for Error {
+    fn from(value: GlobalError) -> Self {
+        Self::Global(value)
+    }
+}
+
+impl From<BindError> for Error {
+    fn from(value: BindError) -> Self {
+        Self::Bind(value)
+    }
+}
+
+impl From<DispatchError> for Error {
+    fn from(value: DispatchError) -> Self {
+        Self::Dispatch(value)
+    }
+}
+
+impl From<WaylandError> for Error {
+    fn from(value: WaylandError) -> Self {
+        Self::Wayland(value)
+    }
+}
+
+impl From<std::io::Error> for Error {
+    fn from(value: std::io::Error) -> Self {
+        Self::Io(value)
+    }
+}
+
+/// バッファの数 (ダブルバッファリング)
+const BUFFER_COUNT: usize = 2;
+
+/// マウスの左ボタン (Linux の入力イベントのコード `BTN_LEFT` )
+const BUTTON_LEFT: u32 = 0x110;
+
+/// コンポジタから受け取ったイベントの状態です。
+#[derive(Default)]
+struct State {
+    /// レイヤーサーフェスの設定が完了したかどうか
+    configured: bool,
+    /// コンポジタによってサーフェスが閉じられたかどうか
+    closed: bool,
+    /// コンポジタがバッファを利用中かどうか
+    busy: [bool; BUFFER_COUNT],
+    /// ポインター (シートがポインターを持つ場合のみ)
+    pointer: Option<WlPointer>,
+    /// ポインターの位置 (サーフェスの左上が原点)
+    position: (i32, i32),
+    /// 左ボタンを押しているかどうか
+    pressed: bool,
+    /// まだ取り出していないポインターの操作
+    events: Vec<PointerEvent>,
+    /// 出力の現在の解像度 (画素)
+    mode: Option<(i32, i32)>,
+    /// 出力が90度または270度回転しているかどうか
+    rotated: bool,
+    /// 出力の拡大率
+    scale: i32,
+}
+
+impl State {
+    /// 出力の論理的な大きさ (サーフェスの座標での大きさ) を取得します。
+    fn output_size(&self) -> Option<(i32, i32)> {
+        let (width, height) = self.mode?;
+        let (width, height) = match self.rotated {
+            true => (height, width),
+            false => (width, height),
+        };
+        let scale = self.scale.max(1);
+        Some((width / scale, height / scale))
+    }
+}
+
+/// キャラクターを表示するウィンドウ (レイヤーサーフェス) です。
+pub struct Window {
+    connection: Connection,
+    queue: EventQueue<State>,
+    state: State,
+    compositor: WlCompositor,
+    surface: WlSurface,
+    layer_surface: ZwlrLayerSurfaceV1,
+    pool: WlShmPool,
+    buffers: Vec<WlBuffer>,
+    /// 共有メモリの実体
+    file: File,
+    width: u32,
+    height: u32,
+    /// 位置の基準にする画面の端
+    anchor: Anchor,
+    /// 画面の端からの余白 (上, 右, 下, 左)
+    margin: [i32; 4],
+    /// 画面に描画する情報です。
+    pub image: Vec<u32>,
+    pub is_hide: bool,
+
+    /// 透過色
+    pub bg: u32,
+}
+
+impl Window {
+    /// ウィンドウを作成します。
+    ///
+    /// `anchor` で指定した画面の端から `margin` だけ離れた位置に表示します。
+    /// 作成直後のウィンドウは非表示です。表示するには `show()` を呼び出してください。
+    /// ```rust,no_run
+    /// use bkrbk::wayland::{Anchor, Window};
+    /// let anchor = Anchor::Bottom | Anchor::Right;
+    /// let mut window = Window::new("bkrbk", 120, 180, 0x00_00_00_FF, anchor, 16).unwrap();
+    /// window.show().unwrap();
+    /// window.image.fill(0x00_FF_00_00);
+    /// window.draw().unwrap();
+    /// ```
+    pub fn new(
+        name: &str,
+        width: u32,
+        height: u32,
+        bg: u32,
+        anchor: Anchor,
+        margin: i32,
+    ) -> Result<Self, Error> {
+        let connection = Connection::connect_to_env()?;
+        let (globals, mut queue) = registry_queue_init::<State>(&connection)?;
+        let qh = queue.handle();
+
+        let compositor: WlCompositor = globals.bind(&qh, 1..=4, ())?;
+        let shm: WlShm = globals.bind(&qh, 1..=1, ())?;
+        let layer_shell: ZwlrLayerShellV1 = globals.bind(&qh, 1..=4, ())?;
+        // ポインターはシートの機能 (capabilities) を受け取った後に取得する
+        let _: Option<WlSeat> = globals.bind(&qh, 1..=5, ()).ok();
+        // 位置の計算に利用するため、表示する出力を決めておく
+        let output: Option<WlOutput> = globals.bind(&qh, 1..=3, ()).ok();
+
+        let surface = compositor.create_surface(&qh, ());
+        let layer_surface = layer_shell.get_layer_surface(
+            &surface,
+            output.as_ref(),
+            zwlr_layer_shell_v1::Layer::Overlay,
+            name.to_string(),
+            &qh,
+            (),
+        );
+        layer_surface.set_size(width, height);
+        layer_surface.set_anchor(anchor);
+        layer_surface.set_margin(margin, margin, margin, margin);
+        layer_surface.set_keyboard_interactivity(KeyboardInteractivity::None);
+        // 他のサーフェスの配置に影響を与えない
+        layer_surface.set_exclusive_zone(-1);
+
+        // 描画するまではクリック不可にする (入力範囲を空にする)
+        let region = compositor.create_region(&qh, ());
+        surface.set_input_region(Some(&region));
+        region.destroy();
+
+        // 共有メモリの確保
+        let stride = width as i32 * 4;
+        let size = stride * height as i32;
+        let file = create_shm_file((size as usize * BUFFER_COUNT) as u64)?;
+        let pool = shm.create_pool(file.as_fd(), size * BUFFER_COUNT as i32, &qh, ());
+        let buffers = (0..BUFFER_COUNT)
+            .map(|i| {
+                pool.create_buffer(
+                    size * i as i32,
+                    width as i32,
+                    height as i32,
+                    stride,
+                    wl_shm::Format::Argb8888,
+                    &qh,
+                    i,
+                )
+            })
+            .collect();
+
+        let mut state = State {
+            scale: 1,
+            ..Default::default()
+        };
+        queue.roundtrip(&mut state)?;
+
+        Ok(Self {
+            connection,
+            queue,
+            state,
+            compositor,
+            surface,
+            layer_surface,
+            pool,
+            buffers,
+            file,
+            width,
+            height,
+            anchor,
+            margin: [margin; 4],
+            image: vec![bg; (width * height) as usize],
+            is_hide: true,
+            bg,
+        })
+    }
+
+    /// 画面の更新を行います。
+    ///
+    /// * 返り値がSomeだった場合の真理値はメッセージの取得有無を表します。
+    /// * 返り値がNoneだった場合はアプリケーションの終了を表します。
+    pub fn update(&mut self) -> Option<bool> {
+        self.connection.flush().ok()?;
+        if let Some(guard) = self.queue.prepare_read() {
+            match guard.read() {
+                Ok(_) => {}
+                Err(WaylandError::Io(e)) if e.kind() == std::io::ErrorKind::WouldBlock => {}
+                Err(_) => return None,
+            }
+        }
+        let count = self.queue.dispatch_pending(&mut self.state).ok()?;
+        match self.state.closed {
+            true => None,
+            false => Some(count > 0),
+        }
+    }
+
+    /// 画像をウィンドウに描画します。
+    ///
+    /// コンポジタが全てのバッファを利用中の場合は何もしません。
+    pub fn draw(&mut self) -> Result<(), Error> {
+        if self.is_hide || !self.state.configured {
+            return Ok(());
+        }
+        let Some(index) = self.state.busy.iter().position(|busy| !busy) else {
+            return Ok(());
+        };
+
+        let data: Vec<u8> = self
+            .image
+            .iter()
+            .map(|&pixel| match pixel == self.bg {
+                true => 0,
+                false => 0xFF_00_00_00 | pixel,
+            })
+            .flat_map(u32::to_le_bytes)
+            .collect();
+        self.file.write_all_at(&data, (data.len() * index) as u64)?;
+
+        self.surface.attach(Some(&self.buffers[index]), 0, 0);
+        self.surface
+            .damage_buffer(0, 0, self.width as i32, self.height as i32);
+        self.surface.commit();
+        self.state.busy[index] = true;
+        self.connection.flush()?;
+        Ok(())
+    }
+
+    /// ウィンドウを表示します。
+    pub fn show(&mut self) -> Result<(), Error> {
+        if !self.state.configured {
+            // バッファを添付せずにコミットすると設定 (configure) が送られてくる
+            self.surface.commit();
+            while !self.state.configured && !self.state.closed {
+                self.queue.blocking_dispatch(&mut self.state)?;
+            }
+        }
+        self.is_hide = false;
+        self.draw()
+    }
+
+    /// ウィンドウの画面上の位置 (左上の座標) を取得します。
+    ///
+    /// 左上の端を基準にしていない場合は出力の大きさから計算します。 (大きさが分からない場合は None を返します)
+    pub fn position(&self) -> Option<(i32, i32)> {
+        let [top, right, bottom, left] = self.margin;
+        let output = self.state.output_size();
+        let axis =
+            |start: Anchor, end: Anchor, near: i32, far: i32, size: u32, total: Option<i32>| {
+                match (self.anchor.contains(start), self.anchor.contains(end)) {
+                    (true, false) => Some(near),
+                    (false, true) => Some(total? - size as i32 - far),
+                    // 両端を基準にした場合や基準にしていない場合は中央に配置される
+                    _ => Some((total? + near - far - size as i32) / 2),
+                }
+            };
+        Some((
+            axis(
+                Anchor::Left,
+                Anchor::Right,
+                left,
+                right,
+                self.width,
+                output.map(|o| o.0),
+            )?,
+            axis(
+                Anchor::Top,
+                Anchor::Bottom,
+                top,
+                bottom,
+                self.height,
+                output.map(|o| o.1),
+            )?,
+        ))
+    }
+
+    /// ウィンドウを配置できる範囲 (出力全体) を取得します。
+    ///
+    /// 他のサーフェスの配置に影響を与えないように表示しているため、パネルなどの範囲も含みます。
+    pub fn work_area(&self) -> Option<Rect> {
+        let (width, height) = self.state.output_size()?;
+        Some(Rect {
+            x: 0,
+            y: 0,
+            width: width as u32,
+            height: height as u32,
+        })
+    }
+
+    /// ウィンドウを画面上の指定した位置 (左上の座標) へ移動します。
+    ///
+    /// 以降は画面の左上の端を基準にします。表示中の場合はすぐに反映し、非表示の場合は次に表示した時に反映します。
+    pub fn move_to(&mut self, x: i32, y: i32) -> Result<(), Error> {
+        self.anchor = Anchor::Top | Anchor::Left;
+        self.margin = [y, 0, 0, x];
+        self.layer_surface.set_anchor(self.anchor);
+        self.layer_surface.set_margin(y, 0, 0, x);
+        if !self.is_hide && self.state.configured {
+            self.surface.commit();
+            self.connection.flush()?;
+        }
+        Ok(())
+    }
+
+    /// 入力を受け付ける範囲を、指定した不透明な部分に限定します。 (次に描画した時に反映されます)
+    pub fn set_input_mask(&mut self, mask: &Mask) {
+        let region = self.compositor.create_region(&self.queue.handle(), ());
+        for rect in mask.rectangles() {
+            region.add(rect.x, rect.y, rect.width as i32, rect.height as i32);
+        }
+        self.surface.set_input_region(Some(&region));
+        region.destroy();
+    }
+
+    /// ウィンドウを隠します。
+    pub fn hide(&mut self) -> Result<(), Error> {
+        // バッファを外すとサーフェスは作成直後の状態に戻る
+        self.surface.attach(None, 0, 0);
+        self.surface.commit();
+        self.connection.flush()?;
+        self.state.configured = false;
+        self.is_hide = true;
+        Ok(())
+    }
+}
+
+impl Drop for Window {
+    /// 以下の解放処理を行います。
+    ///
+    /// 対象 | 役割
+    /// --- | ---
+    /// buffer | 画像の受け渡しに利用。
+    /// pool | 共有メモリの管理に利用。
+    /// layer_surface, surface | キャラクターを表示する際に利用。
+    fn drop(&mut self) {
+        for buffer in self.buffers.iter() {
+            buffer.destroy();
+        }
+        self.pool.destroy();
+        self.layer_surface.destroy();
+        self.surface.destroy();
+        let _ = self.connection.flush();
+    }
+}
+
+impl crate::backend::Backend for Window {
+    fn update(&mut self) -> Option<bool> {
+        Window::update(self)
+    }
+
+    fn image(&mut self) -> &mut [u32] {
+        &mut self.image
+    }
+
+    fn draw(&mut self) {
+        let result = Window::draw(self);
+        self.check(result);
+    }
+
+    fn show(&mut self) {
+        let result = Window::show(self);
+        self.check(result);
+    }
+
+    fn hide(&mut self) {
+        let result = Window::hide(self);
+        self.check(result);
+    }
+
+    fn size(&self) -> (u32, u32) {
+        (self.width, self.height)
+    }
+
+    fn position(&self) -> Option<(i32, i32)> {
+        Window::position(self)
+    }
+
+    fn work_area(&self) -> Option<Rect> {
+        Window::work_area(self)
+    }
+
+    fn move_to(&mut self, x: i32, y: i32) {
+        let result = Window::move_to(self, x, y);
+        self.check(result);
+    }
+
+    /// サーフェスの左上を原点とした座標を、画面上の座標に変換して返します。
+    fn pointer_events(&mut self) -> Vec<PointerEvent> {
+        let (x, y) = self.position().unwrap_or_default();
+        std::mem::take(&mut self.state.events)
+            .into_iter()
+            .map(|event| match event {
+                PointerEvent::Press(px, py) => PointerEvent::Press(x + px, y + py),
+                PointerEvent::Move(px, py) => PointerEvent::Move(x + px, y + py),
+                PointerEvent::Release(px, py) => PointerEvent::Release(x + px, y + py),
+            })
+            .collect()
+    }
+
+    /// 同じコンポジタに、入力を受け付けない別のレイヤーサーフェスを作成します。 (左上の端を基準にします)
+    fn overlay(&self, width: u16, height: u16) -> Option<Box<dyn crate::backend::Backend>> {
+        let anchor = Anchor::Top | Anchor::Left;
+        let window = Window::new("bkrbk-overlay", width as _, height as _, self.bg, anchor, 0);
+        Some(Box::new(window.ok()?))
+    }
+
+    fn set_input_mask(&mut self, mask: &Mask) {
+        Window::set_input_mask(self, mask);
+    }
+}
+
+impl Window {
+    /// 操作に失敗した場合はエラーを表示し、次の `update()` でアプリケーションを終了させます。
+    fn check(&mut self, result: Result<(), Error>) {
+        if let Err(e) = result {
+            eprintln!("{}", e);
+            self.state.closed = true;
+        }
+    }
+}
+
+/// 共有メモリとして利用するファイルを作成します。
+///
+/// ファイルはコンポジタにファイルディスクリプタを渡すためだけに利用するので、作成後すぐに削除します。
+fn create_shm_file(size: u64) -> Result<File, Error> {
+    let dir = std::env::var_os("XDG_RUNTIME_DIR")
+        .map(std::path::PathBuf::from)
+        .unwrap_or_else(std::env::temp_dir);
+    let path = dir.join(format!("bkrbk-shm-{}", std::process::id()));
+    let file = std::fs::OpenOptions::new()
+        .read(true)
+        .write(true)
+        .create_new(true)
+        .open(&path)?;
+    std::fs::remove_file(&path)?;
+    file.set_len(size)?;
+    Ok(file)
+}
+
+impl Dispatch<WlRegistry, GlobalListContents> for State {
+    fn event(
+        _: &mut Self,
+        _: &WlRegistry,
+        _: <WlRegistry as wayland_client::Proxy>::Event,
+        _: &GlobalListContents,
+        _: &Connection,
+        _: &QueueHandle<Self>,
+    ) {
+    }
+}
+
+impl Dispatch<WlBuffer, usize> for State {
+    /// コンポジタがバッファを使い終わった際の処理です。
+    fn event(
+        state: &mut Self,
+        _: &WlBuffer,
+        event: wl_buffer::Event,
+        index: &usize,
+        _: &Connection,
+        _: &QueueHandle<Self>,
+    ) {
+        if let wl_buffer::Event::Release = event {
+            state.busy[*index] = false;
+        }
+    }
+}
+
+impl Dispatch<ZwlrLayerSurfaceV1, ()> for State {
+    /// レイヤーサーフェスの設定や終了の通知を受け取った際の処理です。
+    fn event(
+        state: &mut Self,
+        layer_surface: &ZwlrLayerSurfaceV1,
+        event: zwlr_layer_surface_v1::Event,
+        _: &(),
+        _: &Connection,
+        _: &QueueHandle<Self>,
+    ) {
+        match event {
+            zwlr_layer_surface_v1::Event::Configure { serial, .. } => {
+                layer_surface.ack_configure(serial);
+                state.configured = true;
+            }
+            zwlr_layer_surface_v1::Event::Closed => state.closed = true,
+            _ => {}
+        }
+    }
+}
+
+impl Dispatch<WlSeat, ()> for State {
+    /// シートの機能を受け取った際に、ポインターを取得します。
+    fn event(
+        state: &mut Self,
+        seat: &WlSeat,
+        event: wl_seat::Event,
+        _: &(),
+        _: &Connection,
+        qh: &QueueHandle<Self>,
+    ) {
+        if let wl_seat::Event::Capabilities {
+            capabilities: WEnum::Value(capabilities),
+        } = event
+        {
+            if capabilities.contains(wl_seat::Capability::Pointer) && state.pointer.is_none() {
+                state.pointer = Some(seat.get_pointer(qh, ()));
+            }
+        }
+    }
+}
+
+impl Dispatch<WlPointer, ()> for State {
+    /// ポインターの移動やボタンの操作を受け取った際の処理です。
+    ///
+    /// ボタンを押している間は、サーフェスの外に出てもイベントが届きます。
+    fn event(
+        state: &mut Self,
+        _: &WlPointer,
+        event: wl_pointer::Event,
+        _: &(),
+        _: &Connection,
+        _: &QueueHandle<Self>,
+    ) {
+        match event {
+            wl_pointer::Event::Enter {
+                surface_x,
+                surface_y,
+                ..
+            } => state.position = (surface_x as i32, surface_y as i32),
+            wl_pointer::Event::Motion {
+                surface_x,
+                surface_y,
+                ..
+            } => {
+                state.position = (surface_x as i32, surface_y as i32);
+                if state.pressed {
+                    let (x, y) = state.position;
+                    state.events.push(PointerEvent::Move(x, y));
+                }
+            }
+            wl_pointer::Event::Button {
+                button: BUTTON_LEFT,
+                state: WEnum::Value(button),
+                ..
+            } => {
+                let (x, y) = state.position;
+                match button {
+                    wl_pointer::ButtonState::Pressed => {
+                        state.pressed = true;
+                        state.events.push(PointerEvent::Press(x, y));
+                    }
+                    wl_pointer::ButtonState::Released if state.pressed => {
+                        state.pressed = false;
+                        state.events.push(PointerEvent::Release(x, y));
+                    }
+                    _ => {}
+                }
+            }
+            _ => {}
+        }
+    }
+}
+
+impl Dispatch<WlOutput, ()> for State {
+    /// 出力の解像度や向き、拡大率を受け取った際の処理です。
+    fn event(
+        state: &mut Self,
+        _: &WlOutput,
+        event: wl_output::Event,
+        _: &(),
+        _: &Connection,
+        _: &QueueHandle<Self>,
+    ) {
+        match event {
+            wl_output::Event::Geometry {
+                transform: WEnum::Value(transform),
+                ..
+            } => {
+                state.rotated = matches!(
+                    transform,
+                    wl_output::Transform::_90
+                        | wl_output::Transform::_270
+                        | wl_output::Transform::Flipped90
+                        | wl_output::Transform::Flipped270
+                );
+            }
+            wl_output::Event::Mode {
+                flags: WEnum::Value(flags),
+                width,
+                height,
+                ..
+            } if flags.contains(wl_output::Mode::Current) => state.mode = Some((width, height)),
+            wl_output::Event::Scale { factor } => state.scale = factor,
+            _ => {}
+        }
+    }
+}
+
+delegate_noop!(State: WlCompositor);
+delegate_noop!(State: WlRegion);
+delegate_noop!(State: WlShmPool);
+delegate_noop!(State: ZwlrLayerShellV1);
+delegate_noop!(State: ignore WlShm);
+delegate_noop!(State: ignore WlSurface);
@@ -3,7 +3,9 @@
 use windows::Win32::{
     Foundation::*,
     UI::{
-        Input::KeyboardAndMouse::{GetActiveWindow, SetActiveWindow},
+        Input::KeyboardAndMouse::{
+            GetActiveWindow, GetCapture, ReleaseCapture, SetActiveWindow, SetCapture,
+        },
         WindowsAndMessaging::*,
     },
 };
@@ -137,6 +139,40 @@ impl super::Core {
         LRESULT(0)
     }
 
+    /// 前面のウィンドウ (キャラクター) をマウスで操作した際の処理です。
+    ///
+    /// 透過色の部分はクリックを透過するので、ボタンを押すのは不透明な部分の上だけです。
+    /// ボタンを押している間はマウスをキャプチャして、ウィンドウの外での移動も受け取ります。
+    pub(super) fn on_pointer(window: HWND, message: u32) -> LRESULT {
+        use crate::backend::PointerEvent;
+
+        let Some(this) = Self::get_property(window) else {
+            return LRESULT(0);
+        };
+        if window != this.front.hwnd {
+            return LRESULT(0);
+        }
+        let mut point = POINT::default();
+        if unsafe { GetCursorPos(&mut point) }.is_err() {
+            return LRESULT(0);
+        }
+        let captured = unsafe { GetCapture() } == window;
+        let event = match message {
+            WM_LBUTTONDOWN => {
+                unsafe { SetCapture(window) };
+                PointerEvent::Press(point.x, point.y)
+            }
+            WM_MOUSEMOVE if captured => PointerEvent::Move(point.x, point.y),
+            WM_LBUTTONUP if captured => {
+                let _ = unsafe { ReleaseCapture() };
+                PointerEvent::Release(point.x, point.y)
+            }
+            _ => return LRESULT(0),
+        };
+        this.pointer.push(event);
+        LRESULT(0)
+    }
+
     /// 描画処理です。
     pub(super) fn on_paint(window: HWND) -> LRESULT {
         // MEMO: 必要以上に呼び出されているかも
@@ -70,6 +70,8 @@ pub struct Core {
     pub dib: dib::DIB,
     pub is_hide: bool,
     pub front: window::Window,
+    /// まだ取り出していないポインターの操作
+    pub pointer: Vec<crate::backend::PointerEvent>,
 }
 
 impl Drop for Core {
@@ -107,6 +109,7 @@ impl Core {
             dib: dib::DIB::new(w, h),
             is_hide: true,
             front: window::Window::new(name, width, height, bg),
+            pointer: Vec::new(),
         }
     }
 
@@ -256,16 +259,138 @@ impl Core {
             WM_ACTIVATE => Self::on_active(window),
             WM_MOVE => Self::on_move(window),
             WM_PAINT => Self::on_paint(window),
+            WM_LBUTTONDOWN | WM_MOUSEMOVE | WM_LBUTTONUP => Self::on_pointer(window, message),
             _ => unsafe { DefWindowProcA(window, message, wparam, lparam) },
         }
     }
 }
 
+impl crate::backend::Backend for Core {
+    fn update(&mut self) -> Option<bool> {
+        Core::update(self)
+    }
+
+    fn image(&mut self) -> &mut [u32] {
+        &mut self.front.image
+    }
+
+    fn draw(&mut self) {
+        self.front.draw();
+        self.front.call_draw();
+    }
+
+    fn show(&mut self) {
+        self.front.show(
+            self.handler,
+            self.class_name.reference,
+            &self.main_window_name.entity,
+        );
+    }
+
+    fn hide(&mut self) {
+        self.front.hide(
+            self.handler,
+            self.class_name.reference,
+            &self.main_window_name.entity,
+        );
+    }
+
+    fn size(&self) -> (u32, u32) {
+        let (width, height) = self.front.size();
+        (width as u32, height as u32)
+    }
+
+    fn position(&self) -> Option<(i32, i32)> {
+        let mut rect = RECT::default();
+        unsafe { GetWindowRect(self.handler, &mut rect) }.ok()?;
+        Some((rect.left, rect.top - window::FRONT_OFFSET))
+    }
+
+    fn pointer_events(&mut self) -> Vec<crate::backend::PointerEvent> {
+        std::mem::take(&mut self.pointer)
+    }
+
+    fn work_area(&self) -> Option<crate::hit::Rect> {
+        let mut rect = RECT::default();
+        unsafe {
+            SystemParametersInfoA(
+                SPI_GETWORKAREA,
+                0,
+                Some(&mut rect as *mut RECT as *mut std::ffi::c_void),
+                SYSTEM_PARAMETERS_INFO_UPDATE_FLAGS(0),
+            )
+        }
+        .ok()?;
+        Some(crate::hit::Rect {
+            x: rect.left,
+            y: rect.top,
+            width: (rect.right - rect.left) as u32,
+            height: (rect.bottom - rect.top) as u32,
+        })
+    }
+
+    fn other_windows(&self) -> Vec<crate::hit::Rect> {
+        let mut rects: Vec<crate::hit::Rect> = Vec::new();
+        unsafe {
+            let _ = EnumWindows(
+                Some(collect_window_rect),
+                LPARAM(&mut rects as *mut Vec<crate::hit::Rect> as isize),
+            );
+        }
+        rects
+    }
+
+    fn pointer_position(&self) -> Option<(i32, i32)> {
+        let mut point = POINT::default();
+        unsafe { GetCursorPos(&mut point) }.ok()?;
+        Some((point.x, point.y))
+    }
+
+    fn move_to(&mut self, x: i32, y: i32) {
+        // 前面のウィンドウはメインウィンドウの移動 (WM_MOVE) に合わせて移動する
+        unsafe {
+            let _ = SetWindowPos(
+                self.handler,
+                HWND(0),
+                x,
+                y + window::FRONT_OFFSET,
+                0,
+                0,
+                SWP_NOSIZE | SWP_NOZORDER | SWP_NOACTIVATE,
+            );
+        }
+    }
+}
+
 /// アプリケーションを終了するメッセージを発行します。
 pub fn quit() {
     unsafe { PostQuitMessage(0) }
 }
 
+/// 表示されている他のプロセスのウィンドウの範囲を、 `lparam` が指す一覧に追加します。 ( `EnumWindows` のコールバック関数)
+extern "system" fn collect_window_rect(window: HWND, lparam: LPARAM) -> BOOL {
+    let rects = unsafe { &mut *(lparam.0 as *mut Vec<crate::hit::Rect>) };
+    let mut process_id = 0;
+    unsafe { GetWindowThreadProcessId(window, Some(&mut process_id as *mut u32)) };
+    let visible = unsafe { IsWindowVisible(window).as_bool() && !IsIconic(window).as_bool() };
+    let has_title = unsafe { GetWindowTextLengthA(window) } > 0;
+    let mut rect = RECT::default();
+    if process_id != std::process::id()
+        && visible
+        && has_title
+        && unsafe { GetWindowRect(window, &mut rect) }.is_ok()
+    {
+        rects.push(crate::hit::Rect {
+            x: rect.left,
+            y: rect.top,
+            width: (rect.right - rect.left) as u32,
+            height: (rect.bottom - rect.top) as u32,
+        });
+    }
+    // 列挙を続ける
+    TRUE
+}
+
 /// ディスプレイの大きさを取得します。
 fn get_display_size() -> (i32, i32) {
     let width = unsafe { GetSystemMetrics(SM_CXSCREEN) };
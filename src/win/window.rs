@@ -7,6 +7,9 @@ use windows::{
     Win32::{Foundation::*, Graphics::Gdi::*, UI::WindowsAndMessaging::*},
 };
 
+/// メインウィンドウの上端から、前面のウィンドウの上端までの距離です。 (上方向が正)
+pub const FRONT_OFFSET: i32 = 27;
+
 pub struct Window {
     name: super::StringPair,
     pub hwnd: super::HWND,
@@ -37,10 +40,10 @@ impl Window {
         self.name.set_reference();
         self.hwnd = unsafe {
             CreateWindowExA(
-                // 透明度の指定を可能にする
+                // 透明度の指定を可能にする (透過色の部分はクリックも透過する)
                 WS_EX_LAYERED
-                // クリック不可にする
-                | WS_EX_TRANSPARENT | WS_EX_NOACTIVATE,
+                // クリックしてもアクティブにしない
+                | WS_EX_NOACTIVATE,
                 class_name,
                 self.name.reference,
                 WS_POPUP,
@@ -72,6 +75,11 @@ impl Window {
         }
     }
 
+    /// 描画範囲の大きさを取得します。
+    pub fn size(&self) -> (i32, i32) {
+        (self.width, self.height)
+    }
+
     pub fn call_draw(&self) {
         unsafe {
             windows::Win32::Graphics::Gdi::InvalidateRect(self.hwnd, None, false);
@@ -153,7 +161,15 @@ impl Window {
 
         unsafe {
             let sub_window = FindWindowA(class, self.name.reference);
-            MoveWindow(sub_window, x, y - 27, self.width, self.height, true).unwrap();
+            MoveWindow(
+                sub_window,
+                x,
+                y - FRONT_OFFSET,
+                self.width,
+                self.height,
+                true,
+            )
+            .unwrap();
             ShowWindow(sub_window, ncmdshow);
         }
     }
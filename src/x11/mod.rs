@@ -0,0 +1,616 @@
+//! X11に依存する操作を行うための機能です。
+//!
+//! `win::window::Window` と同様に、枠がなく、タスクバーに表示されないウィンドウを作成します。
+//!
+//! * コンポジットマネージャが動作している場合は ARGB ビジュアルを利用して透過します。
+//! * 動作していない場合は XShape 拡張で描画範囲を切り抜きます。
+//! * 入力範囲は XShape 拡張で不透明な部分に限定し、透明な部分のクリックは透過します。
+//!
+//! ディスプレイが無い環境では `Xvfb` などの仮想Xサーバー上で動作を確認できます。 ( `scripts/headless-test.sh` を参照)
+//! ```bash
+//! xvfb-run cargo run share/jump.gif
+//! ```
+
+use crate::{
+    backend::PointerEvent,
+    hit::{Mask, Rect},
+};
+
+use x11rb::{
+    connection::{Connection, RequestConnection},
+    errors::{ConnectError, ConnectionError, ReplyError, ReplyOrIdError},
+    protocol::{
+        shape::{self, ConnectionExt as _},
+        xproto::*,
+        Event,
+    },
+    rust_connection::RustConnection,
+    wrapper::ConnectionExt as _,
+    NONE,
+};
+
+/// X11の操作で発生するエラーです。
+#[derive(Debug)]
+pub enum Error {
+    /// Xサーバーに接続できませんでした。
+    Connect(ConnectError),
+    /// Xサーバーとの通信に失敗しました。
+    Connection(ConnectionError),
+    /// リクエストに対してエラーが返されました。
+    Reply(ReplyError),
+    /// リソースIDの確保に失敗しました。
+    ReplyOrId(ReplyOrIdError),
+    /// 必要な拡張機能がXサーバーにありません。
+    MissingExtension(&'static str),
+}
+
+impl std::fmt::Display for Error {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Connect(e) => write!(f, "failed to connect to X server: {}", e),
+            Self::Connection(e) => write!(f, "X connection error: {}", e),
+            Self::Reply(e) => write!(f, "X request error: {}", e),
+            Self::ReplyOrId(e) => write!(f, "X request error: {}", e),
+            Self::MissingExtension(name) => write!(f, "X extension `{}` is not available", name),
+        }
+    }
+}
+
+impl std::error::Error for Error {}
+
+impl From<ConnectError> for Error {
+    fn from(value: ConnectError) -> Self {
+        Self::Connect(value)
+    }
+}
+
+impl From<ConnectionError> for Error {
+    fn from(value: ConnectionError) -> Self {
+        Self::Connection(value)
+    }
+}
+
+impl From<ReplyError> for Error {
+    fn from(value: ReplyError) -> Self {
+        Self::Reply(value)
+    }
+}
+
+impl From<ReplyOrIdError> for Error {
+    fn from(value: ReplyOrIdError) -> Self {
+        Self::ReplyOrId(value)
+    }
+}
+
+/// 透過色を透明にする方式です。
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Transparency {
+    /// ARGB ビジュアルのアルファ値で透過します。
+    Argb,
+    /// XShape 拡張でウィンドウの形を切り抜きます。
+    Shape,
+}
+
+/// キャラクターを表示するウィンドウです。
+pub struct Window {
+    connection: RustConnection,
+    /// ルートウィンドウ (画面全体)
+    root: u32,
+    pub window: u32,
+    gc: u32,
+    colormap: u32,
+    width: u16,
+    height: u16,
+    /// ウィンドウの位置
+    x: i32,
+    y: i32,
+    depth: u8,
+    /// 透過の方式
+    pub transparency: Transparency,
+    /// 画面に描画する情報です。
+    pub image: Vec<u32>,
+    /// 最後に設定したウィンドウの形 ( `Transparency::Shape` の場合のみ利用 )
+    mask: Mask,
+    /// まだ取り出していないポインターの操作
+    pointer: Vec<PointerEvent>,
+    /// 操作に失敗したかどうか (次の `update()` でアプリケーションを終了させます)
+    failed: bool,
+    pub is_hide: bool,
+
+    /// 透過色
+    pub bg: u32,
+}
+
+impl Window {
+    /// ウィンドウを作成します。
+    ///
+    /// 作成直後のウィンドウは非表示です。表示するには `show()` を呼び出してください。
+    /// ```rust,no_run
+    /// let mut window = bkrbk::x11::Window::new("bkrbk", 120, 180, 0x00_00_00_FF).unwrap();
+    /// window.show().unwrap();
+    /// window.image.fill(0x00_FF_00_00);
+    /// window.draw().unwrap();
+    /// ```
+    pub fn new(name: &str, width: u16, height: u16, bg: u32) -> Result<Self, Error> {
+        let (connection, screen_num) = x11rb::connect(None)?;
+        if connection
+            .extension_information(shape::X11_EXTENSION_NAME)?
+            .is_none()
+        {
+            return Err(Error::MissingExtension(shape::X11_EXTENSION_NAME));
+        }
+
+        let screen = &connection.setup().roots[screen_num];
+        let root = screen.root;
+        let argb_visual = match has_compositor(&connection, screen_num)? {
+            true => find_argb_visual(screen),
+            false => None,
+        };
+        let (depth, visual, transparency) = match argb_visual {
+            Some(visual) => (32, visual, Transparency::Argb),
+            None => (screen.root_depth, screen.root_visual, Transparency::Shape),
+        };
+
+        // 描画する場所の計算 (ディスプレイの中央)
+        let x = (screen.width_in_pixels as i32 / 2 - width as i32 / 2) as i16;
+        let y = (screen.height_in_pixels as i32 / 2 - height as i32 / 2) as i16;
+
+        let window = connection.generate_id()?;
+        let colormap = connection.generate_id()?;
+        let gc = connection.generate_id()?;
+        connection.create_colormap(ColormapAlloc::NONE, colormap, root, visual)?;
+        connection.create_window(
+            depth,
+            window,
+            root,
+            x,
+            y,
+            width,
+            height,
+            0,
+            WindowClass::INPUT_OUTPUT,
+            visual,
+            &CreateWindowAux::new()
+                .background_pixel(0)
+                .border_pixel(0)
+                .colormap(colormap)
+                // ウィンドウマネージャの管理外にする (枠なし・タスクバーに表示しない)
+                .override_redirect(1)
+                .event_mask(
+                    EventMask::EXPOSURE
+                        | EventMask::STRUCTURE_NOTIFY
+                        | EventMask::BUTTON_PRESS
+                        | EventMask::BUTTON_RELEASE
+                        | EventMask::BUTTON1_MOTION,
+                ),
+        )?;
+        connection.create_gc(gc, window, &CreateGCAux::new())?;
+
+        connection.change_property8(
+            PropMode::REPLACE,
+            window,
+            AtomEnum::WM_NAME,
+            AtomEnum::STRING,
+            name.as_bytes(),
+        )?;
+        let class = format!("{0}\0{0}\0", name);
+        connection.change_property8(
+            PropMode::REPLACE,
+            window,
+            AtomEnum::WM_CLASS,
+            AtomEnum::STRING,
+            class.as_bytes(),
+        )?;
+        set_skip_taskbar(&connection, window)?;
+
+        // 描画するまではクリック不可にする (入力範囲を空にする)
+        connection.shape_rectangles(
+            shape::SO::SET,
+            shape::SK::INPUT,
+            ClipOrdering::UNSORTED,
+            window,
+            0,
+            0,
+            &[],
+        )?;
+        connection.flush()?;
+
+        Ok(Self {
+            connection,
+            root,
+            window,
+            gc,
+            colormap,
+            width,
+            height,
+            x: x as i32,
+            y: y as i32,
+            depth,
+            transparency,
+            image: vec![bg; width as usize * height as usize],
+            mask: Mask::default(),
+            pointer: Vec::new(),
+            failed: false,
+            is_hide: true,
+            bg,
+        })
+    }
+
+    /// 画面の更新を行います。
+    ///
+    /// * 返り値がSomeだった場合の真理値はメッセージの取得有無を表します。
+    /// * 返り値がNoneだった場合はアプリケーションの終了を表します。
+    pub fn update(&mut self) -> Option<bool> {
+        if self.failed {
+            return None;
+        }
+        // ポインターの移動は大量に届くので、溜まっているものをまとめて処理する
+        let mut received = false;
+        loop {
+            match self.connection.poll_for_event() {
+                Ok(Some(Event::DestroyNotify(_))) => return None,
+                Ok(Some(Event::Expose(_))) => self.draw().ok()?,
+                Ok(Some(Event::ButtonPress(e))) if e.detail == 1 => {
+                    self.pointer
+                        .push(PointerEvent::Press(e.root_x as i32, e.root_y as i32));
+                    received = true;
+                }
+                Ok(Some(Event::MotionNotify(e))) => {
+                    self.pointer
+                        .push(PointerEvent::Move(e.root_x as i32, e.root_y as i32));
+                    received = true;
+                }
+                Ok(Some(Event::ButtonRelease(e))) if e.detail == 1 => {
+                    self.pointer
+                        .push(PointerEvent::Release(e.root_x as i32, e.root_y as i32));
+                    received = true;
+                }
+                Ok(Some(_)) => received = true,
+                Ok(None) => return Some(received),
+                Err(_) => return None,
+            }
+        }
+    }
+
+    /// 画像をウィンドウに描画します。
+    pub fn draw(&mut self) -> Result<(), Error> {
+        if self.transparency == Transparency::Shape {
+            self.update_shape()?;
+        }
+
+        let big_endian = self.connection.setup().image_byte_order == ImageOrder::MSB_FIRST;
+        let data: Vec<u8> = self
+            .image
+            .iter()
+            .map(|&pixel| match self.transparency {
+                Transparency::Argb if pixel == self.bg => 0,
+                Transparency::Argb => 0xFF_00_00_00 | pixel,
+                Transparency::Shape => pixel,
+            })
+            .flat_map(|pixel| match big_endian {
+                true => pixel.to_be_bytes(),
+                false => pixel.to_le_bytes(),
+            })
+            .collect();
+
+        // 1リクエストの上限を超えないように行単位で分割して送信する
+        let stride = self.width as usize * 4;
+        let max_rows = (self.connection.maximum_request_bytes() - 32) / stride;
+        for (i, rows) in data.chunks(stride * max_rows.max(1)).enumerate() {
+            self.connection.put_image(
+                ImageFormat::Z_PIXMAP,
+                self.window,
+                self.gc,
+                self.width,
+                (rows.len() / stride) as u16,
+                0,
+                (i * max_rows) as i16,
+                0,
+                self.depth,
+                rows,
+            )?;
+        }
+        self.connection.flush()?;
+        Ok(())
+    }
+
+    /// 透過色以外の部分が表示されるようにウィンドウの形を更新します。
+    fn update_shape(&mut self) -> Result<(), Error> {
+        let mask = Mask::from_image(&self.image, self.width as usize, self.bg);
+        if mask == self.mask {
+            return Ok(());
+        }
+        self.set_shape(shape::SK::BOUNDING, &mask)?;
+        self.mask = mask;
+        Ok(())
+    }
+
+    /// 入力を受け付ける範囲を、指定した不透明な部分に限定します。
+    pub fn set_input_mask(&mut self, mask: &Mask) -> Result<(), Error> {
+        self.set_shape(shape::SK::INPUT, mask)?;
+        self.connection.flush()?;
+        Ok(())
+    }
+
+    /// ウィンドウの形 (描画範囲または入力範囲) を、不透明な部分に設定します。
+    fn set_shape(&self, kind: shape::SK, mask: &Mask) -> Result<(), Error> {
+        let rectangles: Vec<Rectangle> = mask.rectangles().iter().map(rectangle).collect();
+        self.connection.shape_rectangles(
+            shape::SO::SET,
+            kind,
+            ClipOrdering::YX_BANDED,
+            self.window,
+            0,
+            0,
+            &rectangles,
+        )?;
+        Ok(())
+    }
+
+    /// ウィンドウを表示します。
+    pub fn show(&mut self) -> Result<(), Error> {
+        self.connection.map_window(self.window)?;
+        self.connection.configure_window(
+            self.window,
+            &ConfigureWindowAux::new().stack_mode(StackMode::ABOVE),
+        )?;
+        self.connection.flush()?;
+        self.is_hide = false;
+        Ok(())
+    }
+
+    /// ウィンドウを隠します。
+    pub fn hide(&mut self) -> Result<(), Error> {
+        self.connection.unmap_window(self.window)?;
+        self.connection.flush()?;
+        self.is_hide = true;
+        Ok(())
+    }
+
+    /// ウィンドウを配置できる範囲を取得します。
+    ///
+    /// ウィンドウマネージャが `_NET_WORKAREA` を設定していない場合は画面全体を返します。
+    pub fn work_area(&self) -> Result<Rect, Error> {
+        let atom = self
+            .connection
+            .intern_atom(false, b"_NET_WORKAREA")?
+            .reply()?
+            .atom;
+        let reply = self
+            .connection
+            .get_property(false, self.root, atom, AtomEnum::CARDINAL, 0, 4)?
+            .reply()?;
+        // 最初の仮想デスクトップの範囲 (x, y, 幅, 高さ) を利用する
+        if let Some([x, y, width, height]) = reply
+            .value32()
+            .and_then(|values| <[u32; 4]>::try_from(values.collect::<Vec<_>>()).ok())
+        {
+            return Ok(Rect {
+                x: x as i32,
+                y: y as i32,
+                width,
+                height,
+            });
+        }
+        let geometry = self.connection.get_geometry(self.root)?.reply()?;
+        Ok(Rect {
+            x: 0,
+            y: 0,
+            width: geometry.width as u32,
+            height: geometry.height as u32,
+        })
+    }
+
+    /// 他のアプリケーションのウィンドウ (枠を含む) の位置と大きさの一覧を取得します。
+    ///
+    /// ウィンドウマネージャが `_NET_CLIENT_LIST` を設定していない場合は空です。
+    /// 一覧を取得した後に閉じられたウィンドウは除きます。
+    pub fn other_windows(&self) -> Result<Vec<Rect>, Error> {
+        let client_list = self
+            .connection
+            .intern_atom(false, b"_NET_CLIENT_LIST")?
+            .reply()?
+            .atom;
+        let extents = self
+            .connection
+            .intern_atom(false, b"_NET_FRAME_EXTENTS")?
+            .reply()?
+            .atom;
+        let reply = self
+            .connection
+            .get_property(false, self.root, client_list, AtomEnum::WINDOW, 0, 1024)?
+            .reply()?;
+        let windows: Vec<u32> = reply
+            .value32()
+            .map(|values| values.collect())
+            .unwrap_or_default();
+        Ok(windows
+            .into_iter()
+            .filter(|window| *window != self.window)
+            .filter_map(|window| self.frame(window, extents).ok().flatten())
+            .collect())
+    }
+
+    /// 表示されているウィンドウの、枠 (`_NET_FRAME_EXTENTS`) を含めた画面上の範囲を取得します。
+    fn frame(&self, window: u32, extents: u32) -> Result<Option<Rect>, Error> {
+        let attributes = self.connection.get_window_attributes(window)?.reply()?;
+        if attributes.map_state != MapState::VIEWABLE {
+            return Ok(None);
+        }
+        let geometry = self.connection.get_geometry(window)?.reply()?;
+        let origin = self
+            .connection
+            .translate_coordinates(window, self.root, 0, 0)?
+            .reply()?;
+        let [left, right, top, bottom] = self
+            .connection
+            .get_property(false, window, extents, AtomEnum::CARDINAL, 0, 4)?
+            .reply()?
+            .value32()
+            .and_then(|values| <[u32; 4]>::try_from(values.collect::<Vec<_>>()).ok())
+            .unwrap_or_default();
+        Ok(Some(Rect {
+            x: origin.dst_x as i32 - left as i32,
+            y: origin.dst_y as i32 - top as i32,
+            width: geometry.width as u32 + left + right,
+            height: geometry.height as u32 + top + bottom,
+        }))
+    }
+
+    /// ポインターの画面上の位置を取得します。
+    pub fn pointer_position(&self) -> Result<(i32, i32), Error> {
+        let reply = self.connection.query_pointer(self.root)?.reply()?;
+        Ok((reply.root_x as i32, reply.root_y as i32))
+    }
+
+    /// ウィンドウを指定した位置へ移動します。
+    pub fn move_to(&mut self, x: i32, y: i32) -> Result<(), Error> {
+        self.connection
+            .configure_window(self.window, &ConfigureWindowAux::new().x(x).y(y))?;
+        self.connection.flush()?;
+        (self.x, self.y) = (x, y);
+        Ok(())
+    }
+}
+
+impl Drop for Window {
+    /// 以下の解放処理を行います。
+    ///
+    /// 対象 | 役割
+    /// --- | ---
+    /// gc | 画像の転送に利用。
+    /// colormap | ウィンドウのビジュアルに合わせて作成。
+    /// window | ウインドウを扱う際に利用。
+    fn drop(&mut self) {
+        let _ = self.connection.free_gc(self.gc);
+        let _ = self.connection.destroy_window(self.window);
+        let _ = self.connection.free_colormap(self.colormap);
+        let _ = self.connection.flush();
+    }
+}
+
+impl crate::backend::Backend for Window {
+    fn update(&mut self) -> Option<bool> {
+        Window::update(self)
+    }
+
+    fn image(&mut self) -> &mut [u32] {
+        &mut self.image
+    }
+
+    fn draw(&mut self) {
+        let result = Window::draw(self);
+        self.check(result);
+    }
+
+    fn show(&mut self) {
+        let result = Window::show(self);
+        self.check(result);
+    }
+
+    fn hide(&mut self) {
+        let result = Window::hide(self);
+        self.check(result);
+    }
+
+    fn size(&self) -> (u32, u32) {
+        (self.width as u32, self.height as u32)
+    }
+
+    fn position(&self) -> Option<(i32, i32)> {
+        Some((self.x, self.y))
+    }
+
+    fn work_area(&self) -> Option<Rect> {
+        Window::work_area(self).ok()
+    }
+
+    fn other_windows(&self) -> Vec<Rect> {
+        Window::other_windows(self).unwrap_or_default()
+    }
+
+    fn move_to(&mut self, x: i32, y: i32) {
+        let result = Window::move_to(self, x, y);
+        self.check(result);
+    }
+
+    fn pointer_events(&mut self) -> Vec<PointerEvent> {
+        std::mem::take(&mut self.pointer)
+    }
+
+    fn pointer_position(&self) -> Option<(i32, i32)> {
+        Window::pointer_position(self).ok()
+    }
+
+    fn overlay(&self, width: u16, height: u16) -> Option<Box<dyn crate::backend::Backend>> {
+        let window = Window::new("bkrbk-overlay", width, height, self.bg).ok()?;
+        Some(Box::new(window))
+    }
+
+    fn set_input_mask(&mut self, mask: &Mask) {
+        let result = Window::set_input_mask(self, mask);
+        self.check(result);
+    }
+}
+
+impl Window {
+    /// 操作に失敗した場合はエラーを表示し、次の `update()` でアプリケーションを終了させます。
+    fn check(&mut self, result: Result<(), Error>) {
+        if let Err(e) = result {
+            eprintln!("{}", e);
+            self.failed = true;
+        }
+    }
+}
+
+/// コンポジットマネージャが動作しているかを確認します。
+fn has_compositor(connection: &RustConnection, screen_num: usize) -> Result<bool, Error> {
+    let name = format!("_NET_WM_CM_S{}", screen_num);
+    let atom = connection
+        .intern_atom(false, name.as_bytes())?
+        .reply()?
+        .atom;
+    let owner = connection.get_selection_owner(atom)?.reply()?.owner;
+    Ok(owner != NONE)
+}
+
+/// 32bit (ARGB) の TrueColor ビジュアルを探します。
+fn find_argb_visual(screen: &Screen) -> Option<Visualid> {
+    screen
+        .allowed_depths
+        .iter()
+        .filter(|depth| depth.depth == 32)
+        .flat_map(|depth| depth.visuals.iter())
+        .find(|visual| visual.class == VisualClass::TRUE_COLOR)
+        .map(|visual| visual.visual_id)
+}
+
+/// タスクバーやページャーに表示しないようにウィンドウマネージャへ伝えます。
+///
+/// override-redirect のウィンドウは通常ウィンドウマネージャの管理外ですが、念のため設定しておきます。
+fn set_skip_taskbar(connection: &RustConnection, window: u32) -> Result<(), Error> {
+    let intern = |name: &str| -> Result<Atom, Error> {
+        Ok(connection
+            .intern_atom(false, name.as_bytes())?
+            .reply()?
+            .atom)
+    };
+    let state = intern("_NET_WM_STATE")?;
+    let values = [
+        intern("_NET_WM_STATE_SKIP_TASKBAR")?,
+        intern("_NET_WM_STATE_SKIP_PAGER")?,
+        intern("_NET_WM_STATE_ABOVE")?,
+    ];
+    connection.change_property32(PropMode::REPLACE, window, state, AtomEnum::ATOM, &values)?;
+    Ok(())
+}
+
+/// 矩形を X11 の形式に変換します。
+fn rectangle(rect: &Rect) -> Rectangle {
+    Rectangle {
+        x: rect.x as i16,
+        y: rect.y as i16,
+        width: rect.width as u16,
+        height: rect.height as u16,
+    }
+}
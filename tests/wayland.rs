@@ -0,0 +1,54 @@
+//! ヘッドレスのコンポジタ上で Wayland のバックエンドを確認するテストです。
+//!
+//! `wlr-layer-shell` に対応したコンポジタが必要なため、通常は実行しません。
+//! `scripts/headless-test.sh` から実行してください。
+#![cfg(target_os = "linux")]
+
+use bkrbk::backend::Backend;
+use bkrbk::hit::Mask;
+use bkrbk::wayland::{Anchor, Window};
+
+const BG: u32 = 0x00_00_00_FF;
+
+#[test]
+#[ignore = "requires a Wayland compositor with wlr-layer-shell (see scripts/headless-test.sh)"]
+fn layer_surface() {
+    let anchor = Anchor::Bottom | Anchor::Right;
+    let mut window = Window::new("bkrbk-test", 64, 48, BG, anchor, 10).unwrap();
+
+    // 右下の端から余白だけ離れた位置に表示する
+    let area = Backend::work_area(&window).expect("output size is unknown");
+    assert!(area.width >= 64 && area.height >= 48);
+    let expected = (area.width as i32 - 74, area.height as i32 - 58);
+    assert_eq!(Backend::position(&window), Some(expected));
+
+    // 表示して、左半分だけ不透明な画像を描画する
+    Backend::show(&mut window);
+    for (i, pixel) in Backend::image(&mut window).iter_mut().enumerate() {
+        *pixel = match i % 64 < 32 {
+            true => 0x00_FF_00_00,
+            false => BG,
+        };
+    }
+    let mask = Mask::from_image(&window.image, 64, BG);
+    assert_eq!(mask.rectangles().len(), 1);
+    Backend::set_input_mask(&mut window, &mask);
+    Backend::draw(&mut window);
+    assert!(Backend::update(&mut window).is_some());
+
+    // 移動すると左上の端が基準になる
+    Backend::move_to(&mut window, 20, 30);
+    assert_eq!(Backend::position(&window), Some((20, 30)));
+    assert!(Backend::update(&mut window).is_some());
+
+    // 吹き出し用のサーフェスを作成できる
+    let mut overlay = Backend::overlay(&window, 32, 16).expect("failed to create overlay");
+    overlay.move_to(5, 5);
+    overlay.show();
+    overlay.draw();
+    assert_eq!(overlay.position(), Some((5, 5)));
+    assert!(overlay.update().is_some());
+
+    Backend::hide(&mut window);
+    assert!(Backend::update(&mut window).is_some());
+}
@@ -0,0 +1,71 @@
+//! 仮想Xサーバー (Xvfb) 上で X11 のバックエンドを確認するテストです。
+//!
+//! Xサーバーが必要なため、通常は実行しません。
+//! `scripts/headless-test.sh` から実行してください。
+#![cfg(target_os = "linux")]
+
+use bkrbk::backend::Backend;
+use bkrbk::hit::Mask;
+use bkrbk::x11::{Transparency, Window};
+use x11rb::protocol::{shape::ConnectionExt as _, shape::SK, xproto::ConnectionExt as _};
+
+const BG: u32 = 0x00_00_00_FF;
+
+/// 別の接続からウィンドウの形 (描画範囲または入力範囲) を取得します。
+fn rectangles(window: u32, kind: SK) -> Vec<(i16, i16, u16, u16)> {
+    let (connection, _) = x11rb::connect(None).unwrap();
+    let reply = connection
+        .shape_get_rectangles(window, kind)
+        .unwrap()
+        .reply()
+        .unwrap();
+    reply
+        .rectangles
+        .iter()
+        .map(|r| (r.x, r.y, r.width, r.height))
+        .collect()
+}
+
+#[test]
+#[ignore = "requires an X server (see scripts/headless-test.sh)"]
+fn shaped_window() {
+    let mut window = Window::new("bkrbk-test", 64, 48, BG).unwrap();
+
+    // 描画するまではクリックを受け付けない
+    assert!(rectangles(window.window, SK::INPUT).is_empty());
+
+    // 表示して、左半分だけ不透明な画像を描画する
+    Backend::show(&mut window);
+    for (i, pixel) in Backend::image(&mut window).iter_mut().enumerate() {
+        *pixel = match i % 64 < 32 {
+            true => 0x00_FF_00_00,
+            false => BG,
+        };
+    }
+    Backend::draw(&mut window);
+    let mask = Mask::from_image(&window.image, 64, BG);
+    Backend::set_input_mask(&mut window, &mask);
+    assert!(Backend::update(&mut window).is_some());
+
+    // 入力範囲 (とコンポジットマネージャが無い場合は描画範囲) を不透明な部分に限定する
+    assert_eq!(rectangles(window.window, SK::INPUT), [(0, 0, 32, 48)]);
+    if window.transparency == Transparency::Shape {
+        assert_eq!(rectangles(window.window, SK::BOUNDING), [(0, 0, 32, 48)]);
+    }
+
+    // 移動した位置を取得できる
+    Backend::move_to(&mut window, 20, 30);
+    assert_eq!(Backend::position(&window), Some((20, 30)));
+    let (connection, _) = x11rb::connect(None).unwrap();
+    let geometry = connection
+        .get_geometry(window.window)
+        .unwrap()
+        .reply()
+        .unwrap();
+    assert_eq!((geometry.x, geometry.y), (20, 30));
+    assert!(Backend::work_area(&window).is_some());
+    assert!(Backend::pointer_position(&window).is_some());
+
+    Backend::hide(&mut window);
+    assert!(Backend::update(&mut window).is_some());
+}